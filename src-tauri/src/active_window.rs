@@ -0,0 +1,89 @@
+//! Which window has keyboard focus, for per-app mode rules and `{{active_app}}`.
+
+use crate::domain::types::FocusedWindow;
+
+/// Application class and title of the focused window, or `None` when it
+/// cannot be determined. Only X11 is supported so far.
+pub fn focused_window() -> Option<FocusedWindow> {
+  #[cfg(target_os = "linux")]
+  {
+    match x11::focused_window() {
+      Ok(window) => window,
+      Err(error) => {
+        tracing::debug!(%error, "could not read the focused X11 window");
+        None
+      }
+    }
+  }
+  #[cfg(not(target_os = "linux"))]
+  {
+    None
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+  use x11rb::connection::Connection;
+  use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt, Window};
+  use x11rb::rust_connection::RustConnection;
+
+  use crate::domain::types::FocusedWindow;
+
+  type X11Result<T> = Result<T, Box<dyn std::error::Error>>;
+
+  pub fn focused_window() -> X11Result<Option<FocusedWindow>> {
+    let (conn, screen) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen].root;
+    let active_atom = intern(&conn, b"_NET_ACTIVE_WINDOW")?;
+
+    let active = conn
+      .get_property(false, root, active_atom, AtomEnum::WINDOW, 0, 1)?
+      .reply()?;
+    let Some(window) = active.value32().and_then(|mut values| values.next()) else {
+      return Ok(None);
+    };
+    if window == 0 {
+      return Ok(None);
+    }
+
+    Ok(Some(FocusedWindow {
+      app: app_class(&conn, window)?,
+      title: title(&conn, window)?,
+    }))
+  }
+
+  fn intern(conn: &RustConnection, name: &[u8]) -> X11Result<Atom> {
+    Ok(conn.intern_atom(false, name)?.reply()?.atom)
+  }
+
+  /// WM_CLASS holds "instance\0class\0"; the class names the application.
+  fn app_class(conn: &RustConnection, window: Window) -> X11Result<Option<String>> {
+    let class = conn
+      .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+      .reply()?;
+    Ok(
+      class
+        .value
+        .split(|byte| *byte == 0)
+        .rfind(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).into_owned()),
+    )
+  }
+
+  /// `_NET_WM_NAME` (UTF-8), falling back to the legacy WM_NAME.
+  fn title(conn: &RustConnection, window: Window) -> X11Result<Option<String>> {
+    let properties: [(Atom, Atom); 2] = [
+      (intern(conn, b"_NET_WM_NAME")?, intern(conn, b"UTF8_STRING")?),
+      (AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()),
+    ];
+    for (property, kind) in properties {
+      let reply = conn
+        .get_property(false, window, property, kind, 0, 1024)?
+        .reply()?;
+      if !reply.value.is_empty() {
+        return Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()));
+      }
+    }
+    Ok(None)
+  }
+}
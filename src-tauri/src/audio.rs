@@ -1,13 +1,17 @@
-use std::io::Cursor;
+use std::ops::Range;
 use std::sync::{
     atomic::{AtomicBool, AtomicU32, Ordering},
-    Arc, Mutex,
+    mpsc, Arc, Mutex,
 };
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SampleFormat;
-use hound::{SampleFormat as WavSampleFormat, WavSpec, WavWriter};
+
+use crate::domain::error::DiktError;
+use crate::domain::types::{AudioChunk, AudioFormat, ChunkWindow, FrameSink};
+use crate::encoding;
 
 // Lock-free audio level metering: written by CPAL callback, read by emitter thread.
 const SILENT_DB: f32 = -60.0;
@@ -15,6 +19,26 @@ static LEVEL_RMS: AtomicU32 = AtomicU32::new(SILENT_DB.to_bits());
 static LEVEL_PEAK: AtomicU32 = AtomicU32::new(SILENT_DB.to_bits());
 static RECORDING_ACTIVE: AtomicBool = AtomicBool::new(false);
 
+// Voice activity detection: frame size, speech threshold bounds and padding kept around speech.
+const VAD_FRAME_MS: u32 = 20;
+const VAD_NOISE_MARGIN_DB: f32 = 10.0;
+const VAD_MIN_THRESHOLD_DB: f32 = -50.0;
+const VAD_MAX_THRESHOLD_DB: f32 = -35.0;
+const VAD_MIN_SPEECH_FRAMES: usize = 3;
+const VAD_PADDING_MS: u32 = 250;
+
+// Long recordings are uploaded in pieces to stay under provider size and time limits.
+const CHUNK_TARGET_SECS: u32 = 60;
+const CHUNK_SEARCH_SECS: u32 = 10;
+const CHUNK_OVERLAP_MS: u32 = 500;
+
+// Live frames are forwarded in ~100 ms batches to keep WebSocket traffic modest.
+const FRAME_FORWARD_MS: u32 = 100;
+// How long `start` waits for the input stream to open before assuming it will.
+const CAPTURE_START_TIMEOUT: Duration = Duration::from_secs(2);
+// Set by the capture thread when it has to switch input devices; drained by the emitter thread.
+static DEVICE_WARNING: Mutex<Option<String>> = Mutex::new(None);
+
 /// Read the current audio level (RMS and peak in dB). Returns (-60, -60) when silent.
 pub fn current_level() -> (f32, f32) {
     (
@@ -28,16 +52,55 @@ pub fn is_recording() -> bool {
     RECORDING_ACTIVE.load(Ordering::Relaxed)
 }
 
+/// Take the pending input device warning, if the capture thread raised one.
+pub fn take_device_warning() -> Option<String> {
+    DEVICE_WARNING.lock().ok().and_then(|mut guard| guard.take())
+}
+
+fn set_device_warning(message: String) {
+    tracing::warn!("{message}");
+    if let Ok(mut guard) = DEVICE_WARNING.lock() {
+        *guard = Some(message);
+    }
+}
+
+/// List the names of the input devices available on the default host.
+pub fn list_input_devices() -> Result<Vec<String>, DiktError> {
+    let host = cpal::default_host();
+    let devices = host
+        .input_devices()
+        .map_err(|e| capture_error(format!("Failed to enumerate input devices: {e}")))?;
+
+    let mut names: Vec<String> = devices.filter_map(|device| device.name().ok()).collect();
+    names.sort_unstable();
+    names.dedup();
+    Ok(names)
+}
+
 fn reset_levels() {
     LEVEL_RMS.store(SILENT_DB.to_bits(), Ordering::Relaxed);
     LEVEL_PEAK.store(SILENT_DB.to_bits(), Ordering::Relaxed);
 }
 
+/// Capture options for a single recording, resolved from settings when it starts.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingOptions {
+    /// Input device name; `None` uses the system default.
+    pub input_device: Option<String>,
+    /// Sample rate of the encoded output. `0` keeps the device rate.
+    pub target_sample_rate: u32,
+    /// Container/codec used for the upload.
+    pub format: AudioFormat,
+    /// Receives live audio for streaming transcription while recording.
+    pub frames: Option<FrameSink>,
+}
+
 pub struct AudioRecorder {
     samples: Arc<Mutex<Vec<i16>>>,
     recording: Arc<AtomicBool>,
     thread_handle: Mutex<Option<JoinHandle<()>>>,
-    sample_rate: Mutex<u32>,
+    sample_rate: Arc<Mutex<u32>>,
+    options: Mutex<RecordingOptions>,
 }
 
 impl Default for AudioRecorder {
@@ -46,13 +109,14 @@ impl Default for AudioRecorder {
             samples: Arc::new(Mutex::new(Vec::new())),
             recording: Arc::new(AtomicBool::new(false)),
             thread_handle: Mutex::new(None),
-            sample_rate: Mutex::new(16000),
+            sample_rate: Arc::new(Mutex::new(16000)),
+            options: Mutex::new(RecordingOptions::default()),
         }
     }
 }
 
 impl AudioRecorder {
-    pub fn start(&self) -> Result<(), String> {
+    pub fn start(&self, mut options: RecordingOptions) -> Result<(), DiktError> {
         if self.recording.load(Ordering::SeqCst) {
             return Ok(());
         }
@@ -64,28 +128,45 @@ impl AudioRecorder {
 
         let samples = Arc::clone(&self.samples);
         let recording = Arc::clone(&self.recording);
-        let sample_rate_holder = Arc::new(Mutex::new(16000u32));
-        let sample_rate_for_thread = Arc::clone(&sample_rate_holder);
+        let sample_rate_for_thread = Arc::clone(&self.sample_rate);
+        let input_device = options.input_device.clone();
+        // The capture thread owns the sink so the stream ends when capture does.
+        let frames = options.frames.take();
+        *self.options.lock().unwrap() = options;
 
+        let (started_tx, started_rx) = mpsc::channel();
         let handle = thread::spawn(move || {
-            if let Err(e) = run_audio_capture(samples, recording, sample_rate_for_thread) {
-                eprintln!("Audio capture error: {e}");
+            if let Err(e) = run_audio_capture(
+                samples,
+                recording,
+                sample_rate_for_thread,
+                input_device,
+                frames,
+                &started_tx,
+            ) {
+                tracing::error!(error = %e, "audio capture failed");
+                let _ = started_tx.send(Err(e));
             }
         });
 
         *self.thread_handle.lock().unwrap() = Some(handle);
 
-        // Give the thread a moment to start and set the sample rate
-        thread::sleep(std::time::Duration::from_millis(100));
-
-        if let Ok(rate) = sample_rate_holder.lock() {
-            *self.sample_rate.lock().unwrap() = *rate;
+        // Wait for the stream to play, so a missing microphone or permission fails
+        // the recording instead of turning up later as an empty one.
+        match started_rx.recv_timeout(CAPTURE_START_TIMEOUT) {
+            Ok(Err(e)) => {
+                self.recording.store(false, Ordering::SeqCst);
+                RECORDING_ACTIVE.store(false, Ordering::Relaxed);
+                if let Some(handle) = self.thread_handle.lock().unwrap().take() {
+                    let _ = handle.join();
+                }
+                Err(capture_error(e))
+            }
+            _ => Ok(()),
         }
-
-        Ok(())
     }
 
-    pub fn stop(&self) -> Result<Vec<u8>, String> {
+    pub fn stop(&self) -> Result<Vec<AudioChunk>, DiktError> {
         self.recording.store(false, Ordering::SeqCst);
         RECORDING_ACTIVE.store(false, Ordering::Relaxed);
         reset_levels();
@@ -97,29 +178,25 @@ impl AudioRecorder {
 
         let samples = self.samples.lock().unwrap();
         if samples.is_empty() {
-            return Err("No audio captured".to_string());
+            return Err(DiktError::NoAudio("No audio captured".to_string()));
         }
 
         let sample_rate = *self.sample_rate.lock().unwrap();
 
-        let spec = WavSpec {
-            channels: 1,
-            sample_rate: sample_rate.max(1),
-            bits_per_sample: 16,
-            sample_format: WavSampleFormat::Int,
-        };
+        // Drop leading/trailing silence; a recording with no speech never reaches the API.
+        let speech = speech_bounds(&samples, sample_rate)
+            .ok_or_else(|| DiktError::NoAudio("No speech detected".to_string()))?;
 
-        let mut wav_buffer = Vec::new();
-        {
-            let mut writer =
-                WavWriter::new(Cursor::new(&mut wav_buffer), spec).map_err(|e| e.to_string())?;
-            for &sample in samples.iter() {
-                writer.write_sample(sample).map_err(|e| e.to_string())?;
-            }
-            writer.finalize().map_err(|e| e.to_string())?;
-        }
+        let options = self.options.lock().unwrap().clone();
+        // Never upsample: a higher target rate only inflates the upload.
+        let target_rate = match options.target_sample_rate {
+            0 => sample_rate,
+            rate => rate.min(sample_rate),
+        };
+        let target_rate = encoding::supported_sample_rate(options.format, target_rate);
+        let samples = resample(&samples[speech], sample_rate, target_rate);
 
-        Ok(wav_buffer)
+        encode_chunks(&samples, target_rate, options.format).map_err(DiktError::Other)
     }
 }
 
@@ -127,92 +204,424 @@ fn run_audio_capture(
     samples: Arc<Mutex<Vec<i16>>>,
     recording: Arc<AtomicBool>,
     sample_rate_holder: Arc<Mutex<u32>>,
+    input_device: Option<String>,
+    frames: Option<FrameSink>,
+    started: &mpsc::Sender<Result<(), String>>,
 ) -> Result<(), String> {
+    let mut forwarder = frames.map(FrameForwarder::new);
     let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .ok_or("No input device available")?;
+    let device = match input_device.as_deref() {
+        Some(name) => match find_input_device(&host, name) {
+            Some(device) => device,
+            None => {
+                set_device_warning(format!(
+                    "Input device '{name}' not found, using the default microphone"
+                ));
+                default_input_device(&host)?
+            }
+        },
+        None => default_input_device(&host)?,
+    };
 
     let supported_config = device
         .default_input_config()
         .map_err(|e| format!("Failed to get input config: {e}"))?;
 
-    let sample_format = supported_config.sample_format();
-    let config: cpal::StreamConfig = supported_config.into();
+    let mut sample_format = supported_config.sample_format();
+    let mut config: cpal::StreamConfig = supported_config.into();
 
     *sample_rate_holder.lock().unwrap() = config.sample_rate.0;
-    let channels = config.channels as usize;
-
-    let samples_clone = Arc::clone(&samples);
-    let recording_clone = Arc::clone(&recording);
 
-    let err_fn = |err| eprintln!("Audio stream error: {err}");
-
-    let stream = match sample_format {
-        SampleFormat::I16 => device
-            .build_input_stream(
-                &config,
-                move |data: &[i16], _| {
-                    if recording_clone.load(Ordering::SeqCst) {
-                        push_mono_i16(&samples_clone, data, channels, |v| v);
-                    }
-                },
-                err_fn,
-                None,
-            )
-            .map_err(|e| format!("Failed to build input stream: {e}"))?,
-        SampleFormat::U16 => {
-            let samples_clone = Arc::clone(&samples);
-            let recording_clone = Arc::clone(&recording);
-            device
-                .build_input_stream(
-                    &config,
-                    move |data: &[u16], _| {
-                        if recording_clone.load(Ordering::SeqCst) {
-                            push_mono_i16(&samples_clone, data, channels, |v| {
-                                (v as i32 - 32768) as i16
-                            });
-                        }
-                    },
-                    err_fn,
-                    None,
-                )
-                .map_err(|e| format!("Failed to build input stream: {e}"))?
-        }
-        SampleFormat::F32 => {
-            let samples_clone = Arc::clone(&samples);
-            let recording_clone = Arc::clone(&recording);
-            device
-                .build_input_stream(
-                    &config,
-                    move |data: &[f32], _| {
-                        if recording_clone.load(Ordering::SeqCst) {
-                            push_mono_i16(&samples_clone, data, channels, |v| {
-                                let clamped = v.clamp(-1.0, 1.0);
-                                (clamped * i16::MAX as f32) as i16
-                            });
-                        }
-                    },
-                    err_fn,
-                    None,
-                )
-                .map_err(|e| format!("Failed to build input stream: {e}"))?
-        }
-        _ => return Err("Unsupported sample format".to_string()),
-    };
+    let device_lost = Arc::new(AtomicBool::new(false));
+    let mut stream = build_capture_stream(
+        &device,
+        &config,
+        sample_format,
+        &samples,
+        &recording,
+        &device_lost,
+    )?;
 
     stream
         .play()
         .map_err(|e| format!("Failed to start input stream: {e}"))?;
+    let _ = started.send(Ok(()));
 
     // Keep the stream alive while recording
     while recording.load(Ordering::SeqCst) {
+        if device_lost.swap(false, Ordering::SeqCst) {
+            // Release the dead stream before opening the replacement device.
+            drop(stream);
+
+            let fallback = match default_input_device(&host) {
+                Ok(device) => device,
+                Err(e) => {
+                    set_device_warning(format!(
+                        "Microphone disconnected and no fallback is available: {e}"
+                    ));
+                    return Err(e);
+                }
+            };
+            set_device_warning(
+                "Microphone disconnected, switched to the default input device".to_string(),
+            );
+
+            let (fallback_config, fallback_format) = fallback_input_config(&fallback, &config)?;
+            if fallback_config.sample_rate != config.sample_rate {
+                // Keep the buffer at a single rate so the WAV header stays truthful.
+                let mut buffer = samples.lock().unwrap();
+                let resampled = resample(
+                    &buffer,
+                    config.sample_rate.0,
+                    fallback_config.sample_rate.0,
+                );
+                *buffer = resampled;
+                *sample_rate_holder.lock().unwrap() = fallback_config.sample_rate.0;
+                if let Some(forwarder) = forwarder.as_mut() {
+                    forwarder.rebase(config.sample_rate.0, fallback_config.sample_rate.0);
+                }
+            }
+
+            config = fallback_config;
+            sample_format = fallback_format;
+            stream = build_capture_stream(
+                &fallback,
+                &config,
+                sample_format,
+                &samples,
+                &recording,
+                &device_lost,
+            )?;
+            stream
+                .play()
+                .map_err(|e| format!("Failed to start input stream: {e}"))?;
+        }
+
+        if let Some(forwarder) = forwarder.as_mut() {
+            forwarder.forward(&samples.lock().unwrap(), config.sample_rate.0, false);
+        }
+
         thread::sleep(std::time::Duration::from_millis(10));
     }
 
+    if let Some(forwarder) = forwarder.as_mut() {
+        forwarder.forward(&samples.lock().unwrap(), config.sample_rate.0, true);
+    }
+
     Ok(())
 }
 
+/// Sends newly captured audio to a streaming transcriber at the rate it expects.
+struct FrameForwarder {
+    sink: FrameSink,
+    sent: usize,
+}
+
+impl FrameForwarder {
+    fn new(sink: FrameSink) -> Self {
+        Self { sink, sent: 0 }
+    }
+
+    /// Forward whatever has been captured since the last call.
+    ///
+    /// Only whole resampling blocks are sent until `flush`, so every batch
+    /// converts to an exact number of output samples and no drift builds up.
+    fn forward(&mut self, samples: &[i16], sample_rate: u32, flush: bool) {
+        let pending = samples.len().saturating_sub(self.sent);
+        let block = (sample_rate / gcd(sample_rate, self.sink.sample_rate)).max(1) as usize;
+        let batch = if flush {
+            pending
+        } else if pending < (sample_rate * FRAME_FORWARD_MS / 1000) as usize {
+            0
+        } else {
+            pending / block * block
+        };
+        if batch == 0 {
+            return;
+        }
+
+        let frame = resample(
+            &samples[self.sent..self.sent + batch],
+            sample_rate,
+            self.sink.sample_rate,
+        );
+        self.sent += batch;
+        // A closed receiver just means the live transcript was abandoned.
+        let _ = self.sink.sender.send(frame);
+    }
+
+    /// Keep the read position valid after the capture buffer was resampled.
+    fn rebase(&mut self, from_rate: u32, to_rate: u32) {
+        self.sent = (self.sent as u64 * to_rate as u64 / from_rate.max(1) as u64) as usize;
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Classify a failure to open the microphone; the OS refusing access shows up
+/// only in the backend's message (ALSA's EACCES, WASAPI's E_ACCESSDENIED).
+fn capture_error(message: String) -> DiktError {
+    let lower = message.to_lowercase();
+    if lower.contains("permission denied") || lower.contains("access is denied") {
+        DiktError::Permission(format!("Microphone access denied: {message}"))
+    } else {
+        DiktError::Other(message)
+    }
+}
+
+fn default_input_device(host: &cpal::Host) -> Result<cpal::Device, String> {
+    host.default_input_device()
+        .ok_or_else(|| "No input device available".to_string())
+}
+
+fn find_input_device(host: &cpal::Host, name: &str) -> Option<cpal::Device> {
+    host.input_devices()
+        .ok()?
+        .find(|device| device.name().map(|n| n == name).unwrap_or(false))
+}
+
+/// Pick a config for the fallback device, preferring the sample rate already in use.
+fn fallback_input_config(
+    device: &cpal::Device,
+    current: &cpal::StreamConfig,
+) -> Result<(cpal::StreamConfig, SampleFormat), String> {
+    if let Ok(mut ranges) = device.supported_input_configs() {
+        let matching = ranges.find(|range| {
+            matches!(
+                range.sample_format(),
+                SampleFormat::I16 | SampleFormat::U16 | SampleFormat::F32
+            ) && range.min_sample_rate() <= current.sample_rate
+                && range.max_sample_rate() >= current.sample_rate
+        });
+        if let Some(range) = matching {
+            let supported = range.with_sample_rate(current.sample_rate);
+            let sample_format = supported.sample_format();
+            return Ok((supported.into(), sample_format));
+        }
+    }
+
+    let supported = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get input config: {e}"))?;
+    let sample_format = supported.sample_format();
+    Ok((supported.into(), sample_format))
+}
+
+fn build_capture_stream(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sample_format: SampleFormat,
+    samples: &Arc<Mutex<Vec<i16>>>,
+    recording: &Arc<AtomicBool>,
+    device_lost: &Arc<AtomicBool>,
+) -> Result<cpal::Stream, String> {
+    let channels = config.channels as usize;
+    let samples_clone = Arc::clone(samples);
+    let recording_clone = Arc::clone(recording);
+    let device_lost = Arc::clone(device_lost);
+
+    let err_fn = move |err: cpal::StreamError| match err {
+        cpal::StreamError::DeviceNotAvailable => device_lost.store(true, Ordering::SeqCst),
+        other => tracing::warn!(error = %other, "audio stream error"),
+    };
+
+    let stream = match sample_format {
+        SampleFormat::I16 => device.build_input_stream(
+            config,
+            move |data: &[i16], _| {
+                if recording_clone.load(Ordering::SeqCst) {
+                    push_mono_i16(&samples_clone, data, channels, |v| v);
+                }
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::U16 => device.build_input_stream(
+            config,
+            move |data: &[u16], _| {
+                if recording_clone.load(Ordering::SeqCst) {
+                    push_mono_i16(&samples_clone, data, channels, |v| {
+                        (v as i32 - 32768) as i16
+                    });
+                }
+            },
+            err_fn,
+            None,
+        ),
+        SampleFormat::F32 => device.build_input_stream(
+            config,
+            move |data: &[f32], _| {
+                if recording_clone.load(Ordering::SeqCst) {
+                    push_mono_i16(&samples_clone, data, channels, |v| {
+                        let clamped = v.clamp(-1.0, 1.0);
+                        (clamped * i16::MAX as f32) as i16
+                    });
+                }
+            },
+            err_fn,
+            None,
+        ),
+        _ => return Err("Unsupported sample format".to_string()),
+    };
+
+    stream.map_err(|e| format!("Failed to build input stream: {e}"))
+}
+
+/// Resample a mono buffer from one rate to another.
+///
+/// Downsampling averages the source samples covered by each output sample,
+/// which acts as a simple low-pass filter against aliasing. Upsampling uses
+/// linear interpolation.
+pub fn resample(input: &[i16], from_rate: u32, to_rate: u32) -> Vec<i16> {
+    if input.is_empty() || from_rate == 0 || to_rate == 0 || from_rate == to_rate {
+        return input.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let out_len = ((input.len() as f64) / ratio).round() as usize;
+    let last = input.len() - 1;
+
+    if ratio > 1.0 {
+        return (0..out_len)
+            .map(|i| {
+                let start = ((i as f64 * ratio) as usize).min(last);
+                let end = (((i + 1) as f64 * ratio) as usize).clamp(start + 1, input.len());
+                let window = &input[start..end];
+                let sum: i64 = window.iter().map(|&s| s as i64).sum();
+                (sum / window.len() as i64) as i16
+            })
+            .collect();
+    }
+
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let idx = (pos.floor() as usize).min(last);
+            let next = (idx + 1).min(last);
+            let frac = pos - idx as f64;
+            let value = input[idx] as f64 + (input[next] as f64 - input[idx] as f64) * frac;
+            value.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+        })
+        .collect()
+}
+
+/// Convert a linear amplitude into dBFS, floored at `SILENT_DB`.
+fn amplitude_db(amplitude: f64) -> f32 {
+    if amplitude > 0.0 {
+        (20.0 * (amplitude / i16::MAX as f64).log10()).max(SILENT_DB as f64) as f32
+    } else {
+        SILENT_DB
+    }
+}
+
+fn rms_db(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return SILENT_DB;
+    }
+    let sum_sq: f64 = samples.iter().map(|&s| (s as f64) * (s as f64)).sum();
+    amplitude_db((sum_sq / samples.len() as f64).sqrt())
+}
+
+/// Find the sample range that contains speech, padded on both sides.
+///
+/// Frames are classified by RMS level against a threshold derived from the
+/// recording's own noise floor. Returns `None` when no speech is found.
+fn speech_bounds(samples: &[i16], sample_rate: u32) -> Option<Range<usize>> {
+    let frame_len = ((sample_rate * VAD_FRAME_MS / 1000) as usize).max(1);
+    let levels: Vec<f32> = samples.chunks(frame_len).map(rms_db).collect();
+    if levels.is_empty() {
+        return None;
+    }
+
+    let mut sorted = levels.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted[sorted.len() / 10];
+    let threshold =
+        (noise_floor + VAD_NOISE_MARGIN_DB).clamp(VAD_MIN_THRESHOLD_DB, VAD_MAX_THRESHOLD_DB);
+
+    let voiced = levels.iter().filter(|&&level| level >= threshold).count();
+    if voiced < VAD_MIN_SPEECH_FRAMES {
+        return None;
+    }
+
+    let first = levels.iter().position(|&level| level >= threshold)?;
+    let last = levels.iter().rposition(|&level| level >= threshold)?;
+
+    let padding = (sample_rate * VAD_PADDING_MS / 1000) as usize;
+    let start = (first * frame_len).saturating_sub(padding);
+    let end = ((last + 1) * frame_len + padding).min(samples.len());
+    Some(start..end)
+}
+
+/// Pick the sample offsets where a long recording should be cut.
+///
+/// Each cut lands on the quietest frame in the last few seconds before the
+/// chunk target, so words are rarely split. Short recordings yield no cuts.
+fn chunk_boundaries(samples: &[i16], sample_rate: u32) -> Vec<usize> {
+    let target = (sample_rate * CHUNK_TARGET_SECS) as usize;
+    let search = (sample_rate * CHUNK_SEARCH_SECS) as usize;
+    let frame_len = ((sample_rate * VAD_FRAME_MS / 1000) as usize).max(1);
+    if target == 0 {
+        return Vec::new();
+    }
+
+    let mut cuts = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > target {
+        let window_end = start + target;
+        let window_start = window_end - search.min(target - frame_len);
+        let quietest = (window_start..window_end - frame_len)
+            .step_by(frame_len)
+            .map(|frame| (frame, rms_db(&samples[frame..frame + frame_len])))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(frame, _)| frame + frame_len / 2)
+            .unwrap_or(window_end);
+        cuts.push(quietest);
+        start = quietest;
+    }
+    cuts
+}
+
+/// Encode a recording as one or more overlapping chunks.
+///
+/// Every chunk extends a little past its cut points so a word straddling a
+/// cut is heard in full by at least one side.
+fn encode_chunks(
+    samples: &[i16],
+    sample_rate: u32,
+    format: AudioFormat,
+) -> Result<Vec<AudioChunk>, String> {
+    let overlap = (sample_rate * CHUNK_OVERLAP_MS / 1000) as usize;
+    let secs = |index: usize| index as f64 / sample_rate.max(1) as f64;
+
+    let mut bounds = vec![0];
+    bounds.extend(chunk_boundaries(samples, sample_rate));
+    bounds.push(samples.len());
+
+    bounds
+        .windows(2)
+        .map(|pair| {
+            let (keep_start, keep_end) = (pair[0], pair[1]);
+            let start = keep_start.saturating_sub(overlap);
+            let end = (keep_end + overlap).min(samples.len());
+            Ok(AudioChunk {
+                audio: encoding::encode(&samples[start..end], sample_rate, format)?,
+                window: ChunkWindow {
+                    offset_secs: secs(start),
+                    keep_from_secs: secs(keep_start),
+                    keep_until_secs: (keep_end < samples.len()).then(|| secs(keep_end)),
+                },
+            })
+        })
+        .collect()
+}
+
 fn push_mono_i16<T, F>(samples: &Arc<Mutex<Vec<i16>>>, data: &[T], channels: usize, convert: F)
 where
     T: Copy,
@@ -248,19 +657,130 @@ where
     }
 
     if frame_count > 0 {
-        let norm = i16::MAX as f64;
-        let rms = (sum_sq / frame_count as f64).sqrt();
-        let rms_db = if rms > 0.0 {
-            (20.0 * (rms / norm).log10()).max(-60.0) as f32
-        } else {
-            SILENT_DB
-        };
-        let peak_db = if max_abs > 0 {
-            (20.0 * (max_abs as f64 / norm).log10()).max(-60.0) as f32
-        } else {
-            SILENT_DB
-        };
+        let rms_db = amplitude_db((sum_sq / frame_count as f64).sqrt());
+        let peak_db = amplitude_db(max_abs as f64);
         LEVEL_RMS.store(rms_db.to_bits(), Ordering::Relaxed);
         LEVEL_PEAK.store(peak_db.to_bits(), Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(len: usize, amplitude: f64) -> Vec<i16> {
+        (0..len)
+            .map(|i| ((i as f64 * 0.1).sin() * amplitude) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn refused_microphone_is_a_permission_error() {
+        let alsa = "ALSA function 'snd_pcm_open' failed with error 'EACCES: Permission denied'";
+        assert!(matches!(capture_error(alsa.to_string()), DiktError::Permission(_)));
+        let wasapi = "Access is denied. (0x80070005)";
+        assert!(matches!(capture_error(wasapi.to_string()), DiktError::Permission(_)));
+        assert!(matches!(
+            capture_error("No input device available".to_string()),
+            DiktError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn speech_bounds_rejects_silence() {
+        let samples = vec![0i16; 16_000];
+        assert!(speech_bounds(&samples, 16_000).is_none());
+    }
+
+    #[test]
+    fn speech_bounds_rejects_steady_noise() {
+        let samples = tone(16_000, 40.0);
+        assert!(speech_bounds(&samples, 16_000).is_none());
+    }
+
+    #[test]
+    fn speech_bounds_trims_leading_and_trailing_silence() {
+        let mut samples = vec![0i16; 16_000];
+        samples.extend(tone(8_000, 8_000.0));
+        samples.extend(vec![0i16; 16_000]);
+
+        let bounds = speech_bounds(&samples, 16_000).unwrap();
+        // 250 ms of padding on each side of the voiced region.
+        assert_eq!(bounds, 12_000..28_000);
+    }
+
+    #[test]
+    fn speech_bounds_keeps_recording_without_silence() {
+        let samples = tone(16_000, 8_000.0);
+        assert_eq!(speech_bounds(&samples, 16_000), Some(0..16_000));
+    }
+
+    #[test]
+    fn resample_scales_length() {
+        let samples = tone(48_000, 1_000.0);
+        assert_eq!(resample(&samples, 48_000, 16_000).len(), 16_000);
+        assert_eq!(resample(&samples, 44_100, 16_000).len(), 17_415);
+        assert_eq!(resample(&samples, 16_000, 48_000).len(), 144_000);
+        assert_eq!(resample(&samples, 48_000, 48_000), samples);
+    }
+
+    #[test]
+    fn short_recordings_are_not_chunked() {
+        let samples = tone(16_000 * 30, 8_000.0);
+        assert!(chunk_boundaries(&samples, 16_000).is_empty());
+    }
+
+    #[test]
+    fn chunks_are_cut_at_the_quietest_point() {
+        // Speech with a short pause at 55 s, inside the search window.
+        let mut samples = tone(16_000 * 55, 8_000.0);
+        samples.extend(vec![0i16; 8_000]);
+        samples.extend(tone(16_000 * 30, 8_000.0));
+
+        let cuts = chunk_boundaries(&samples, 16_000);
+        assert_eq!(cuts.len(), 1);
+        assert!((16_000 * 55..16_000 * 55 + 8_000).contains(&cuts[0]));
+    }
+
+    #[test]
+    fn chunks_overlap_and_cover_the_recording() {
+        let samples = tone(16_000 * 150, 8_000.0);
+        let chunks = encode_chunks(&samples, 16_000, AudioFormat::Wav).unwrap();
+        assert_eq!(chunks.len(), 3);
+
+        assert_eq!(chunks[0].window.keep_from_secs, 0.0);
+        assert_eq!(chunks[2].window.keep_until_secs, None);
+        for pair in chunks.windows(2) {
+            let cut = pair[0].window.keep_until_secs.unwrap();
+            assert_eq!(pair[1].window.keep_from_secs, cut);
+            assert!((pair[1].window.offset_secs - (cut - 0.5)).abs() < 1e-9);
+            let first_end = pair[0].window.offset_secs + pair[0].audio.duration_secs.unwrap();
+            assert!(first_end > cut);
+        }
+    }
+
+    #[test]
+    fn forwarder_sends_whole_blocks_at_the_sink_rate() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut forwarder = FrameForwarder::new(FrameSink {
+            sender,
+            sample_rate: 16_000,
+        });
+        let samples = tone(44_100, 1_000.0);
+
+        // 100 ms at 44.1 kHz is 4410 samples, exactly 10 blocks of 441.
+        forwarder.forward(&samples[..4_500], 44_100, false);
+        assert_eq!(receiver.try_recv().unwrap().len(), 1_600);
+
+        forwarder.forward(&samples, 44_100, true);
+        assert_eq!(receiver.try_recv().unwrap().len(), 14_400);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn resample_preserves_dc_level() {
+        let samples = vec![1_000i16; 4_800];
+        let resampled = resample(&samples, 48_000, 16_000);
+        assert!(resampled.iter().all(|&s| s == 1_000));
+    }
+}
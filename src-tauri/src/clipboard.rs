@@ -4,8 +4,10 @@ use std::time::Duration;
 use arboard::Clipboard;
 use enigo::{Direction::{Click, Press, Release}, Enigo, Key, Keyboard, Settings};
 
+use crate::domain::error::DiktError;
+
 #[cfg(target_os = "macos")]
-fn wrap_accessibility_error(message: String) -> String {
+fn wrap_accessibility_error(message: String) -> DiktError {
   let normalized = message.to_ascii_lowercase();
   let permission_error = normalized.contains("accessibility")
     || normalized.contains("not permitted")
@@ -14,32 +16,36 @@ fn wrap_accessibility_error(message: String) -> String {
     || normalized.contains("trust");
 
   if permission_error {
-    "Accessibility permission required. Grant access to Dikt in System Settings > Privacy & Security > Accessibility, then restart the app.".to_string()
+    DiktError::Permission("Accessibility permission required. Grant access to Dikt in System Settings > Privacy & Security > Accessibility, then restart the app.".to_string())
   } else {
-    message
+    DiktError::Paste(message)
   }
 }
 
 #[cfg(not(target_os = "macos"))]
-fn wrap_accessibility_error(message: String) -> String {
-  message
+fn wrap_accessibility_error(message: String) -> DiktError {
+  if message.to_ascii_lowercase().contains("permission denied") {
+    DiktError::Permission(message)
+  } else {
+    DiktError::Paste(message)
+  }
 }
 
-pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
-  let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
-  clipboard.set_text(text).map_err(|e| e.to_string())?;
+pub fn copy_to_clipboard(text: &str) -> Result<(), DiktError> {
+  let mut clipboard = Clipboard::new().map_err(|e| DiktError::Paste(e.to_string()))?;
+  clipboard.set_text(text).map_err(|e| DiktError::Paste(e.to_string()))?;
   Ok(())
 }
 
-pub fn copy_and_paste(text: &str, restore_clipboard: bool) -> Result<(), String> {
-  let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+pub fn copy_and_paste(text: &str, restore_clipboard: bool) -> Result<(), DiktError> {
+  let mut clipboard = Clipboard::new().map_err(|e| DiktError::Paste(e.to_string()))?;
   let original_clipboard = if restore_clipboard {
     clipboard.get_text().ok()
   } else {
     None
   };
 
-  clipboard.set_text(text).map_err(|e| e.to_string())?;
+  clipboard.set_text(text).map_err(|e| DiktError::Paste(e.to_string()))?;
 
   thread::sleep(Duration::from_millis(50));
 
@@ -51,12 +57,55 @@ pub fn copy_and_paste(text: &str, restore_clipboard: bool) -> Result<(), String>
 
   if let Some(original_text) = original_clipboard {
     thread::sleep(Duration::from_millis(50));
-    clipboard.set_text(original_text).map_err(|e| e.to_string())?;
+    clipboard.set_text(original_text).map_err(|e| DiktError::Paste(e.to_string()))?;
   }
 
   Ok(())
 }
 
+/// Current clipboard text, if the clipboard holds any.
+pub fn clipboard_text() -> Option<String> {
+  Clipboard::new().ok()?.get_text().ok()
+}
+
+/// Copy the selection in the focused app and return it, putting the previous
+/// clipboard text back afterwards, also when copying fails. `None` when nothing
+/// is selected.
+pub fn copy_selection() -> Result<Option<String>, DiktError> {
+  let mut clipboard = Clipboard::new().map_err(|e| DiktError::Paste(e.to_string()))?;
+  let original_text = clipboard.get_text().ok();
+  // Cleared first so an empty selection is not mistaken for the old clipboard.
+  // Anything other than text is left alone, since it could not be put back.
+  if original_text.is_some() {
+    let _ = clipboard.clear();
+  }
+
+  let selection = press_copy().map(|()| {
+    thread::sleep(Duration::from_millis(100));
+    clipboard.get_text().ok().filter(|text| !text.is_empty())
+  });
+
+  let restored = match &original_text {
+    Some(text) => clipboard.set_text(text.clone()),
+    // The copied selection replaced whatever was there; leave the clipboard empty.
+    None if matches!(selection, Ok(Some(_))) => clipboard.clear(),
+    None => Ok(()),
+  };
+  let selection = selection?;
+  restored.map_err(|e| DiktError::Paste(e.to_string()))?;
+  Ok(selection)
+}
+
+fn press_copy() -> Result<(), DiktError> {
+  let mut enigo = Enigo::new(&Settings::default()).map_err(|e| wrap_accessibility_error(e.to_string()))?;
+  let modifier = paste_modifier_key();
+  enigo.key(modifier, Press).map_err(|e| wrap_accessibility_error(e.to_string()))?;
+  let copied = enigo.key(Key::Unicode('c'), Click).map_err(|e| wrap_accessibility_error(e.to_string()));
+  // Release the modifier even if the click failed, so it is not left held down.
+  let released = enigo.key(modifier, Release).map_err(|e| wrap_accessibility_error(e.to_string()));
+  copied.and(released)
+}
+
 fn paste_modifier_key() -> Key {
   // macOS uses Command, Windows/Linux use Control.
   #[cfg(target_os = "macos")]
@@ -1,9 +1,14 @@
 use tauri::{AppHandle, Emitter, Manager, PhysicalPosition, State, WebviewWindow};
 
-use crate::domain::types::VocabularyEntry;
+use crate::domain::error::DiktError;
+use crate::domain::types::{
+    DictationState, DictationUpdate, FormatterBackend, Mode, VocabularyEntry,
+};
+use crate::format_text::ChatEndpoint;
 use crate::settings::AppSettings;
 use crate::state::AppState;
-use crate::transcription_history::TranscriptionHistoryItem;
+use crate::transcribe_local;
+use crate::transcription_history::{LatencySummary, TranscriptionHistoryItem};
 
 #[derive(serde::Serialize, Clone)]
 struct AudioLevelPayload {
@@ -11,6 +16,11 @@ struct AudioLevelPayload {
     peak_db: f32,
 }
 
+#[derive(serde::Serialize, Clone)]
+struct DeferredDictationPayload {
+    text: String,
+}
+
 /// Full recovery for the main window: re-apply layered attributes, ensure
 /// always-on-top, and show the window. Called by reset_position and the
 /// periodic watchdog.
@@ -30,8 +40,12 @@ pub fn update_hit_region(
     Ok(())
 }
 
+// Async so streaming transcription can be spawned onto the runtime.
 #[tauri::command]
-pub fn start_recording(window: WebviewWindow, state: State<'_, AppState>) -> Result<(), String> {
+pub async fn start_recording(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+) -> Result<(), DiktError> {
     let window = window.clone();
     state.manager.start_recording(move |update| {
         let _ = window.emit("dictation:update", update);
@@ -42,7 +56,7 @@ pub fn start_recording(window: WebviewWindow, state: State<'_, AppState>) -> Res
 pub async fn stop_and_transcribe(
     window: WebviewWindow,
     state: State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<String, DiktError> {
     let window = window.clone();
     let app = window.app_handle();
     let update_window = window.clone();
@@ -53,6 +67,30 @@ pub async fn stop_and_transcribe(
         })
         .await;
 
+    emit_history_events(app, &result);
+    result
+}
+
+#[tauri::command]
+pub async fn retry_last_dictation(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+) -> Result<String, DiktError> {
+    let window = window.clone();
+    let app = window.app_handle();
+    let update_window = window.clone();
+    let result = state
+        .manager
+        .retry_last_dictation(move |update| {
+            let _ = update_window.emit("dictation:update", update);
+        })
+        .await;
+
+    emit_history_events(app, &result);
+    result
+}
+
+fn emit_history_events(app: &AppHandle, result: &Result<String, DiktError>) {
     if let Some(message) = crate::transcription_history::take_runtime_error() {
         let _ = app.emit("transcription-history-error", message);
     }
@@ -60,17 +98,25 @@ pub async fn stop_and_transcribe(
     if result.is_ok() {
         let _ = app.emit("transcription-history-updated", ());
     }
+}
 
-    result
+#[tauri::command]
+pub fn cancel_dictation(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+) -> Result<(), DiktError> {
+    state.manager.cancel(move |update| {
+        let _ = window.emit("dictation:update", update);
+    })
 }
 
 #[tauri::command]
-pub fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
+pub fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, DiktError> {
     state.manager.get_settings()
 }
 
 #[tauri::command]
-pub fn save_settings(settings: AppSettings, state: State<'_, AppState>) -> Result<(), String> {
+pub fn save_settings(settings: AppSettings, state: State<'_, AppState>) -> Result<(), DiktError> {
     state.manager.save_settings(settings)
 }
 
@@ -78,22 +124,27 @@ pub fn save_settings(settings: AppSettings, state: State<'_, AppState>) -> Resul
 pub fn save_vocabulary(
     vocabulary: Vec<VocabularyEntry>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), DiktError> {
     state.manager.save_vocabulary(vocabulary)
 }
 
 #[tauri::command]
-pub async fn test_connection(settings: AppSettings) -> Result<String, String> {
+pub async fn test_connection(settings: AppSettings) -> Result<String, DiktError> {
+    if settings.provider == transcribe_local::LOCAL_PROVIDER {
+        transcribe_local::validate_model_path(&settings.local_model_path)?;
+        return Ok("Local model found — transcription runs offline.".to_string());
+    }
+
     if settings.api_key.trim().is_empty() {
-        return Err("Missing API key".to_string());
+        return Err(DiktError::Auth("Missing API key".to_string()));
     }
 
     if settings.base_url.trim().is_empty() {
-        return Err("Missing base URL".to_string());
+        return Err("Missing base URL".into());
     }
 
     if settings.model.trim().is_empty() {
-        return Err("Missing model".to_string());
+        return Err("Missing model".into());
     }
 
     let trimmed = settings.base_url.trim_end_matches('/');
@@ -111,24 +162,26 @@ pub async fn test_connection(settings: AppSettings) -> Result<String, String> {
         .await
         .map_err(|e| {
             if e.is_timeout() {
-                "Connection timed out — check your base URL.".to_string()
+                DiktError::Network("Connection timed out — check your base URL.".to_string())
             } else if e.is_connect() {
-                format!("Connection failed — could not reach {trimmed}")
+                DiktError::Network(format!("Connection failed — could not reach {trimmed}"))
             } else {
-                format!("Request failed: {e}")
+                DiktError::Other(format!("Request failed: {e}"))
             }
         })?;
 
     let status = response.status();
     if status == reqwest::StatusCode::UNAUTHORIZED {
-        return Err("Authentication failed — check your API key.".to_string());
+        return Err(DiktError::Auth("Authentication failed — check your API key.".to_string()));
     }
     if status == reqwest::StatusCode::FORBIDDEN {
-        return Err("Access denied — your API key may lack permissions.".to_string());
+        return Err(DiktError::Auth(
+            "Access denied — your API key may lack permissions.".to_string(),
+        ));
     }
     if !status.is_success() {
         let body = response.text().await.unwrap_or_default();
-        return Err(format!("API returned {status}: {body}"));
+        return Err(DiktError::from_status(status.as_u16(), body));
     }
 
     Ok("Connection successful — API key is valid.".to_string())
@@ -138,27 +191,72 @@ pub async fn test_connection(settings: AppSettings) -> Result<String, String> {
 pub async fn fetch_provider_models(
     base_url: String,
     api_key: String,
-) -> Result<Vec<String>, String> {
-    let result = crate::models_api::fetch_models(&base_url, &api_key).await;
+    backend: Option<FormatterBackend>,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, DiktError> {
+    let retry = state.manager.get_settings()?.retry_policy;
+    let endpoint = ChatEndpoint {
+        backend: backend.unwrap_or_default(),
+        base_url: &base_url,
+        api_key: &api_key,
+    };
+    let result = crate::models_api::fetch_models(&endpoint, &retry).await;
     if let Err(ref e) = result {
-        eprintln!("fetch_provider_models error: {e}");
+        tracing::warn!(error = %e, "fetch_provider_models failed");
     }
     result
 }
 
 #[tauri::command]
-pub fn get_transcription_history() -> Result<Vec<TranscriptionHistoryItem>, String> {
-    crate::transcription_history::load_history()
+pub fn list_input_devices() -> Result<Vec<String>, DiktError> {
+    crate::audio::list_input_devices()
+}
+
+#[tauri::command]
+pub fn get_transcription_history() -> Result<Vec<TranscriptionHistoryItem>, DiktError> {
+    crate::transcription_history::load_history().map_err(DiktError::Storage)
+}
+
+/// Save a formatted history item as an example for the mode that produced it.
+#[tauri::command]
+pub fn add_mode_example_from_history(
+    id: String,
+    state: State<'_, AppState>,
+) -> Result<Mode, DiktError> {
+    let item = crate::transcription_history::load_history()
+        .map_err(DiktError::Storage)?
+        .into_iter()
+        .find(|item| item.id == id)
+        .ok_or("History item not found")?;
+    state.manager.add_history_example(&item)
+}
+
+#[tauri::command]
+pub fn delete_transcription_history_item(id: String) -> Result<(), DiktError> {
+    crate::transcription_history::delete_item(&id).map_err(DiktError::Storage)
+}
+
+#[tauri::command]
+pub fn clear_transcription_history() -> Result<(), DiktError> {
+    crate::transcription_history::clear_history().map_err(DiktError::Storage)
+}
+
+/// p50/p95 per dictation stage over the most recent history entries (100 by default).
+#[tauri::command]
+pub fn get_latency_summary(recent: Option<usize>) -> Result<LatencySummary, DiktError> {
+    crate::transcription_history::latency_summary(recent.unwrap_or(100))
+        .map_err(DiktError::Storage)
 }
 
 #[tauri::command]
-pub fn delete_transcription_history_item(id: String) -> Result<(), String> {
-    crate::transcription_history::delete_item(&id)
+pub fn reveal_log_file() -> Result<(), DiktError> {
+    crate::logging::reveal()
 }
 
+/// The log files as one text with API keys redacted, for attaching to bug reports.
 #[tauri::command]
-pub fn clear_transcription_history() -> Result<(), String> {
-    crate::transcription_history::clear_history()
+pub fn export_log() -> Result<String, DiktError> {
+    crate::logging::export()
 }
 
 #[tauri::command]
@@ -178,6 +276,7 @@ pub fn hide_settings_window(app: AppHandle) -> Result<(), String> {
 
 /// Background thread that broadcasts audio level events at ~20 FPS while recording.
 /// Both the main window and settings window can subscribe to `audio:level`.
+/// Input device warnings and live partial transcripts are forwarded as `dictation:update`.
 /// Exits when the main window is destroyed (app shutting down).
 pub fn start_audio_level_emitter(app: &AppHandle) {
     let app = app.clone();
@@ -187,6 +286,15 @@ pub fn start_audio_level_emitter(app: &AppHandle) {
             if app.get_webview_window("main").is_none() {
                 break;
             }
+            if let Some(warning) = crate::audio::take_device_warning() {
+                let _ = app.emit(
+                    "dictation:update",
+                    DictationUpdate::new(DictationState::Recording).message(warning),
+                );
+            }
+            if let Some(update) = app.state::<AppState>().manager.take_partial_update() {
+                let _ = app.emit("dictation:update", update);
+            }
             if !crate::audio::is_recording() {
                 continue;
             }
@@ -196,6 +304,42 @@ pub fn start_audio_level_emitter(app: &AppHandle) {
     });
 }
 
+/// Background task that drains the offline queue, backing off while the
+/// provider stays unreachable. Finished transcripts go to history and raise
+/// `dictation:deferred` instead of being pasted.
+pub fn start_offline_queue_worker(app: &AppHandle) {
+    const IDLE_POLL: std::time::Duration = std::time::Duration::from_secs(10);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut failures = 0;
+        loop {
+            let result = app.state::<AppState>().manager.process_next_queued().await;
+            if let Some(message) = crate::transcription_history::take_runtime_error() {
+                let _ = app.emit("transcription-history-error", message);
+            }
+            let delay = match result {
+                Ok(Some(text)) => {
+                    failures = 0;
+                    let _ = app.emit("transcription-history-updated", ());
+                    let _ = app.emit("dictation:deferred", DeferredDictationPayload { text });
+                    std::time::Duration::ZERO
+                }
+                Ok(None) => {
+                    failures = 0;
+                    IDLE_POLL
+                }
+                Err(e) => {
+                    failures += 1;
+                    tracing::warn!(attempt = failures, error = %e, "queued dictation failed");
+                    crate::offline_queue::backoff_delay(failures)
+                }
+            };
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
 pub fn show_settings_window_internal(app: &AppHandle) -> Result<(), String> {
     let settings_window = app
         .get_webview_window("settings")
@@ -0,0 +1,187 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Errors returned by the ports and the dictation manager.
+///
+/// Serialized for the frontend as `{ code, message }` (plus `status` for
+/// provider errors). `code` is stable, so the UI can pick an actionable hint
+/// without parsing `message`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiktError {
+  /// API key missing, rejected or lacking permissions.
+  Auth(String),
+  /// The provider asked us to slow down (HTTP 429).
+  RateLimited(String),
+  /// The provider could not be reached at all (no connection, DNS, timeout).
+  Network(String),
+  /// The provider rejected or failed the request.
+  Provider { status: u16, body: String },
+  /// Nothing was recorded, or the recording held no speech.
+  NoAudio(String),
+  /// Pasting or copying the transcript failed.
+  Paste(String),
+  /// An OS permission (microphone, accessibility) is missing.
+  Permission(String),
+  /// Reading or writing settings, history or the offline queue failed.
+  Storage(String),
+  /// Another dictation is already in progress.
+  Busy,
+  /// The dictation was cancelled on purpose.
+  Cancelled,
+  /// Anything else: invalid local configuration, wrong state, internal failures.
+  Other(String),
+}
+
+impl DiktError {
+  /// Classify a failed HTTP response from a provider.
+  pub fn from_status(status: u16, body: String) -> Self {
+    match status {
+      401 | 403 => DiktError::Auth(format!("API error {status}: {body}")),
+      429 => DiktError::RateLimited(body),
+      _ => DiktError::Provider { status, body },
+    }
+  }
+
+  /// Prefix the message with where the error happened, keeping the variant.
+  pub fn context(self, context: &str) -> Self {
+    let with_context = |message: String| format!("{context}: {message}");
+    match self {
+      DiktError::Auth(message) => DiktError::Auth(with_context(message)),
+      DiktError::RateLimited(body) => DiktError::RateLimited(with_context(body)),
+      DiktError::Network(message) => DiktError::Network(with_context(message)),
+      DiktError::Provider { status, body } => DiktError::Provider {
+        status,
+        body: with_context(body),
+      },
+      DiktError::NoAudio(message) => DiktError::NoAudio(with_context(message)),
+      DiktError::Paste(message) => DiktError::Paste(with_context(message)),
+      DiktError::Permission(message) => DiktError::Permission(with_context(message)),
+      DiktError::Storage(message) => DiktError::Storage(with_context(message)),
+      DiktError::Other(message) => DiktError::Other(with_context(message)),
+      DiktError::Busy | DiktError::Cancelled => self,
+    }
+  }
+
+  pub fn code(&self) -> &'static str {
+    match self {
+      DiktError::Auth(_) => "auth",
+      DiktError::RateLimited(_) => "rate_limited",
+      DiktError::Network(_) => "network",
+      DiktError::Provider { .. } => "provider",
+      DiktError::NoAudio(_) => "no_audio",
+      DiktError::Paste(_) => "paste",
+      DiktError::Permission(_) => "permission",
+      DiktError::Storage(_) => "storage",
+      DiktError::Busy => "busy",
+      DiktError::Cancelled => "cancelled",
+      DiktError::Other(_) => "other",
+    }
+  }
+
+  /// The provider was not reached, so the same request may work later.
+  pub fn is_unreachable(&self) -> bool {
+    matches!(self, DiktError::Network(_))
+  }
+
+  /// Worth sending the same request again; a rejected key or request is not.
+  pub fn is_transient(&self) -> bool {
+    match self {
+      DiktError::RateLimited(_) | DiktError::Network(_) => true,
+      DiktError::Provider { status, .. } => *status >= 500,
+      _ => false,
+    }
+  }
+}
+
+impl std::fmt::Display for DiktError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DiktError::RateLimited(body) => write!(f, "Rate limited by provider: {body}"),
+      DiktError::Network(message) => write!(f, "Provider unreachable: {message}"),
+      DiktError::Provider { status, body } => write!(f, "API error {status}: {body}"),
+      DiktError::Busy => write!(f, "Busy"),
+      DiktError::Cancelled => write!(f, "Cancelled"),
+      DiktError::Auth(message)
+      | DiktError::NoAudio(message)
+      | DiktError::Paste(message)
+      | DiktError::Permission(message)
+      | DiktError::Storage(message)
+      | DiktError::Other(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+impl std::error::Error for DiktError {}
+
+impl Serialize for DiktError {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let status = match self {
+      DiktError::Provider { status, .. } => Some(*status),
+      _ => None,
+    };
+    let mut state = serializer.serialize_struct("DiktError", 2 + usize::from(status.is_some()))?;
+    state.serialize_field("code", self.code())?;
+    state.serialize_field("message", &self.to_string())?;
+    if let Some(status) = status {
+      state.serialize_field("status", &status)?;
+    }
+    state.end()
+  }
+}
+
+impl From<String> for DiktError {
+  fn from(message: String) -> Self {
+    DiktError::Other(message)
+  }
+}
+
+impl From<&str> for DiktError {
+  fn from(message: &str) -> Self {
+    DiktError::Other(message.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::DiktError;
+
+  #[test]
+  fn http_statuses_map_to_variants() {
+    assert_eq!(DiktError::from_status(401, "bad key".into()).code(), "auth");
+    assert_eq!(DiktError::from_status(429, "slow down".into()).code(), "rate_limited");
+    assert_eq!(
+      DiktError::from_status(500, "oops".into()),
+      DiktError::Provider {
+        status: 500,
+        body: "oops".to_string()
+      }
+    );
+  }
+
+  #[test]
+  fn only_transient_errors_are_worth_retrying() {
+    assert!(DiktError::from_status(503, "unavailable".into()).is_transient());
+    assert!(DiktError::from_status(429, "slow down".into()).is_transient());
+    assert!(DiktError::Network("timed out".into()).is_transient());
+    assert!(!DiktError::from_status(401, "bad key".into()).is_transient());
+    assert!(!DiktError::from_status(400, "bad audio".into()).is_transient());
+    assert!(!DiktError::Other("unsupported format".into()).is_transient());
+  }
+
+  #[test]
+  fn serializes_with_stable_code() {
+    let json = serde_json::to_value(DiktError::Network("connection refused".into())).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({ "code": "network", "message": "Provider unreachable: connection refused" })
+    );
+
+    let json = serde_json::to_value(DiktError::Provider {
+      status: 502,
+      body: "bad gateway".into(),
+    })
+    .unwrap();
+    assert_eq!(json["code"], "provider");
+    assert_eq!(json["status"], 502);
+  }
+}
@@ -1,7 +1,22 @@
-use crate::{audio::AudioRecorder, clipboard, format_text, settings, transcribe};
+use crate::audio::{AudioRecorder, RecordingOptions};
+use crate::{
+  active_window, clipboard, format_text, offline_queue, settings, transcribe, transcribe_local, transcribe_stream,
+};
 use crate::transcribe::TranscriptionResult;
 
-use super::ports::{Formatter, Paster, Recorder, SettingsStore, Transcriber};
+use std::future::Future;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_util::sync::CancellationToken;
+
+use super::error::DiktError;
+use super::ports::{
+  DesktopContext, Formatter, OfflineQueue, PartialTextSink, Paster, Recorder, SettingsStore, StreamingTranscriber,
+  Transcriber,
+};
+use super::types::{
+  AudioChunk, AudioFormat, EncodedAudio, FocusedWindow, FrameSink, Mode, QueuedDictation,
+};
 use crate::settings::AppSettings;
 
 pub struct CpalRecorder(AudioRecorder);
@@ -13,11 +28,28 @@ impl Default for CpalRecorder {
 }
 
 impl Recorder for CpalRecorder {
-  fn start(&self) -> Result<(), String> {
-    self.0.start()
+  fn start(&self, settings: &AppSettings, frames: Option<FrameSink>) -> Result<(), DiktError> {
+    // The local model decodes WAV itself and wants 16 kHz, whatever the upload settings say.
+    let (target_sample_rate, format) = if settings.provider == transcribe_local::LOCAL_PROVIDER {
+      (transcribe_local::WHISPER_SAMPLE_RATE, AudioFormat::Wav)
+    } else {
+      (settings.target_sample_rate, settings.audio_format)
+    };
+    // Refused before recording, so a saved format this build lacks loses nothing.
+    if !crate::encoding::is_available(format) {
+      return Err(crate::encoding::OPUS_UNAVAILABLE.into());
+    }
+    self
+      .0
+      .start(RecordingOptions {
+        input_device: settings.input_device.clone(),
+        target_sample_rate,
+        format,
+        frames,
+      })
   }
 
-  fn stop(&self) -> Result<Vec<u8>, String> {
+  fn stop(&self) -> Result<Vec<AudioChunk>, DiktError> {
     self.0.stop()
   }
 }
@@ -26,42 +58,102 @@ pub struct FileAndKeyringSettingsStore;
 
 impl SettingsStore for FileAndKeyringSettingsStore {
   fn load(&self) -> AppSettings {
-    settings::load_settings()
+    let settings = settings::load_settings();
+    crate::logging::register_secrets(&settings);
+    settings
+  }
+
+  fn save(&self, settings: &AppSettings) -> Result<(), DiktError> {
+    crate::logging::register_secrets(settings);
+    settings::save_settings(settings).map_err(DiktError::Storage)
+  }
+}
+
+pub struct FileOfflineQueue;
+
+impl OfflineQueue for FileOfflineQueue {
+  fn enqueue(&self, chunks: &[AudioChunk], settings: &AppSettings) -> Result<(), DiktError> {
+    offline_queue::enqueue(chunks, settings).map_err(DiktError::Storage)
+  }
+
+  fn next(&self) -> Result<Option<(QueuedDictation, Vec<AudioChunk>)>, DiktError> {
+    offline_queue::next().map_err(DiktError::Storage)
+  }
+
+  fn remove(&self, id: &str) -> Result<(), DiktError> {
+    offline_queue::remove(id).map_err(DiktError::Storage)
   }
 
-  fn save(&self, settings: &AppSettings) -> Result<(), String> {
-    settings::save_settings(settings)
+  fn record_attempt(&self, id: &str) -> Result<u32, DiktError> {
+    offline_queue::record_attempt(id).map_err(DiktError::Storage)
   }
 }
 
 pub struct ClipboardPaster;
 
 impl Paster for ClipboardPaster {
-  fn paste(&self, text: &str) -> Result<(), String> {
+  fn paste(&self, text: &str) -> Result<(), DiktError> {
     clipboard::copy_and_paste(text, true)
   }
 
-  fn copy(&self, text: &str) -> Result<(), String> {
+  fn copy(&self, text: &str) -> Result<(), DiktError> {
     clipboard::copy_to_clipboard(text)
   }
 }
 
-pub struct OpenAiCompatibleFormatter;
+pub struct SystemDesktopContext;
+
+impl DesktopContext for SystemDesktopContext {
+  fn clipboard_text(&self) -> Option<String> {
+    clipboard::clipboard_text()
+  }
+
+  fn selected_text(&self) -> Option<String> {
+    clipboard::copy_selection().unwrap_or_else(|error| {
+      tracing::warn!(%error, "could not copy the selected text");
+      None
+    })
+  }
+
+  fn focused_window(&self) -> Option<FocusedWindow> {
+    active_window::focused_window()
+  }
+
+  fn user_name(&self) -> Option<String> {
+    ["USER", "USERNAME"]
+      .iter()
+      .find_map(|name| std::env::var(name).ok())
+      .filter(|name| !name.trim().is_empty())
+  }
+}
+
+/// Formats through the chat API of each mode's backend (OpenAI-compatible, Anthropic, Ollama).
+pub struct ChatApiFormatter;
 
 #[async_trait::async_trait]
-impl Formatter for OpenAiCompatibleFormatter {
+impl Formatter for ChatApiFormatter {
   async fn format(
     &self,
-    base_url: &str,
-    api_key: &str,
-    model: &str,
-    system_prompt: &str,
+    settings: &AppSettings,
+    mode: &Mode,
     text: &str,
-  ) -> Result<String, String> {
-    format_text::format_text(base_url, api_key, model, system_prompt, text).await
+    cancel: &CancellationToken,
+    on_partial: PartialTextSink,
+  ) -> Result<String, DiktError> {
+    until_cancelled(
+      cancel,
+      format_text::format_text(
+        &format_text::ChatEndpoint::for_mode(settings, mode),
+        &format_text::ChatRequest::for_mode(mode, text),
+        &settings.retry_policy,
+        &*on_partial,
+      ),
+    )
+    .await
   }
 }
 
+/// Calls the configured provider, then `transcription_fallbacks` in order when it is down.
 pub struct OpenAiCompatibleTranscriber;
 
 #[async_trait::async_trait]
@@ -69,17 +161,167 @@ impl Transcriber for OpenAiCompatibleTranscriber {
   async fn transcribe(
     &self,
     settings: &AppSettings,
-    audio_wav: Vec<u8>,
+    audio: EncodedAudio,
+    prompt: Option<&str>,
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError> {
+    until_cancelled(
+      cancel,
+      transcribe::transcribe(
+        &transcription_endpoints(settings),
+        audio,
+        prompt,
+        &settings.retry_policy,
+      ),
+    )
+    .await
+  }
+}
+
+fn transcription_endpoints(settings: &AppSettings) -> Vec<transcribe::Endpoint<'_>> {
+  let primary = transcribe::Endpoint {
+    provider: &settings.provider,
+    base_url: &settings.base_url,
+    model: &settings.model,
+    api_key: &settings.api_key,
+  };
+  let fallbacks = settings
+    .transcription_fallbacks
+    .iter()
+    // The local model is its own transcriber, not an HTTP endpoint.
+    .filter(|fallback| fallback.provider != transcribe_local::LOCAL_PROVIDER)
+    .map(|fallback| transcribe::Endpoint {
+      provider: &fallback.provider,
+      base_url: &fallback.base_url,
+      model: &fallback.model,
+      api_key: provider_key(settings, &fallback.provider),
+    });
+  std::iter::once(primary).chain(fallbacks).collect()
+}
+
+/// The key saved for `provider`; the transcription provider's comes from
+/// `api_key`, which may have been edited since it was saved per provider.
+fn provider_key<'a>(settings: &'a AppSettings, provider: &str) -> &'a str {
+  if provider == settings.provider {
+    &settings.api_key
+  } else {
+    settings
+      .provider_api_keys
+      .get(provider)
+      .map(String::as_str)
+      .unwrap_or("")
+  }
+}
+
+/// Model and key for live streaming, which may be another service than the
+/// one batch transcription uses.
+fn streaming_endpoint(settings: &AppSettings) -> (&str, &str) {
+  let protocol = settings.streaming_protocol;
+  let model = Some(settings.streaming_model.trim())
+    .filter(|model| !model.is_empty())
+    .unwrap_or(protocol.default_model());
+  let key_ref = settings
+    .streaming_api_key_ref
+    .as_deref()
+    .map(str::trim)
+    .filter(|key_ref| !key_ref.is_empty())
+    .or(protocol.key_provider());
+  let api_key = key_ref.map_or("", |key_ref| provider_key(settings, key_ref));
+  (model, api_key)
+}
+
+pub struct LocalWhisperTranscriber;
+
+#[async_trait::async_trait]
+impl Transcriber for LocalWhisperTranscriber {
+  async fn transcribe(
+    &self,
+    settings: &AppSettings,
+    audio: EncodedAudio,
+    prompt: Option<&str>,
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError> {
+    until_cancelled(
+      cancel,
+      async {
+        transcribe_local::transcribe(&settings.local_model_path, audio, prompt).await
+      },
+    )
+    .await
+  }
+}
+
+/// Sends each request to the local model or the remote API based on `settings.provider`.
+pub struct ProviderTranscriber {
+  remote: OpenAiCompatibleTranscriber,
+  local: LocalWhisperTranscriber,
+}
+
+impl Default for ProviderTranscriber {
+  fn default() -> Self {
+    Self {
+      remote: OpenAiCompatibleTranscriber,
+      local: LocalWhisperTranscriber,
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl Transcriber for ProviderTranscriber {
+  async fn transcribe(
+    &self,
+    settings: &AppSettings,
+    audio: EncodedAudio,
+    prompt: Option<&str>,
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError> {
+    if settings.provider == transcribe_local::LOCAL_PROVIDER {
+      self.local.transcribe(settings, audio, prompt, cancel).await
+    } else {
+      self.remote.transcribe(settings, audio, prompt, cancel).await
+    }
+  }
+}
+
+pub struct WebSocketStreamingTranscriber;
+
+#[async_trait::async_trait]
+impl StreamingTranscriber for WebSocketStreamingTranscriber {
+  fn sample_rate(&self, settings: &AppSettings) -> u32 {
+    settings.streaming_protocol.sample_rate()
+  }
+
+  async fn transcribe_stream(
+    &self,
+    settings: &AppSettings,
+    frames: UnboundedReceiver<Vec<i16>>,
     prompt: Option<&str>,
-  ) -> Result<TranscriptionResult, String> {
-    transcribe::transcribe(
-      &settings.base_url,
-      &settings.api_key,
-      &settings.model,
-      &settings.provider,
-      audio_wav,
-      prompt,
+    on_partial: PartialTextSink,
+  ) -> Result<TranscriptionResult, DiktError> {
+    let (model, api_key) = streaming_endpoint(settings);
+    transcribe_stream::transcribe_stream(
+      transcribe_stream::StreamRequest {
+        url: &settings.streaming_url,
+        api_key,
+        model,
+        protocol: settings.streaming_protocol,
+        prompt,
+      },
+      frames,
+      on_partial.as_ref(),
     )
     .await
   }
 }
+
+/// Run `request` until it finishes or `cancel` fires; dropping it on cancel
+/// closes any in-flight HTTP connection.
+async fn until_cancelled<T>(
+  cancel: &CancellationToken,
+  request: impl Future<Output = Result<T, DiktError>>,
+) -> Result<T, DiktError> {
+  tokio::select! {
+    _ = cancel.cancelled() => Err(DiktError::Cancelled),
+    result = request => result,
+  }
+}
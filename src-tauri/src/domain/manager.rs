@@ -1,27 +1,107 @@
-use std::sync::Mutex;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use futures_util::{StreamExt, TryStreamExt};
 use regex::Regex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, info_span, warn, Instrument, Span};
 
+use crate::retry;
 use crate::settings::AppSettings;
+use crate::transcribe::TranscriptionResult;
+use crate::transcription_history::TranscriptionHistoryItem;
 
 use super::{
-  ports::{Formatter, Paster, Recorder, SettingsStore, Transcriber},
-  types::{DictationState, DictationUpdate, VocabularyEntry},
+  error::DiktError,
+  ports::{
+    DesktopContext, Formatter, OfflineQueue, PartialTextSink, Paster, Recorder, SettingsStore,
+    StreamingTranscriber, Transcriber,
+  },
+  reply_check,
+  stitch::stitch_transcriptions,
+  template::{self, PromptVariables},
+  types::{
+    provider_base_url, AudioChunk, DictationState, DictationUpdate, EncodedAudio, FocusedWindow,
+    FormatterBackend, FrameSink, Mode, ModeExample, ModeKind, ModeStep, QueuedDictation,
+    RejectedFormat, StageTimings, StepOutput, VocabularyEntry,
+  },
 };
 
 const MAX_PROMPT_ENTRIES: usize = 50;
 const MAX_PROMPT_CHARS: usize = 800;
 const MAX_REPLACEMENTS_PER_ENTRY: usize = 10;
+const MAX_CONCURRENT_CHUNKS: usize = 3;
+const MAX_CHUNK_ATTEMPTS: usize = 3;
+// Queued dictations the provider keeps rejecting are dropped after this many tries.
+const MAX_QUEUED_ATTEMPTS: u32 = 5;
 
 pub struct DictationSessionManager {
   state: Mutex<DictationState>,
   settings: Mutex<AppSettings>,
+  // Replaced at the start of every recording.
+  cancel: Mutex<CancellationToken>,
 
   recorder: Box<dyn Recorder>,
   settings_store: Box<dyn SettingsStore>,
   transcriber: Box<dyn Transcriber>,
   paster: Box<dyn Paster>,
   formatter: Box<dyn Formatter>,
+
+  streaming_transcriber: Option<Arc<dyn StreamingTranscriber>>,
+  stream_task: Mutex<Option<JoinHandle<Result<TranscriptionResult, DiktError>>>>,
+  partial_text: Arc<Mutex<Option<String>>>,
+
+  failed_dictation: Mutex<Option<FailedDictation>>,
+  offline_queue: Option<Box<dyn OfflineQueue>>,
+  desktop_context: Option<Box<dyn DesktopContext>>,
+
+  // Numbers the `dictation` spans in the log.
+  sessions: AtomicU64,
+  // Span of the recording in progress, from `start_recording` until it is stopped.
+  recording: Mutex<Option<RecordingSpan>>,
+}
+
+struct RecordingSpan {
+  session: Span,
+  record: Span,
+  started: Instant,
+  // Focused window when recording started, for mode rules and `{{active_app}}`.
+  window: Option<FocusedWindow>,
+  // Desktop prompt variables read when recording started, before the user
+  // could switch windows or change the selection.
+  desktop: PromptVariables,
+}
+
+/// Recording and settings from a dictation that failed after recording stopped.
+#[derive(Clone)]
+struct FailedDictation {
+  chunks: Vec<AudioChunk>,
+  settings: AppSettings,
+  desktop: PromptVariables,
+  // Set when only the paste failed, so a retry pastes this instead of
+  // transcribing the recording again.
+  unpasted: Option<UnpastedText>,
+}
+
+/// Finished transcript that could not be pasted, with its history entry.
+#[derive(Clone)]
+struct UnpastedText {
+  text: String,
+  history_id: Option<String>,
+  timings: StageTimings,
+}
+
+/// Transcript after the active mode ran, with what history needs to show it.
+struct FormattedText {
+  text: String,
+  mode_id: Option<String>,
+  mode_name: Option<String>,
+  original_text: Option<String>,
+  step_outputs: Option<Vec<StepOutput>>,
+  rejected_format: Option<RejectedFormat>,
 }
 
 impl DictationSessionManager {
@@ -36,15 +116,42 @@ impl DictationSessionManager {
     Self {
       state: Mutex::new(DictationState::Idle),
       settings: Mutex::new(initial_settings),
+      cancel: Mutex::new(CancellationToken::new()),
       recorder,
       settings_store,
       transcriber,
       paster,
       formatter,
+      streaming_transcriber: None,
+      stream_task: Mutex::new(None),
+      partial_text: Arc::new(Mutex::new(None)),
+      failed_dictation: Mutex::new(None),
+      offline_queue: None,
+      desktop_context: None,
+      sessions: AtomicU64::new(0),
+      recording: Mutex::new(None),
     }
   }
 
-  pub fn get_settings(&self) -> Result<AppSettings, String> {
+  /// Enable live transcription for sessions where `streaming_enabled` is set.
+  pub fn with_streaming_transcriber(mut self, transcriber: Arc<dyn StreamingTranscriber>) -> Self {
+    self.streaming_transcriber = Some(transcriber);
+    self
+  }
+
+  /// Queue recordings for later when the provider cannot be reached.
+  pub fn with_offline_queue(mut self, queue: Box<dyn OfflineQueue>) -> Self {
+    self.offline_queue = Some(queue);
+    self
+  }
+
+  /// Source of the clipboard, selection and focused app for prompt variables.
+  pub fn with_desktop_context(mut self, context: Box<dyn DesktopContext>) -> Self {
+    self.desktop_context = Some(context);
+    self
+  }
+
+  pub fn get_settings(&self) -> Result<AppSettings, DiktError> {
     Ok(
       self
         .settings
@@ -54,7 +161,15 @@ impl DictationSessionManager {
     )
   }
 
-  pub fn save_settings(&self, settings: AppSettings) -> Result<(), String> {
+  pub fn save_settings(&self, settings: AppSettings) -> Result<(), DiktError> {
+    if !crate::encoding::is_available(settings.audio_format) {
+      return Err(crate::encoding::OPUS_UNAVAILABLE.into());
+    }
+    for mode in &settings.modes {
+      validate_steps(mode)
+        .and_then(|()| validate_endpoint(&settings, mode))
+        .map_err(|e| e.context(&format!("Mode \"{}\"", mode.name)))?;
+    }
     self.settings_store.save(&settings)?;
     let mut guard = self
       .settings
@@ -64,7 +179,7 @@ impl DictationSessionManager {
     Ok(())
   }
 
-  pub fn save_vocabulary(&self, vocabulary: Vec<VocabularyEntry>) -> Result<(), String> {
+  pub fn save_vocabulary(&self, vocabulary: Vec<VocabularyEntry>) -> Result<(), DiktError> {
     let mut next_settings = self
       .settings
       .lock()
@@ -82,165 +197,1265 @@ impl DictationSessionManager {
     Ok(())
   }
 
-  pub fn start_recording<F>(&self, mut on_update: F) -> Result<(), String>
+  /// Save what a mode made of a dictation in history as an example for that
+  /// mode; returns the updated mode.
+  pub fn add_history_example(&self, item: &TranscriptionHistoryItem) -> Result<Mode, DiktError> {
+    let mode_id = item
+      .mode_id
+      .as_deref()
+      .ok_or("Only history items a mode changed can become examples")?;
+    let mode = self
+      .get_settings()?
+      .modes
+      .into_iter()
+      .find(|mode| mode.id == mode_id)
+      .ok_or("The mode no longer exists")?;
+    let example = history_example(&mode, item)?;
+    self.add_mode_example(mode_id, example)
+  }
+
+  /// Append `example` to the mode with `mode_id` and save; returns the updated mode.
+  pub fn add_mode_example(&self, mode_id: &str, example: ModeExample) -> Result<Mode, DiktError> {
+    let mut next_settings = self
+      .settings
+      .lock()
+      .map_err(|_| "Settings lock poisoned".to_string())?
+      .clone();
+    let mode = next_settings
+      .modes
+      .iter_mut()
+      .find(|mode| mode.id == mode_id)
+      .ok_or("The mode no longer exists")?;
+    if !mode.examples.contains(&example) {
+      mode.examples.push(example);
+    }
+    let mode = mode.clone();
+
+    self.settings_store.save(&next_settings)?;
+
+    let mut guard = self
+      .settings
+      .lock()
+      .map_err(|_| "Settings lock poisoned".to_string())?;
+    *guard = next_settings;
+    Ok(mode)
+  }
+
+  pub fn start_recording<F>(&self, mut on_update: F) -> Result<(), DiktError>
   where
     F: FnMut(DictationUpdate),
   {
+    let settings = self
+      .settings
+      .lock()
+      .map_err(|_| "Settings lock poisoned".to_string())?
+      .clone();
+
     {
       let mut state = self.state.lock().map_err(|_| "State lock poisoned".to_string())?;
       if *state != DictationState::Idle {
-        return Err("Busy".to_string());
+        return Err(DiktError::Busy);
       }
       *state = DictationState::Recording;
+      *self.cancel.lock().map_err(|_| "Cancel lock poisoned".to_string())? =
+        CancellationToken::new();
+    }
+
+    let session = self.session_span(false);
+    let _entered = session.enter();
+    let record = info_span!("stage", stage = "record");
+    info!(provider = %settings.provider, streaming = settings.streaming_enabled, "recording started");
+    let window = self
+      .desktop_context
+      .as_ref()
+      .and_then(|desktop| desktop.focused_window());
+    if let Ok(mut recording) = self.recording.lock() {
+      *recording = Some(RecordingSpan {
+        session: session.clone(),
+        record,
+        started: Instant::now(),
+        window,
+        desktop: PromptVariables::default(),
+      });
     }
 
     on_update(DictationUpdate::new(DictationState::Recording));
 
-    match self.recorder.start() {
-      Ok(()) => Ok(()),
+    let frames = if settings.streaming_enabled {
+      self.start_stream(&settings)
+    } else {
+      None
+    };
+
+    match self.recorder.start(&settings, frames) {
+      Ok(()) => {
+        // Read after the recorder started, since copying the selection takes a moment.
+        self.capture_desktop(settings);
+        Ok(())
+      }
       Err(e) => {
+        warn!(error = %e, code = e.code(), "recorder failed to start");
+        self.take_recording_span();
+        self.abort_stream();
         let _ = self.set_state(DictationState::Idle);
-        on_update(DictationUpdate::new(DictationState::Error).message(e.clone()));
+        on_update(DictationUpdate::new(DictationState::Error).error(e.clone()));
         Err(e)
       }
     }
   }
 
-  pub async fn stop_and_process<F>(&self, mut on_update: F) -> Result<String, String>
+  pub async fn stop_and_process<F>(&self, mut on_update: F) -> Result<String, DiktError>
   where
     F: FnMut(DictationUpdate),
   {
     {
       let mut state = self.state.lock().map_err(|_| "State lock poisoned".to_string())?;
       if *state != DictationState::Recording {
-        return Err("Not recording".to_string());
+        return Err("Not recording".into());
       }
       *state = DictationState::Transcribing;
     }
 
+    let cancel = self
+      .cancel
+      .lock()
+      .map_err(|_| "Cancel lock poisoned".to_string())?
+      .clone();
     on_update(DictationUpdate::new(DictationState::Transcribing));
 
+    let recording = self.take_recording_span();
+    let session = recording
+      .as_ref()
+      .map(|recording| recording.session.clone())
+      .unwrap_or_else(|| self.session_span(false));
+
     let result = async {
-      let wav_data = self.recorder.stop()?;
-      let local_duration = wav_duration_secs(&wav_data);
+      let stream_task = self
+        .stream_task
+        .lock()
+        .map_err(|_| "Stream lock poisoned".to_string())?
+        .take();
+      // A new recording replaces whatever an earlier failure left behind.
+      self.set_failed_dictation(None);
+      let stop_started = Instant::now();
+      let stopped = self.recorder.stop();
+      let timings = StageTimings {
+        stop: Some(elapsed_ms(stop_started)),
+        ..StageTimings::default()
+      };
+      if let Some(recording) = &recording {
+        log_stage_end(&recording.record, recording.started, &stopped);
+      }
+      let chunks = match stopped {
+        Ok(chunks) => chunks,
+        Err(e) => {
+          if let Some(task) = stream_task {
+            task.abort();
+          }
+          return Err(e);
+        }
+      };
 
       let settings = self
         .settings
         .lock()
         .map_err(|_| "Settings lock poisoned".to_string())?
         .clone();
+      let window = recording.as_ref().and_then(|recording| recording.window.as_ref());
+      let settings = apply_mode_rules(settings, window);
+      let desktop = recording
+        .as_ref()
+        .map(|recording| recording.desktop.clone())
+        .unwrap_or_default();
 
-      let prompt = build_vocabulary_prompt(&settings.vocabulary);
-      let transcription_result = self
-        .transcriber
-        .transcribe(&settings, wav_data, prompt.as_deref())
-        .await?;
+      // Kept until the run succeeds so a failure can be retried without re-recording.
+      self.set_failed_dictation(Some(FailedDictation {
+        chunks: chunks.clone(),
+        settings: settings.clone(),
+        desktop: desktop.clone(),
+        unpasted: None,
+      }));
+
+      self
+        .process_recording(
+          chunks,
+          &settings,
+          desktop,
+          stream_task,
+          timings,
+          &cancel,
+          &mut on_update,
+        )
+        .await
+    }
+    .instrument(session.clone())
+    .await;
+
+    session.in_scope(|| self.finish_run(result, &cancel, on_update))
+  }
+
+  /// Run transcription, formatting and paste again on the recording from the
+  /// last failed dictation, with the settings it was recorded under. When only
+  /// the paste failed, the finished text is pasted again instead.
+  pub async fn retry_last_dictation<F>(&self, mut on_update: F) -> Result<String, DiktError>
+  where
+    F: FnMut(DictationUpdate),
+  {
+    let failed = self
+      .failed_dictation
+      .lock()
+      .map_err(|_| "Retry lock poisoned".to_string())?
+      .clone()
+      .ok_or_else(|| "No failed dictation to retry".to_string())?;
+
+    let first_state = match failed.unpasted {
+      Some(_) => DictationState::Pasting,
+      None => DictationState::Transcribing,
+    };
+    let cancel = CancellationToken::new();
+    {
+      let mut state = self.state.lock().map_err(|_| "State lock poisoned".to_string())?;
+      if *state != DictationState::Idle {
+        return Err(DiktError::Busy);
+      }
+      *state = first_state;
+      *self.cancel.lock().map_err(|_| "Cancel lock poisoned".to_string())? = cancel.clone();
+    }
+
+    on_update(DictationUpdate::new(first_state));
+
+    let session = self.session_span(true);
+    let result = match failed.unpasted {
+      Some(unpasted) => {
+        self
+          .paste_again(unpasted, &failed.settings, &mut on_update)
+          .instrument(session.clone())
+          .await
+      }
+      None => {
+        self
+          .process_recording(
+            failed.chunks,
+            &failed.settings,
+            failed.desktop,
+            None,
+            StageTimings::default(),
+            &cancel,
+            &mut on_update,
+          )
+          .instrument(session.clone())
+          .await
+      }
+    };
 
-      let duration_secs = transcription_result.duration_secs.or(local_duration);
-      let language = transcription_result.language;
+    session.in_scope(|| self.finish_run(result, &cancel, on_update))
+  }
 
-      let text = apply_vocabulary_replacements(&transcription_result.text, &settings.vocabulary);
+  /// Transcribe a finished recording (or take the live transcript when one
+  /// streamed in), apply the active mode and paste the result.
+  ///
+  /// `timings` holds the stages that already ran; the rest are added to it and
+  /// reported with `Done` and in history. `desktop` holds the prompt variables
+  /// read from the desktop when recording started.
+  #[allow(clippy::too_many_arguments)]
+  async fn process_recording<F>(
+    &self,
+    chunks: Vec<AudioChunk>,
+    settings: &AppSettings,
+    desktop: PromptVariables,
+    stream_task: Option<JoinHandle<Result<TranscriptionResult, DiktError>>>,
+    mut timings: StageTimings,
+    cancel: &CancellationToken,
+    on_update: &mut F,
+  ) -> Result<String, DiktError>
+  where
+    F: FnMut(DictationUpdate),
+  {
+    let local_duration = recording_duration_secs(&chunks);
 
-      let mut mode_name: Option<String> = None;
-      let mut original_text: Option<String> = None;
+    let prompt = build_vocabulary_prompt(&settings.vocabulary);
+    let transcribe_started = Instant::now();
+    let streamed = match stream_task {
+      Some(task) => {
+        let abort = task.abort_handle();
+        let outcome = tokio::select! {
+          _ = cancel.cancelled() => {
+            abort.abort();
+            return Err(DiktError::Cancelled);
+          }
+          outcome = task => outcome,
+        };
+        match outcome {
+          Ok(Ok(result)) => Some(result),
+          Ok(Err(e)) => {
+            warn!(error = %e, "streaming transcription failed, uploading the recording instead");
+            None
+          }
+          Err(e) => {
+            warn!(error = %e, "streaming transcription task failed, uploading the recording instead");
+            None
+          }
+        }
+      }
+      None => None,
+    };
+    let transcription_result = match streamed {
+      Some(result) => {
+        timings.transcribe = Some(elapsed_ms(transcribe_started));
+        result
+      }
+      None => {
+        stage(
+          "transcribe",
+          &mut timings.transcribe,
+          self.transcribe_chunks(settings, chunks, prompt.as_deref(), cancel),
+        )
+        .await
+        .map_err(|err| self.queue_if_unreachable(err, cancel))?
+      }
+    };
+    timings.split_upload(transcription_result.upload_ms);
+
+    let duration_secs = transcription_result.duration_secs.or(local_duration);
+    let language = transcription_result.language;
+    let provider = transcription_result.provider;
+
+    let text = apply_vocabulary_replacements(&transcription_result.text, &settings.vocabulary);
+
+    let mode = active_mode(settings);
+    let variables = prompt_variables(settings, language.as_deref(), desktop);
+    if mode.is_some() {
+      let _ = self.set_state(DictationState::Formatting);
+      on_update(DictationUpdate::new(DictationState::Formatting));
+    }
+    let FormattedText {
+      text,
+      mode_id,
+      mode_name,
+      original_text,
+      step_outputs,
+      rejected_format,
+    } = stage(
+      "format",
+      &mut timings.format,
+      self.apply_mode(settings, mode, variables, text, cancel, self.partial_text_sink()),
+    )
+    .await?;
+    if mode.is_none() {
+      timings.format = None;
+    }
+
+    if cancel.is_cancelled() {
+      return Err(DiktError::Cancelled);
+    }
 
-      let text = if let Some(ref mode_id) = settings.active_mode_id {
-        if let Some(mode) = settings.modes.iter().find(|m| &m.id == mode_id) {
-          let _ = self.set_state(DictationState::Formatting);
-          on_update(DictationUpdate::new(DictationState::Formatting));
-          match self
+    {
+      let _ = self.set_state(DictationState::Pasting);
+    }
+    on_update(DictationUpdate::new(DictationState::Pasting));
+
+    let pasted = stage("paste", &mut timings.paste, async { self.paster.paste(&text) }).await;
+    if pasted.is_err() {
+      timings.paste = None;
+    }
+
+    // Recorded even when pasting failed, so the transcript can be copied from history.
+    let history_id = record_history(crate::transcription_history::AppendItemParams {
+      text: text.clone(),
+      duration_secs,
+      language,
+      mode_id,
+      mode_name,
+      original_text,
+      step_outputs,
+      rejected_format,
+      provider,
+      deferred: false,
+      timings_ms: Some(timings),
+    });
+    if let Err(e) = pasted {
+      self.keep_unpasted(UnpastedText {
+        text,
+        history_id,
+        timings,
+      });
+      return Err(e);
+    }
+
+    self.finish_paste(settings, text.clone(), timings, on_update);
+    Ok(text)
+  }
+
+  /// Paste text that was transcribed and formatted before the paste failed,
+  /// adding the paste time to its history entry.
+  async fn paste_again<F>(
+    &self,
+    unpasted: UnpastedText,
+    settings: &AppSettings,
+    on_update: &mut F,
+  ) -> Result<String, DiktError>
+  where
+    F: FnMut(DictationUpdate),
+  {
+    let UnpastedText {
+      text,
+      history_id,
+      mut timings,
+    } = unpasted;
+    stage("paste", &mut timings.paste, async { self.paster.paste(&text) }).await?;
+
+    if let Some(id) = history_id {
+      if let Err(e) =
+        crate::transcription_history::update_item(&id, |item| item.timings_ms = Some(timings))
+      {
+        warn!(error = %e, "failed to update transcription history");
+      }
+    }
+
+    self.finish_paste(settings, text.clone(), timings, on_update);
+    Ok(text)
+  }
+
+  /// Copy the pasted text if the settings ask for it and report `Done`.
+  fn finish_paste<F>(&self, settings: &AppSettings, text: String, timings: StageTimings, on_update: &mut F)
+  where
+    F: FnMut(DictationUpdate),
+  {
+    if settings.copy_to_clipboard_on_success {
+      if let Err(copy_err) = self.paster.copy(&text) {
+        warn!(error = %copy_err, "failed to copy transcript to clipboard");
+      }
+    }
+
+    {
+      let _ = self.set_state(DictationState::Done);
+    }
+    on_update(
+      DictationUpdate::new(DictationState::Done)
+        .text(text)
+        .timings(timings),
+    );
+  }
+
+  /// Transcribe the oldest dictation in the offline queue into history.
+  ///
+  /// Current credentials are used with the mode and vocabulary it was recorded
+  /// under. Nothing is pasted, since whatever window has focus now is unrelated.
+  /// Returns the transcript, or `None` when the queue is empty.
+  pub async fn process_next_queued(&self) -> Result<Option<String>, DiktError> {
+    let Some(queue) = self.offline_queue.as_ref() else {
+      return Ok(None);
+    };
+    let Some((queued, chunks)) = queue.next()? else {
+      return Ok(None);
+    };
+    let span = info_span!("queued_dictation", id = %queued.id);
+    self
+      .process_queued(queue.as_ref(), queued, chunks)
+      .instrument(span)
+      .await
+  }
+
+  async fn process_queued(
+    &self,
+    queue: &dyn OfflineQueue,
+    queued: QueuedDictation,
+    chunks: Vec<AudioChunk>,
+  ) -> Result<Option<String>, DiktError> {
+    let settings = AppSettings {
+      active_mode_id: queued.active_mode_id.clone(),
+      vocabulary: queued.vocabulary.clone(),
+      ..self.get_settings()?
+    };
+    let local_duration = recording_duration_secs(&chunks);
+    let prompt = build_vocabulary_prompt(&settings.vocabulary);
+    let cancel = CancellationToken::new();
+
+    let mut timings = StageTimings::default();
+    let transcribed = stage(
+      "transcribe",
+      &mut timings.transcribe,
+      self.transcribe_chunks(&settings, chunks, prompt.as_deref(), &cancel),
+    );
+    let result = match transcribed.await {
+      Ok(result) => result,
+      Err(e) if e.is_unreachable() => return Err(e),
+      Err(e) => {
+        let attempts = queue.record_attempt(&queued.id)?;
+        if attempts >= MAX_QUEUED_ATTEMPTS {
+          queue.remove(&queued.id)?;
+          return Err(e.context(&format!(
+            "Dropped a queued dictation after {attempts} failed attempts"
+          )));
+        }
+        return Err(e);
+      }
+    };
+
+    let text = apply_vocabulary_replacements(&result.text, &settings.vocabulary);
+    timings.split_upload(result.upload_ms);
+
+    // The selection a transform applied to is long gone, so those modes are skipped.
+    let mode = active_mode(&settings).filter(|mode| mode.kind != ModeKind::Transform);
+    // The desktop has moved on since this was recorded, so only the
+    // variables that do not depend on it are filled in.
+    let variables = prompt_variables(
+      &settings,
+      result.language.as_deref(),
+      PromptVariables::default(),
+    );
+    let formatted = stage(
+      "format",
+      &mut timings.format,
+      // Nothing shows a queued dictation's progress, so partials are dropped.
+      self.apply_mode(&settings, mode, variables, text, &cancel, Box::new(|_| {})),
+    )
+    .await?;
+    if mode.is_none() {
+      timings.format = None;
+    }
+
+    record_history(crate::transcription_history::AppendItemParams {
+      text: formatted.text.clone(),
+      duration_secs: result.duration_secs.or(local_duration),
+      language: result.language,
+      mode_id: formatted.mode_id,
+      mode_name: formatted.mode_name,
+      original_text: formatted.original_text,
+      step_outputs: formatted.step_outputs,
+      rejected_format: formatted.rejected_format,
+      provider: result.provider,
+      deferred: true,
+      timings_ms: Some(timings),
+    });
+    queue.remove(&queued.id)?;
+    info!("queued dictation transcribed into history");
+
+    Ok(Some(formatted.text))
+  }
+
+  /// Run the transcript through `mode`'s steps with their prompt variables
+  /// expanded, keeping the original text if a step fails. A transform mode fails
+  /// instead, since pasting the spoken instruction would overwrite the selection.
+  async fn apply_mode(
+    &self,
+    settings: &AppSettings,
+    mode: Option<&Mode>,
+    variables: PromptVariables,
+    text: String,
+    cancel: &CancellationToken,
+    on_partial: PartialTextSink,
+  ) -> Result<FormattedText, DiktError> {
+    let Some(mode) = mode else {
+      return Ok(FormattedText {
+        text,
+        mode_id: None,
+        mode_name: None,
+        original_text: None,
+        step_outputs: None,
+        rejected_format: None,
+      });
+    };
+
+    let input = match mode.kind {
+      ModeKind::Format => text.clone(),
+      ModeKind::Transform => {
+        let target = variables
+          .selected_text
+          .as_deref()
+          .or(variables.clipboard.as_deref())
+          .ok_or_else(|| {
+            DiktError::Other("Select or copy the text to transform, then dictate the instruction".to_string())
+          })?;
+        transform_request(&text, target)
+      }
+    };
+    let (result, step_outputs) = self
+      .run_steps(settings, mode, &variables, input, cancel, on_partial)
+      .await;
+    let rejected_format = result
+      .as_ref()
+      .ok()
+      .and_then(|_| reply_rejection(mode, &text, &step_outputs));
+    // A single step's output is the result itself.
+    let step_outputs = (!mode.steps.is_empty()).then_some(step_outputs);
+    match result {
+      Ok(_) if rejected_format.is_some() => {
+        warn!(
+          reason = rejected_format.as_ref().map(|rejected| rejected.reason.as_str()),
+          "formatted text reads like a reply, using original text"
+        );
+        Ok(FormattedText {
+          text,
+          mode_id: Some(mode.id.clone()),
+          mode_name: Some(mode.name.clone()),
+          original_text: None,
+          step_outputs,
+          rejected_format,
+        })
+      }
+      Ok(formatted) => {
+        let original_text = (formatted != text).then_some(text);
+        Ok(FormattedText {
+          text: formatted,
+          mode_id: Some(mode.id.clone()),
+          mode_name: Some(mode.name.clone()),
+          original_text,
+          step_outputs,
+          rejected_format: None,
+        })
+      }
+      Err(_) if cancel.is_cancelled() => Err(DiktError::Cancelled),
+      Err(e) if mode.kind == ModeKind::Transform => Err(e),
+      Err(e) => {
+        warn!(error = %e, "formatting failed, using original text");
+        Ok(FormattedText {
+          text,
+          mode_id: None,
+          mode_name: None,
+          original_text: None,
+          step_outputs,
+          rejected_format: None,
+        })
+      }
+    }
+  }
+
+  /// Run `mode`'s pipeline, feeding each step the previous output. Stops at the
+  /// first failing step; the outputs so far are returned either way.
+  async fn run_steps(
+    &self,
+    settings: &AppSettings,
+    mode: &Mode,
+    variables: &PromptVariables,
+    input: String,
+    cancel: &CancellationToken,
+    on_partial: PartialTextSink,
+  ) -> (Result<String, DiktError>, Vec<StepOutput>) {
+    // Shared so every chat step can stream into the pill.
+    let on_partial = Arc::new(on_partial);
+    let mut text = input;
+    let mut outputs = Vec::new();
+    let pipeline = mode.pipeline();
+    // Examples show what the mode as a whole should produce, so they go with
+    // the chat step whose output is pasted.
+    let last_llm = pipeline
+      .iter()
+      .rposition(|step| matches!(step, ModeStep::Llm { .. }));
+
+    for (index, step) in pipeline.iter().enumerate() {
+      let result = match step {
+        ModeStep::Llm {
+          system_prompt,
+          model,
+        } => {
+          let step_mode = Mode {
+            system_prompt: template::render(system_prompt, variables),
+            model: model
+              .clone()
+              .filter(|model| !model.trim().is_empty())
+              .unwrap_or_else(|| mode.model.clone()),
+            steps: Vec::new(),
+            examples: if Some(index) == last_llm {
+              mode.examples.clone()
+            } else {
+              Vec::new()
+            },
+            ..mode.clone()
+          };
+          let on_partial = on_partial.clone();
+          self
             .formatter
             .format(
-              &settings.base_url,
-              &settings.api_key,
-              &mode.model,
-              &mode.system_prompt,
+              settings,
+              &step_mode,
               &text,
+              cancel,
+              Box::new(move |partial| (*on_partial)(partial)),
             )
             .await
-          {
-            Ok(formatted) => {
-              mode_name = Some(mode.name.clone());
-              if formatted != text {
-                original_text = Some(text);
-              }
-              formatted
-            }
-            Err(e) => {
-              eprintln!("Formatting failed, using original text: {e}");
-              text
-            }
-          }
-        } else {
-          text
         }
-      } else {
-        text
+        ModeStep::Vocabulary => Ok(apply_vocabulary_replacements(&text, &settings.vocabulary)),
+        ModeStep::Regex {
+          pattern,
+          replacement,
+        } => Regex::new(pattern)
+          .map(|regex| regex.replace_all(&text, replacement.as_str()).into_owned())
+          .map_err(|e| DiktError::Other(format!("Invalid regex step: {e}"))),
       };
 
-      if let Err(e) = crate::transcription_history::append_item(
-        crate::transcription_history::AppendItemParams {
-          text: text.clone(),
-          duration_secs,
-          language,
-          mode_name,
-          original_text,
-        },
-      ) {
-        eprintln!("Failed to save transcription history: {e}");
-        crate::transcription_history::record_runtime_error(format!(
-          "Failed to save transcription history: {e}"
-        ));
+      match result {
+        Ok(output) => {
+          outputs.push(StepOutput {
+            step: step.label().to_string(),
+            text: output.clone(),
+            error: None,
+          });
+          text = output;
+        }
+        Err(e) => {
+          outputs.push(StepOutput {
+            step: step.label().to_string(),
+            text: String::new(),
+            error: Some(e.to_string()),
+          });
+          return (Err(e), outputs);
+        }
       }
+    }
 
-      {
-        let _ = self.set_state(DictationState::Pasting);
+    (Ok(text), outputs)
+  }
+
+  /// Return to Idle after a run and report how it ended.
+  fn finish_run<F>(
+    &self,
+    result: Result<String, DiktError>,
+    cancel: &CancellationToken,
+    mut on_update: F,
+  ) -> Result<String, DiktError>
+  where
+    F: FnMut(DictationUpdate),
+  {
+    let _ = self.set_state(DictationState::Idle);
+    if let Ok(mut partial) = self.partial_text.lock() {
+      *partial = None;
+    }
+
+    match result {
+      Ok(text) => {
+        info!(chars = text.chars().count(), "dictation finished");
+        self.set_failed_dictation(None);
+        Ok(text)
       }
-      on_update(DictationUpdate::new(DictationState::Pasting));
+      Err(_) if cancel.is_cancelled() => {
+        info!("dictation cancelled");
+        // Cancelling is a deliberate discard, so there is nothing to retry.
+        self.set_failed_dictation(None);
+        on_update(DictationUpdate::new(DictationState::Cancelled));
+        Err(DiktError::Cancelled)
+      }
+      Err(err) => {
+        warn!(error = %err, code = err.code(), "dictation failed");
+        let mut update = DictationUpdate::new(DictationState::Error).error(err.clone());
+        if self.can_retry() {
+          update = update.can_retry();
+        }
+        on_update(update);
+        Err(err)
+      }
+    }
+  }
+
+  /// Queue the kept recording when transcription could not reach the provider.
+  /// Only the transcribe stage decides this: a later stage failing (an
+  /// unreachable formatter, say) keeps the recording for `retry_last_dictation`,
+  /// since the queue would transcribe it again and skip what failed.
+  fn queue_if_unreachable(&self, err: DiktError, cancel: &CancellationToken) -> DiktError {
+    if !err.is_unreachable() || cancel.is_cancelled() || !self.queue_failed_dictation() {
+      return err;
+    }
+    warn!(error = %err, "provider unreachable, recording queued");
+    DiktError::Network(
+      "the recording is queued and will be transcribed once it is back".to_string(),
+    )
+  }
+
+  /// Move the kept recording into the offline queue. Returns false, leaving it
+  /// available to retry, when there is no queue or saving fails.
+  fn queue_failed_dictation(&self) -> bool {
+    let Some(queue) = self.offline_queue.as_ref() else {
+      return false;
+    };
+    let Some(failed) = self.failed_dictation.lock().ok().and_then(|mut failed| failed.take()) else {
+      return false;
+    };
+
+    match queue.enqueue(&failed.chunks, &failed.settings) {
+      Ok(()) => true,
+      Err(e) => {
+        warn!(error = %e, "failed to queue dictation for later");
+        self.set_failed_dictation(Some(failed));
+        false
+      }
+    }
+  }
+
+  fn set_failed_dictation(&self, failed: Option<FailedDictation>) {
+    if let Ok(mut guard) = self.failed_dictation.lock() {
+      *guard = failed;
+    }
+  }
 
-      self.paster.paste(&text)?;
-      if settings.copy_to_clipboard_on_success {
-        if let Err(copy_err) = self.paster.copy(&text) {
-          eprintln!("Failed to copy transcript to clipboard: {copy_err}");
+  fn keep_unpasted(&self, unpasted: UnpastedText) {
+    if let Ok(mut guard) = self.failed_dictation.lock() {
+      if let Some(failed) = guard.as_mut() {
+        failed.unpasted = Some(unpasted);
+      }
+    }
+  }
+
+  fn can_retry(&self) -> bool {
+    self
+      .failed_dictation
+      .lock()
+      .map(|failed| failed.is_some())
+      .unwrap_or(false)
+  }
+
+  /// Abort the current dictation at whatever stage it is in; nothing gets pasted.
+  ///
+  /// A recording is stopped and discarded here. Later stages are running inside
+  /// `stop_and_process`, which sees the cancelled token, drops its pending
+  /// requests and reports `Cancelled` itself. Pasting is too late to undo.
+  pub fn cancel<F>(&self, mut on_update: F) -> Result<(), DiktError>
+  where
+    F: FnMut(DictationUpdate),
+  {
+    let was_recording = {
+      let mut state = self.state.lock().map_err(|_| "State lock poisoned".to_string())?;
+      match *state {
+        DictationState::Idle => return Ok(()),
+        DictationState::Recording => {
+          *state = DictationState::Cancelled;
+          true
         }
+        _ => false,
       }
+    };
 
-      {
-        let _ = self.set_state(DictationState::Done);
+    self
+      .cancel
+      .lock()
+      .map_err(|_| "Cancel lock poisoned".to_string())?
+      .cancel();
+
+    if was_recording {
+      let session = self
+        .take_recording_span()
+        .map(|recording| recording.session)
+        .unwrap_or_else(Span::none);
+      let _entered = session.enter();
+      info!("recording cancelled");
+      self.abort_stream();
+      if let Err(e) = self.recorder.stop() {
+        warn!(error = %e, "failed to stop recorder on cancel");
       }
-      on_update(DictationUpdate::new(DictationState::Done).text(text.clone()));
+      if let Ok(mut partial) = self.partial_text.lock() {
+        *partial = None;
+      }
+      on_update(DictationUpdate::new(DictationState::Cancelled));
+      self.set_state(DictationState::Idle)?;
+    }
+
+    Ok(())
+  }
 
-      Ok::<_, String>(text)
+  /// Latest live transcript not yet delivered, as an update for the current state.
+  ///
+  /// Polled by the UI event loop; returns `None` outside recording, transcribing
+  /// and formatting so a late partial never overrides a finished session.
+  pub fn take_partial_update(&self) -> Option<DictationUpdate> {
+    let state = *self.state.lock().ok()?;
+    if !matches!(
+      state,
+      DictationState::Recording | DictationState::Transcribing | DictationState::Formatting
+    ) {
+      return None;
     }
-    .await;
+    let text = self.partial_text.lock().ok()?.take()?;
+    Some(DictationUpdate::new(state).partial_text(text))
+  }
 
-    // Always return to Idle at the end of a run.
-    let _ = self.set_state(DictationState::Idle);
+  /// Sink that replaces the pending partial text, cleared so the preview starts
+  /// fresh (the formatted text must not be appended to the live transcript).
+  fn partial_text_sink(&self) -> PartialTextSink {
+    let partial_text = Arc::clone(&self.partial_text);
+    if let Ok(mut partial) = partial_text.lock() {
+      *partial = None;
+    }
+    Box::new(move |text: String| {
+      if let Ok(mut partial) = partial_text.lock() {
+        *partial = Some(text);
+      }
+    })
+  }
 
-    match result {
-      Ok(text) => Ok(text),
-      Err(err) => {
-        on_update(DictationUpdate::new(DictationState::Error).message(err.clone()));
-        Err(err)
+  /// Open a streaming transcription for a new recording and return the sink
+  /// the recorder should feed. Falls back to upload-only on failure.
+  fn start_stream(&self, settings: &AppSettings) -> Option<FrameSink> {
+    let streaming = Arc::clone(self.streaming_transcriber.as_ref()?);
+    let runtime = match tokio::runtime::Handle::try_current() {
+      Ok(runtime) => runtime,
+      Err(_) => {
+        warn!("streaming transcription needs an async runtime, uploading after recording instead");
+        return None;
+      }
+    };
+
+    let (sender, frames) = tokio::sync::mpsc::unbounded_channel();
+    let sample_rate = streaming.sample_rate(settings);
+    let settings = settings.clone();
+    let on_partial = self.partial_text_sink();
+
+    let task = runtime.spawn(
+      async move {
+        let prompt = build_vocabulary_prompt(&settings.vocabulary);
+        streaming
+          .transcribe_stream(&settings, frames, prompt.as_deref(), on_partial)
+          .await
       }
+      .in_current_span(),
+    );
+
+    *self.stream_task.lock().ok()? = Some(task);
+    Some(FrameSink { sender, sample_rate })
+  }
+
+  fn abort_stream(&self) {
+    if let Some(task) = self.stream_task.lock().ok().and_then(|mut task| task.take()) {
+      task.abort();
     }
   }
 
-  fn set_state(&self, next: DictationState) -> Result<(), String> {
+  async fn transcribe_chunks(
+    &self,
+    settings: &AppSettings,
+    mut chunks: Vec<AudioChunk>,
+    prompt: Option<&str>,
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError> {
+    if chunks.len() == 1 {
+      let chunk = chunks.remove(0);
+      return self
+        .transcriber
+        .transcribe(settings, chunk.audio, prompt, cancel)
+        .await;
+    }
+
+    let total = chunks.len();
+    let parts = futures_util::stream::iter(chunks.into_iter().enumerate())
+      .map(|(index, chunk)| async move {
+        let result = self
+          .transcribe_chunk(settings, chunk.audio, prompt, cancel)
+          .await
+          .map_err(|e| e.context(&format!("Chunk {} of {total} failed", index + 1)))?;
+        Ok::<_, DiktError>((chunk.window, result))
+      })
+      .buffered(MAX_CONCURRENT_CHUNKS)
+      .try_collect::<Vec<_>>()
+      .await?;
+
+    Ok(stitch_transcriptions(parts))
+  }
+
+  /// Transcribe one chunk of a long recording, retrying it on its own so a
+  /// transient failure does not discard the chunks that already succeeded.
+  /// Retries wait out the backoff so they do not pile onto a struggling server.
+  /// Other errors would fail again the same way, so they end the run at once.
+  async fn transcribe_chunk(
+    &self,
+    settings: &AppSettings,
+    audio: EncodedAudio,
+    prompt: Option<&str>,
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError> {
+    let mut attempt = 1;
+    loop {
+      match self
+        .transcriber
+        .transcribe(settings, audio.clone(), prompt, cancel)
+        .await
+      {
+        Ok(result) => return Ok(result),
+        Err(e) if e.is_transient() && attempt < MAX_CHUNK_ATTEMPTS && !cancel.is_cancelled() => {
+          // Each request has already been retried per `retry_policy`, so
+          // continue its backoff rather than starting over.
+          let policy = &settings.retry_policy;
+          let delay = retry::delay(policy, policy.max_attempts.saturating_add(attempt as u32));
+          warn!(attempt, error = %e, ?delay, "chunk transcription failed, retrying");
+          tokio::select! {
+            _ = cancel.cancelled() => return Err(DiktError::Cancelled),
+            _ = tokio::time::sleep(delay) => {}
+          }
+          attempt += 1;
+        }
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  fn set_state(&self, next: DictationState) -> Result<(), DiktError> {
     let mut state = self.state.lock().map_err(|_| "State lock poisoned".to_string())?;
     *state = next;
     Ok(())
   }
+
+  /// Span around one dictation, from recording (or retry) to paste.
+  fn session_span(&self, retry: bool) -> Span {
+    let session = self.sessions.fetch_add(1, Ordering::Relaxed) + 1;
+    info_span!("dictation", session, retry)
+  }
+
+  /// Read the desktop prompt variables the mode this recording will run with
+  /// needs, while the window the user dictates into still has focus.
+  fn capture_desktop(&self, settings: AppSettings) {
+    let Some(context) = self.desktop_context.as_deref() else {
+      return;
+    };
+    let Ok(mut recording) = self.recording.lock() else {
+      return;
+    };
+    let Some(recording) = recording.as_mut() else {
+      return;
+    };
+    let settings = apply_mode_rules(settings, recording.window.as_ref());
+    let mode = active_mode(&settings);
+    recording.desktop = desktop_variables(mode, recording.window.as_ref(), context);
+  }
+
+  fn take_recording_span(&self) -> Option<RecordingSpan> {
+    self.recording.lock().ok().and_then(|mut recording| recording.take())
+  }
 }
 
-fn build_vocabulary_prompt(vocabulary: &[VocabularyEntry]) -> Option<String> {
-  let words: Vec<&str> = vocabulary
+/// Run one stage of a dictation in its own span, log how long it took and
+/// store that in `elapsed`.
+async fn stage<T>(
+  name: &'static str,
+  elapsed: &mut Option<u64>,
+  run: impl Future<Output = Result<T, DiktError>>,
+) -> Result<T, DiktError> {
+  let span = info_span!("stage", stage = name);
+  let started = Instant::now();
+  let result = run.instrument(span.clone()).await;
+  *elapsed = Some(log_stage_end(&span, started, &result));
+  result
+}
+
+fn log_stage_end<T>(span: &Span, started: Instant, result: &Result<T, DiktError>) -> u64 {
+  let elapsed_ms = elapsed_ms(started);
+  span.in_scope(|| match result {
+    Ok(_) => info!(elapsed_ms, "finished"),
+    Err(e) => warn!(elapsed_ms, error = %e, code = e.code(), "failed"),
+  });
+  elapsed_ms
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+  started.elapsed().as_millis() as u64
+}
+
+fn active_mode(settings: &AppSettings) -> Option<&Mode> {
+  let mode_id = settings.active_mode_id.as_ref()?;
+  settings.modes.iter().find(|mode| &mode.id == mode_id)
+}
+
+/// Settings for a dictation started in `window`: the first mode rule matching
+/// it replaces `active_mode_id`. Rules naming a deleted mode are skipped.
+fn apply_mode_rules(mut settings: AppSettings, window: Option<&FocusedWindow>) -> AppSettings {
+  let Some(window) = window else {
+    return settings;
+  };
+  let mode_id = settings
+    .mode_rules
+    .iter()
+    .filter(|rule| {
+      rule
+        .mode_id
+        .as_ref()
+        .is_none_or(|mode_id| settings.modes.iter().any(|mode| &mode.id == mode_id))
+    })
+    .find(|rule| rule.matches(window))
+    .map(|rule| rule.mode_id.clone());
+  if let Some(mode_id) = mode_id {
+    info!(app = window.app.as_deref(), mode_id = mode_id.as_deref(), "mode picked by window rule");
+    settings.active_mode_id = mode_id;
+  }
+  settings
+}
+
+/// Save a history entry, returning its id unless saving failed.
+fn record_history(params: crate::transcription_history::AppendItemParams) -> Option<String> {
+  match crate::transcription_history::append_item(params) {
+    Ok(id) => Some(id),
+    Err(e) => {
+      warn!(error = %e, "failed to save transcription history");
+      crate::transcription_history::record_runtime_error(format!(
+        "Failed to save transcription history: {e}"
+      ));
+      None
+    }
+  }
+}
+
+/// The desktop variables `mode`'s prompt uses; the desktop is only queried
+/// for those, since reading the selection goes through the clipboard.
+fn desktop_variables(
+  mode: Option<&Mode>,
+  window: Option<&FocusedWindow>,
+  desktop: &dyn DesktopContext,
+) -> PromptVariables {
+  let Some(mode) = mode else {
+    return PromptVariables::default();
+  };
+  let pipeline = mode.pipeline();
+  let uses = |variable| {
+    pipeline
+      .iter()
+      .filter_map(ModeStep::system_prompt)
+      .any(|prompt| template::uses(prompt, variable))
+  };
+
+  // A transform needs the selection, or the clipboard when nothing is selected.
+  let transform = mode.kind == ModeKind::Transform;
+  let selected_text = (uses(template::SELECTED_TEXT) || transform)
+    .then(|| desktop.selected_text())
+    .flatten();
+  let clipboard = (uses(template::CLIPBOARD) || (transform && selected_text.is_none()))
+    .then(|| desktop.clipboard_text())
+    .flatten();
+
+  PromptVariables {
+    clipboard,
+    selected_text,
+    active_app: window
+      .filter(|_| uses(template::ACTIVE_APP))
+      .and_then(|window| window.app.clone()),
+    user_name: uses(template::USER_NAME)
+      .then(|| desktop.user_name())
+      .flatten(),
+    ..PromptVariables::default()
+  }
+}
+
+/// All prompt variables for a run: `desktop`'s, read when recording started,
+/// plus the date, time, language and vocabulary.
+fn prompt_variables(
+  settings: &AppSettings,
+  language: Option<&str>,
+  desktop: PromptVariables,
+) -> PromptVariables {
+  let now = chrono::Local::now();
+  PromptVariables {
+    date: Some(now.format("%Y-%m-%d").to_string()),
+    time: Some(now.format("%H:%M").to_string()),
+    language: language.map(str::to_string),
+    vocabulary: Some(vocabulary_words(&settings.vocabulary).join(", ")),
+    ..desktop
+  }
+}
+
+/// The output of `mode`'s first chat step, when it reads like a reply to its
+/// input rather than that input formatted. Only that step sees the dictation;
+/// later ones get its output, and regex or vocabulary steps cannot reply.
+fn reply_rejection(mode: &Mode, transcript: &str, outputs: &[StepOutput]) -> Option<RejectedFormat> {
+  // A transform's output is meant to differ from the spoken instruction, and
+  // JSON output shares little with the words spoken.
+  if mode.kind != ModeKind::Format || !mode.reply_check || mode.params.json_schema.is_some() {
+    return None;
+  }
+  let index = mode
+    .pipeline()
+    .iter()
+    .position(|step| matches!(step, ModeStep::Llm { .. }))?;
+  let output = outputs.get(index).filter(|output| output.error.is_none())?;
+  let input = match index.checked_sub(1) {
+    Some(previous) => outputs.get(previous)?.text.as_str(),
+    None => transcript,
+  };
+  let reason = reply_check::check(input, &output.text).err()?;
+  Some(RejectedFormat {
+    text: output.text.clone(),
+    reason: reason.to_string(),
+  })
+}
+
+/// The example `item` makes for `mode`. Examples go with the last chat step,
+/// so a pipeline's example is that step's input and output, not the transcript.
+fn history_example(mode: &Mode, item: &TranscriptionHistoryItem) -> Result<ModeExample, DiktError> {
+  // History keeps a transform's instruction but not the text it applied to.
+  if mode.kind == ModeKind::Transform {
+    return Err("Transform results cannot become examples".into());
+  }
+  let original_text = item
+    .original_text
+    .clone()
+    .ok_or("Only history items a mode changed can become examples")?;
+  let Some(outputs) = &item.step_outputs else {
+    return Ok(ModeExample {
+      input: original_text,
+      output: item.text.clone(),
+    });
+  };
+
+  let changed = || DiktError::from("The mode's steps changed since this was recorded");
+  let pipeline = mode.pipeline();
+  let index = pipeline
+    .iter()
+    .rposition(|step| matches!(step, ModeStep::Llm { .. }))
+    .ok_or("The mode has no chat step to learn from")?;
+  let output = outputs
+    .get(index)
+    .filter(|output| output.step == pipeline[index].label() && output.error.is_none())
+    .ok_or_else(changed)?;
+  let input = match index.checked_sub(1) {
+    Some(previous) => outputs.get(previous).ok_or_else(changed)?.text.clone(),
+    None => original_text,
+  };
+  Ok(ModeExample {
+    input,
+    output: output.text.clone(),
+  })
+}
+
+/// Reject prompts with unknown variables and regex steps that do not compile.
+fn validate_steps(mode: &Mode) -> Result<(), DiktError> {
+  for step in mode.pipeline().iter() {
+    match step {
+      ModeStep::Llm { system_prompt, .. } => template::validate(system_prompt)?,
+      ModeStep::Vocabulary => {}
+      ModeStep::Regex { pattern, .. } => {
+        Regex::new(pattern).map_err(|e| DiktError::Other(format!("Invalid regex step: {e}")))?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Refuse an OpenAI-compatible mode that names a provider with no known server
+/// and no base URL of its own, rather than sending that provider's key to the
+/// transcription server.
+fn validate_endpoint(settings: &AppSettings, mode: &Mode) -> Result<(), DiktError> {
+  if mode.backend != FormatterBackend::OpenaiCompatible || mode.custom_base_url().is_some() {
+    return Ok(());
+  }
+  match mode.other_provider(&settings.provider) {
+    Some(provider) if provider_base_url(provider).is_none() => Err(DiktError::Other(format!(
+      "Set a base URL for provider \"{provider}\""
+    ))),
+    _ => Ok(()),
+  }
+}
+
+/// User message for a transform mode: the spoken instruction and the text it applies to.
+fn transform_request(instruction: &str, target: &str) -> String {
+  format!("Instruction: {instruction}\n\nText:\n{target}")
+}
+
+/// Enabled vocabulary words, capped at the number sent as a prompt.
+fn vocabulary_words(vocabulary: &[VocabularyEntry]) -> Vec<&str> {
+  vocabulary
     .iter()
     .filter(|entry| entry.enabled)
     .map(|entry| entry.word.trim())
     .filter(|word| !word.is_empty())
     .take(MAX_PROMPT_ENTRIES)
-    .collect();
+    .collect()
+}
+
+fn build_vocabulary_prompt(vocabulary: &[VocabularyEntry]) -> Option<String> {
+  let words = vocabulary_words(vocabulary);
 
   if words.is_empty() {
     return None;
@@ -286,7 +1501,7 @@ fn apply_vocabulary_replacements(text: &str, vocabulary: &[VocabularyEntry]) ->
       let regex = match Regex::new(&pattern) {
         Ok(regex) => regex,
         Err(error) => {
-          eprintln!("Invalid replacement regex '{replacement}': {error}");
+          warn!(%error, replacement, "invalid replacement regex");
           continue;
         }
       };
@@ -318,12 +1533,22 @@ fn is_word_char(ch: char) -> bool {
   ch.is_alphanumeric() || ch == '_'
 }
 
+/// Length of the whole recording: where the last chunk starts plus its own duration.
+fn recording_duration_secs(chunks: &[AudioChunk]) -> Option<f64> {
+  let last = chunks.last()?;
+  let duration = last
+    .audio
+    .duration_secs
+    .or_else(|| wav_duration_secs(&last.audio.bytes))?;
+  Some(last.window.offset_secs + duration)
+}
+
 /// Extract duration from a WAV buffer by reading the header.
 /// Returns None if the buffer is too small or the byte rate is zero.
 fn wav_duration_secs(data: &[u8]) -> Option<f64> {
   // Standard WAV: byte_rate is at offset 28 (4 bytes, little-endian).
   // The "data" sub-chunk starts after the fmt chunk; its size gives the raw audio length.
-  if data.len() < 44 {
+  if data.len() < 44 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
     return None;
   }
 
@@ -352,7 +1577,9 @@ fn wav_duration_secs(data: &[u8]) -> Option<f64> {
 
 #[cfg(test)]
 mod tests {
-  use super::{apply_vocabulary_replacements, build_vocabulary_prompt, VocabularyEntry};
+  use super::{
+    apply_vocabulary_replacements, build_vocabulary_prompt, wav_duration_secs, VocabularyEntry,
+  };
 
   #[test]
   fn build_prompt_returns_none_for_empty_vocabulary() {
@@ -409,4 +1636,24 @@ mod tests {
       "Kubernetes"
     );
   }
+
+  #[test]
+  fn wav_duration_reads_16khz_mono_output() {
+    let spec = hound::WavSpec {
+      channels: 1,
+      sample_rate: 16_000,
+      bits_per_sample: 16,
+      sample_format: hound::SampleFormat::Int,
+    };
+    let mut buffer = Vec::new();
+    {
+      let mut writer = hound::WavWriter::new(std::io::Cursor::new(&mut buffer), spec).unwrap();
+      for _ in 0..24_000 {
+        writer.write_sample(0i16).unwrap();
+      }
+      writer.finalize().unwrap();
+    }
+
+    assert_eq!(wav_duration_secs(&buffer), Some(1.5));
+  }
 }
@@ -1,6 +1,10 @@
+pub mod error;
 pub mod impls;
 pub mod manager;
 pub mod ports;
+pub mod reply_check;
+pub mod stitch;
+pub mod template;
 pub mod types;
 
 #[cfg(test)]
@@ -1,39 +1,93 @@
 use crate::settings::AppSettings;
 use crate::transcribe::TranscriptionResult;
 
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_util::sync::CancellationToken;
+
+use super::error::DiktError;
+use super::types::{AudioChunk, EncodedAudio, FocusedWindow, FrameSink, Mode, QueuedDictation};
+
 #[async_trait::async_trait]
 pub trait Formatter: Send + Sync {
+  /// Rewrite `text` with `mode`'s model and prompt on its backend (endpoint and
+  /// retry policy from `settings`), reporting the formatted text so far to
+  /// `on_partial` while it streams in.
   async fn format(
     &self,
-    base_url: &str,
-    api_key: &str,
-    model: &str,
-    system_prompt: &str,
+    settings: &AppSettings,
+    mode: &Mode,
     text: &str,
-  ) -> Result<String, String>;
+    cancel: &CancellationToken,
+    on_partial: PartialTextSink,
+  ) -> Result<String, DiktError>;
 }
 
 pub trait SettingsStore: Send + Sync {
   fn load(&self) -> AppSettings;
-  fn save(&self, settings: &AppSettings) -> Result<(), String>;
+  fn save(&self, settings: &AppSettings) -> Result<(), DiktError>;
 }
 
 pub trait Recorder: Send + Sync {
-  fn start(&self) -> Result<(), String>;
-  fn stop(&self) -> Result<Vec<u8>, String>;
+  /// Start capturing; when `frames` is set, live audio is also forwarded to it.
+  fn start(&self, settings: &AppSettings, frames: Option<FrameSink>) -> Result<(), DiktError>;
+  /// Stop capturing and return the recording, split into chunks when it is long.
+  fn stop(&self) -> Result<Vec<AudioChunk>, DiktError>;
 }
 
 pub trait Paster: Send + Sync {
-  fn paste(&self, text: &str) -> Result<(), String>;
-  fn copy(&self, text: &str) -> Result<(), String>;
+  fn paste(&self, text: &str) -> Result<(), DiktError>;
+  fn copy(&self, text: &str) -> Result<(), DiktError>;
+}
+
+/// What the user is working in, for per-app mode rules and the `{{clipboard}}`,
+/// `{{selected_text}}`, `{{active_app}}` and `{{user_name}}` prompt variables.
+/// `None` when unavailable.
+pub trait DesktopContext: Send + Sync {
+  fn clipboard_text(&self) -> Option<String>;
+  /// Text selected in the focused app; may briefly go through the clipboard.
+  fn selected_text(&self) -> Option<String>;
+  fn focused_window(&self) -> Option<FocusedWindow>;
+  fn user_name(&self) -> Option<String>;
 }
 
 #[async_trait::async_trait]
 pub trait Transcriber: Send + Sync {
+  /// Fails with `DiktError::Network` when the provider cannot be reached, and
+  /// `DiktError::Cancelled` when `cancel` fires mid-request.
   async fn transcribe(
     &self,
     settings: &AppSettings,
-    audio_wav: Vec<u8>,
+    audio: EncodedAudio,
     prompt: Option<&str>,
-  ) -> Result<TranscriptionResult, String>;
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError>;
+}
+
+/// Called with the full text so far each time a streaming transcriber or formatter produces more.
+pub type PartialTextSink = Box<dyn Fn(String) + Send + Sync>;
+
+#[async_trait::async_trait]
+pub trait StreamingTranscriber: Send + Sync {
+  /// Sample rate the endpoint expects for the frames passed to `transcribe_stream`.
+  fn sample_rate(&self, settings: &AppSettings) -> u32;
+
+  /// Transcribe frames as they arrive; resolves once the frame channel closes
+  /// and the final transcript is in.
+  async fn transcribe_stream(
+    &self,
+    settings: &AppSettings,
+    frames: UnboundedReceiver<Vec<i16>>,
+    prompt: Option<&str>,
+    on_partial: PartialTextSink,
+  ) -> Result<TranscriptionResult, DiktError>;
+}
+
+/// Durable storage for recordings made while the provider was unreachable.
+pub trait OfflineQueue: Send + Sync {
+  fn enqueue(&self, chunks: &[AudioChunk], settings: &AppSettings) -> Result<(), DiktError>;
+  /// Oldest queued dictation and its audio, if any.
+  fn next(&self) -> Result<Option<(QueuedDictation, Vec<AudioChunk>)>, DiktError>;
+  fn remove(&self, id: &str) -> Result<(), DiktError>;
+  /// Count a failed attempt and return the new total.
+  fn record_attempt(&self, id: &str) -> Result<u32, DiktError>;
 }
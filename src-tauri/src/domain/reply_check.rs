@@ -0,0 +1,153 @@
+//! Catch chat models that answer a dictated question instead of cleaning it up.
+//!
+//! A cleanup keeps most of the speaker's words at roughly the same length, so
+//! output that opens like a chat reply, shrinks or grows a lot, or trades the
+//! words for new ones is rejected and the transcript is pasted instead.
+
+use std::collections::HashSet;
+use std::fmt;
+
+/// Openers of a chat reply, matched as whole words at the start of the output.
+const REPLY_OPENERS: [&str; 12] = [
+  "sure",
+  "certainly",
+  "of course",
+  "absolutely",
+  "here is",
+  "here's",
+  "here are",
+  "i'd be happy",
+  "i'm sorry",
+  "i can't",
+  "i cannot",
+  "as an ai",
+];
+
+// Short transcripts say too little to judge by length or word choice.
+const MIN_TRANSCRIPT_WORDS: usize = 4;
+const MIN_LENGTH_RATIO: f64 = 0.3;
+const MAX_LENGTH_RATIO: f64 = 2.5;
+// Share of the output's distinct words that the speaker said.
+const MIN_WORD_OVERLAP: f64 = 0.5;
+
+/// Why formatted text was taken for a reply rather than the transcript cleaned up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+  ReplyOpener(&'static str),
+  TooShort(f64),
+  TooLong(f64),
+  LowOverlap(f64),
+}
+
+impl fmt::Display for Rejection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ReplyOpener(opener) => write!(f, "starts like a chat reply (\"{opener}\")"),
+      Self::TooShort(ratio) => write!(f, "only {:.0}% of the transcript's length", ratio * 100.0),
+      Self::TooLong(ratio) => write!(f, "{ratio:.1}x the transcript's length"),
+      Self::LowOverlap(overlap) => {
+        write!(f, "only {:.0}% of its words were dictated", overlap * 100.0)
+      }
+    }
+  }
+}
+
+/// Check that `formatted` reads like `transcript` cleaned up.
+pub fn check(transcript: &str, formatted: &str) -> Result<(), Rejection> {
+  // The speaker may have opened with "Sure" themselves.
+  if let Some(opener) = reply_opener(formatted).filter(|opener| reply_opener(transcript) != Some(*opener)) {
+    return Err(Rejection::ReplyOpener(opener));
+  }
+
+  let spoken = words(transcript);
+  if spoken.len() < MIN_TRANSCRIPT_WORDS {
+    return Ok(());
+  }
+
+  let ratio = formatted.trim().chars().count() as f64 / transcript.trim().chars().count() as f64;
+  if ratio < MIN_LENGTH_RATIO {
+    return Err(Rejection::TooShort(ratio));
+  }
+  if ratio > MAX_LENGTH_RATIO {
+    return Err(Rejection::TooLong(ratio));
+  }
+
+  let spoken: HashSet<String> = spoken.into_iter().collect();
+  let written: HashSet<String> = words(formatted).into_iter().collect();
+  if written.is_empty() {
+    return Ok(());
+  }
+  let overlap = written.intersection(&spoken).count() as f64 / written.len() as f64;
+  if overlap < MIN_WORD_OVERLAP {
+    return Err(Rejection::LowOverlap(overlap));
+  }
+  Ok(())
+}
+
+fn reply_opener(text: &str) -> Option<&'static str> {
+  let text = text.trim_start().to_lowercase().replace('\u{2019}', "'");
+  REPLY_OPENERS.into_iter().find(|opener| {
+    text
+      .strip_prefix(opener)
+      .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric()))
+  })
+}
+
+fn words(text: &str) -> Vec<String> {
+  text
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(str::to_lowercase)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn accepts_cleaned_up_transcripts() {
+    assert_eq!(
+      check(
+        "um so what time does the uh meeting start tomorrow question mark",
+        "So, what time does the meeting start tomorrow?"
+      ),
+      Ok(())
+    );
+    assert_eq!(check("sure I can do that", "Sure, I can do that."), Ok(()));
+    assert_eq!(check("ok", "OK."), Ok(()));
+  }
+
+  #[test]
+  fn rejects_replies_to_the_dictation() {
+    assert_eq!(
+      check("what is the capital of france", "Sure, the capital of France is Paris."),
+      Err(Rejection::ReplyOpener("sure"))
+    );
+    assert_eq!(
+      check("how do I undo the last commit", "Here\u{2019}s how: run git reset --soft HEAD~1."),
+      Err(Rejection::ReplyOpener("here's"))
+    );
+    assert!(matches!(
+      check(
+        "can you explain what a mutex is",
+        "A mutex lets only one thread at a time hold a lock on shared data, so concurrent writers cannot corrupt it."
+      ),
+      Err(Rejection::TooLong(_))
+    ));
+    assert!(matches!(
+      check("what is twelve times twelve", "144"),
+      Err(Rejection::TooShort(_))
+    ));
+    assert!(matches!(
+      check("what is the weather like today", "It is sunny and warm, about 24 degrees."),
+      Err(Rejection::LowOverlap(_))
+    ));
+  }
+
+  #[test]
+  fn openers_match_whole_words_only() {
+    assert_eq!(reply_opener("Surely we can ship this today"), None);
+    assert_eq!(reply_opener("Of course not"), Some("of course"));
+  }
+}
@@ -0,0 +1,198 @@
+use crate::transcribe::{TranscriptionResult, TranscriptionSegment};
+
+use super::types::ChunkWindow;
+
+// Overlap is half a second of audio, so only a handful of words can repeat.
+const MAX_OVERLAP_WORDS: usize = 8;
+
+/// Join per-chunk transcriptions, in recording order, into one result.
+///
+/// Segment timestamps are shifted onto the recording's timeline and segments
+/// outside a chunk's keep window are dropped, which removes the words heard
+/// twice in the overlap. Chunks without segments fall back to trimming words
+/// that repeat across the seam.
+pub fn stitch_transcriptions(parts: Vec<(ChunkWindow, TranscriptionResult)>) -> TranscriptionResult {
+  let mut text = String::new();
+  let mut segments: Vec<TranscriptionSegment> = Vec::new();
+  let mut all_have_segments = true;
+  let mut language = None;
+  let mut duration_secs = None;
+  let mut providers: Vec<String> = Vec::new();
+  let mut upload_ms = None;
+
+  for (window, result) in parts {
+    language = language.or(result.language);
+    // With failover, chunks of one recording may be served by different providers.
+    if let Some(provider) = result.provider.filter(|provider| !providers.contains(provider)) {
+      providers.push(provider);
+    }
+    duration_secs = result.duration_secs.map(|duration| window.offset_secs + duration);
+    // Chunks upload side by side, so the slowest one is what the user waited for.
+    upload_ms = upload_ms.max(result.upload_ms);
+
+    let piece = match result.segments.filter(|segments| !segments.is_empty()) {
+      Some(chunk_segments) => {
+        let kept: Vec<TranscriptionSegment> = chunk_segments
+          .into_iter()
+          .map(|segment| TranscriptionSegment {
+            start: segment.start + window.offset_secs,
+            end: segment.end + window.offset_secs,
+            text: segment.text,
+          })
+          .filter(|segment| in_keep_window(&window, (segment.start + segment.end) / 2.0))
+          .collect();
+        let piece = join_words(kept.iter().map(|segment| segment.text.as_str()));
+        segments.extend(kept);
+        piece
+      }
+      None => {
+        all_have_segments = false;
+        drop_repeated_prefix(&text, result.text.trim())
+      }
+    };
+
+    if !piece.is_empty() {
+      if !text.is_empty() {
+        text.push(' ');
+      }
+      text.push_str(&piece);
+    }
+  }
+
+  TranscriptionResult {
+    text,
+    duration_secs,
+    language,
+    segments: all_have_segments.then_some(segments),
+    provider: (!providers.is_empty()).then(|| providers.join(", ")),
+    upload_ms,
+  }
+}
+
+fn in_keep_window(window: &ChunkWindow, time: f64) -> bool {
+  time >= window.keep_from_secs && window.keep_until_secs.is_none_or(|until| time < until)
+}
+
+fn join_words<'a>(pieces: impl Iterator<Item = &'a str>) -> String {
+  pieces
+    .map(str::trim)
+    .filter(|piece| !piece.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Remove the longest run of words at the start of `next` that repeats the end of `previous`.
+fn drop_repeated_prefix(previous: &str, next: &str) -> String {
+  let previous_words: Vec<String> = previous.split_whitespace().map(normalize_word).collect();
+  let next_words: Vec<&str> = next.split_whitespace().collect();
+
+  let max = MAX_OVERLAP_WORDS
+    .min(previous_words.len())
+    .min(next_words.len());
+  let repeated = (1..=max)
+    .rev()
+    .find(|&count| {
+      previous_words[previous_words.len() - count..]
+        .iter()
+        .zip(&next_words[..count])
+        .all(|(a, b)| *a == normalize_word(b))
+    })
+    .unwrap_or(0);
+
+  next_words[repeated..].join(" ")
+}
+
+fn normalize_word(word: &str) -> String {
+  word
+    .chars()
+    .filter(|ch| ch.is_alphanumeric())
+    .flat_map(char::to_lowercase)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn window(offset_secs: f64, keep_from_secs: f64, keep_until_secs: Option<f64>) -> ChunkWindow {
+    ChunkWindow {
+      offset_secs,
+      keep_from_secs,
+      keep_until_secs,
+    }
+  }
+
+  fn segment(start: f64, end: f64, text: &str) -> TranscriptionSegment {
+    TranscriptionSegment {
+      start,
+      end,
+      text: text.to_string(),
+    }
+  }
+
+  fn result(text: &str, segments: Option<Vec<TranscriptionSegment>>) -> TranscriptionResult {
+    TranscriptionResult {
+      text: text.to_string(),
+      duration_secs: Some(60.5),
+      language: Some("english".to_string()),
+      segments,
+      provider: Some("groq".to_string()),
+      upload_ms: Some(40),
+    }
+  }
+
+  #[test]
+  fn segments_are_offset_and_trimmed_to_keep_windows() {
+    let first = result(
+      "Hello there. General",
+      Some(vec![segment(0.0, 59.0, "Hello there."), segment(59.8, 60.4, "General")]),
+    );
+    let second = result(
+      "General Kenobi.",
+      Some(vec![segment(0.25, 2.0, "General Kenobi.")]),
+    );
+
+    let stitched = stitch_transcriptions(vec![
+      (window(0.0, 0.0, Some(60.0)), first),
+      (window(59.5, 60.0, None), second),
+    ]);
+
+    assert_eq!(stitched.text, "Hello there. General Kenobi.");
+    let segments = stitched.segments.unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[1].start, 59.75);
+    assert_eq!(segments[1].end, 61.5);
+    assert_eq!(stitched.duration_secs, Some(120.0));
+  }
+
+  #[test]
+  fn plain_text_chunks_drop_repeated_words_at_the_seam() {
+    let stitched = stitch_transcriptions(vec![
+      (window(0.0, 0.0, Some(60.0)), result("We should ship it on", None)),
+      (window(59.5, 60.0, None), result("On Friday, then.", None)),
+    ]);
+
+    assert_eq!(stitched.text, "We should ship it on Friday, then.");
+    assert!(stitched.segments.is_none());
+    assert_eq!(stitched.provider.as_deref(), Some("groq"));
+  }
+
+  #[test]
+  fn providers_of_failed_over_chunks_are_listed_once() {
+    let mut second = result("two", None);
+    second.provider = Some("openai".to_string());
+    let stitched = stitch_transcriptions(vec![
+      (window(0.0, 0.0, Some(60.0)), result("one", None)),
+      (window(59.5, 60.0, Some(120.0)), second),
+      (window(119.5, 120.0, None), result("three", None)),
+    ]);
+
+    assert_eq!(stitched.provider.as_deref(), Some("groq, openai"));
+  }
+
+  #[test]
+  fn unrelated_words_at_the_seam_are_kept() {
+    assert_eq!(drop_repeated_prefix("one two", "three four"), "three four");
+    assert_eq!(drop_repeated_prefix("", "three four"), "three four");
+  }
+}
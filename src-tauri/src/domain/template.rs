@@ -0,0 +1,140 @@
+//! `{{variable}}` placeholders in mode system prompts.
+//!
+//! Prompts are checked with [`validate`] when settings are saved, so a typo
+//! fails in the settings window instead of silently at dictation time.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::error::DiktError;
+
+pub const DATE: &str = "date";
+pub const TIME: &str = "time";
+pub const LANGUAGE: &str = "language";
+pub const CLIPBOARD: &str = "clipboard";
+pub const SELECTED_TEXT: &str = "selected_text";
+pub const ACTIVE_APP: &str = "active_app";
+pub const USER_NAME: &str = "user_name";
+pub const VOCABULARY: &str = "vocabulary";
+
+pub const VARIABLES: [&str; 8] = [
+  DATE,
+  TIME,
+  LANGUAGE,
+  CLIPBOARD,
+  SELECTED_TEXT,
+  ACTIVE_APP,
+  USER_NAME,
+  VOCABULARY,
+];
+
+static PLACEHOLDER: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"\{\{\s*([^{}]*?)\s*\}\}").expect("valid placeholder pattern"));
+
+/// Values for the variables; the ones left `None` expand to an empty string.
+#[derive(Debug, Clone, Default)]
+pub struct PromptVariables {
+  pub date: Option<String>,
+  pub time: Option<String>,
+  pub language: Option<String>,
+  pub clipboard: Option<String>,
+  pub selected_text: Option<String>,
+  pub active_app: Option<String>,
+  pub user_name: Option<String>,
+  pub vocabulary: Option<String>,
+}
+
+impl PromptVariables {
+  fn get(&self, name: &str) -> Option<&str> {
+    let value = match name {
+      DATE => &self.date,
+      TIME => &self.time,
+      LANGUAGE => &self.language,
+      CLIPBOARD => &self.clipboard,
+      SELECTED_TEXT => &self.selected_text,
+      ACTIVE_APP => &self.active_app,
+      USER_NAME => &self.user_name,
+      VOCABULARY => &self.vocabulary,
+      _ => return None,
+    };
+    value.as_deref()
+  }
+}
+
+/// Whether `template` mentions `variable`, so costly values (like copying the
+/// selection) are only looked up when a prompt needs them.
+pub fn uses(template: &str, variable: &str) -> bool {
+  PLACEHOLDER
+    .captures_iter(template)
+    .any(|captures| &captures[1] == variable)
+}
+
+/// Fail on placeholders that name no known variable.
+pub fn validate(template: &str) -> Result<(), DiktError> {
+  let unknown: Vec<String> = PLACEHOLDER
+    .captures_iter(template)
+    .map(|captures| captures[1].to_string())
+    .filter(|name| !VARIABLES.contains(&name.as_str()))
+    .map(|name| format!("{{{{{name}}}}}"))
+    .collect();
+
+  if unknown.is_empty() {
+    return Ok(());
+  }
+  Err(DiktError::Other(format!(
+    "Unknown prompt variable {}. Available: {}",
+    unknown.join(", "),
+    VARIABLES.join(", ")
+  )))
+}
+
+/// Expand the placeholders in `template`. Unknown ones are left as written,
+/// since `validate` already rejected them on save.
+pub fn render(template: &str, variables: &PromptVariables) -> String {
+  PLACEHOLDER
+    .replace_all(template, |captures: &regex::Captures| {
+      let name = &captures[1];
+      if VARIABLES.contains(&name) {
+        variables.get(name).unwrap_or("").to_string()
+      } else {
+        captures[0].to_string()
+      }
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn renders_known_variables_and_blanks_missing_ones() {
+    let variables = PromptVariables {
+      language: Some("german".to_string()),
+      user_name: Some("Sam".to_string()),
+      ..PromptVariables::default()
+    };
+
+    assert_eq!(
+      render("Reply in {{language}} as {{ user_name }}.{{clipboard}}", &variables),
+      "Reply in german as Sam."
+    );
+    assert_eq!(render("No placeholders { here }", &variables), "No placeholders { here }");
+  }
+
+  #[test]
+  fn rejects_unknown_variables() {
+    assert!(validate("Today is {{date}} at {{time}}.").is_ok());
+
+    let error = validate("Sign as {{username}} in {{langauge}}").unwrap_err();
+    assert_eq!(error.code(), "other");
+    assert!(error.to_string().contains("{{username}}, {{langauge}}"));
+    assert!(validate("{{}}").is_err());
+  }
+
+  #[test]
+  fn detects_used_variables() {
+    assert!(uses("Rewrite: {{ selected_text }}", SELECTED_TEXT));
+    assert!(!uses("Rewrite the selection", SELECTED_TEXT));
+  }
+}
@@ -1,15 +1,26 @@
 use std::sync::{
   atomic::{AtomicBool, AtomicUsize, Ordering},
-  Mutex,
+  Arc, Mutex,
 };
 
+use tokio_util::sync::CancellationToken;
+
 use crate::settings::AppSettings;
 use crate::transcribe::TranscriptionResult;
+use crate::transcription_history::TranscriptionHistoryItem;
 
 use super::{
+  error::DiktError,
   manager::DictationSessionManager,
-  ports::{Formatter, Paster, Recorder, SettingsStore, Transcriber},
-  types::{DictationState, Mode, VocabularyEntry},
+  ports::{
+    DesktopContext, Formatter, OfflineQueue, PartialTextSink, Paster, Recorder, SettingsStore,
+    StreamingTranscriber, Transcriber,
+  },
+  types::{
+    AudioChunk, ChunkWindow, DictationState, EncodedAudio, FocusedWindow, FrameSink, Mode,
+    ModeExample, ModeKind, ModeRule, ModeStep, QueuedDictation, StageTimings, StepOutput,
+    VocabularyEntry,
+  },
 };
 
 // ============================================================================
@@ -22,13 +33,54 @@ struct MockRecorder {
   stop_called: AtomicUsize,
   should_fail_start: AtomicBool,
   should_fail_stop: AtomicBool,
-  audio_data: Mutex<Vec<u8>>,
+  chunks: Mutex<Vec<AudioChunk>>,
+  frames: Mutex<Option<FrameSink>>,
+}
+
+fn mock_audio(bytes: &[u8]) -> EncodedAudio {
+  EncodedAudio {
+    bytes: bytes.to_vec(),
+    mime_type: "audio/wav".to_string(),
+    file_extension: "wav".to_string(),
+    duration_secs: None,
+  }
 }
 
 impl MockRecorder {
   fn new() -> Self {
     Self {
-      audio_data: Mutex::new(vec![0u8; 100]), // Fake WAV data
+      chunks: Mutex::new(vec![AudioChunk {
+        audio: mock_audio(&[0u8; 100]), // Fake WAV data
+        window: ChunkWindow {
+          offset_secs: 0.0,
+          keep_from_secs: 0.0,
+          keep_until_secs: None,
+        },
+      }]),
+      ..Default::default()
+    }
+  }
+
+  /// A long recording whose chunk payloads are the given texts, for use with
+  /// `MockTranscriber::echo`.
+  fn with_chunks(texts: &[&str]) -> Self {
+    let chunks = texts
+      .iter()
+      .enumerate()
+      .map(|(index, text)| {
+        let cut = 60.0 * index as f64;
+        AudioChunk {
+          audio: mock_audio(text.as_bytes()),
+          window: ChunkWindow {
+            offset_secs: (cut - 0.5).max(0.0),
+            keep_from_secs: cut,
+            keep_until_secs: (index + 1 < texts.len()).then_some(cut + 60.0),
+          },
+        }
+      })
+      .collect();
+    Self {
+      chunks: Mutex::new(chunks),
       ..Default::default()
     }
   }
@@ -47,20 +99,26 @@ impl MockRecorder {
 }
 
 impl Recorder for MockRecorder {
-  fn start(&self) -> Result<(), String> {
+  fn start(&self, _settings: &AppSettings, frames: Option<FrameSink>) -> Result<(), DiktError> {
     self.start_called.fetch_add(1, Ordering::SeqCst);
     if self.should_fail_start.load(Ordering::SeqCst) {
-      return Err("Mock start failure".to_string());
+      return Err("Mock start failure".into());
+    }
+    if let Some(sink) = frames {
+      let _ = sink.sender.send(vec![0i16; 160]);
+      *self.frames.lock().unwrap() = Some(sink);
     }
     Ok(())
   }
 
-  fn stop(&self) -> Result<Vec<u8>, String> {
+  fn stop(&self) -> Result<Vec<AudioChunk>, DiktError> {
     self.stop_called.fetch_add(1, Ordering::SeqCst);
+    // Closing the live sink ends any streaming transcription.
+    self.frames.lock().unwrap().take();
     if self.should_fail_stop.load(Ordering::SeqCst) {
-      return Err("Mock stop failure".to_string());
+      return Err("Mock stop failure".into());
     }
-    Ok(self.audio_data.lock().unwrap().clone())
+    Ok(self.chunks.lock().unwrap().clone())
   }
 }
 
@@ -90,7 +148,7 @@ impl SettingsStore for MockSettingsStore {
     self.settings.lock().unwrap().clone()
   }
 
-  fn save(&self, settings: &AppSettings) -> Result<(), String> {
+  fn save(&self, settings: &AppSettings) -> Result<(), DiktError> {
     self.save_called.fetch_add(1, Ordering::SeqCst);
     *self.settings.lock().unwrap() = settings.clone();
     Ok(())
@@ -98,29 +156,56 @@ impl SettingsStore for MockSettingsStore {
 }
 
 struct MockTranscriber {
-  transcribe_called: AtomicUsize,
-  result: Mutex<Result<TranscriptionResult, String>>,
+  transcribe_called: Arc<AtomicUsize>,
+  result: Mutex<Result<TranscriptionResult, DiktError>>,
+  echo_audio: AtomicBool,
+  failures_before_success: AtomicUsize,
+  wait_for_cancel: AtomicBool,
 }
 
 impl MockTranscriber {
   fn new(text: &str) -> Self {
     Self {
-      transcribe_called: AtomicUsize::new(0),
+      transcribe_called: Arc::new(AtomicUsize::new(0)),
       result: Mutex::new(Ok(TranscriptionResult {
         text: text.to_string(),
         duration_secs: Some(1.5),
         language: Some("english".to_string()),
         segments: None,
+        provider: None,
+        upload_ms: Some(5),
       })),
+      echo_audio: AtomicBool::new(false),
+      failures_before_success: AtomicUsize::new(0),
+      wait_for_cancel: AtomicBool::new(false),
     }
   }
 
-  fn with_failure(error: &str) -> Self {
+  fn with_failure(error: impl Into<DiktError>) -> Self {
     Self {
-      transcribe_called: AtomicUsize::new(0),
-      result: Mutex::new(Err(error.to_string())),
+      result: Mutex::new(Err(error.into())),
+      ..Self::new("")
     }
   }
+
+  /// Returns the audio payload as the transcript text.
+  fn echo() -> Self {
+    let mock = Self::new("");
+    mock.echo_audio.store(true, Ordering::SeqCst);
+    mock
+  }
+
+  fn failing_first(mut self, failures: usize) -> Self {
+    self.failures_before_success = AtomicUsize::new(failures);
+    self
+  }
+
+  /// Never answers; resolves only once the session is cancelled.
+  fn hanging() -> Self {
+    let mock = Self::new("");
+    mock.wait_for_cancel.store(true, Ordering::SeqCst);
+    mock
+  }
 }
 
 #[async_trait::async_trait]
@@ -128,30 +213,102 @@ impl Transcriber for MockTranscriber {
   async fn transcribe(
     &self,
     _settings: &AppSettings,
-    _audio_wav: Vec<u8>,
+    audio: EncodedAudio,
     _prompt: Option<&str>,
-  ) -> Result<TranscriptionResult, String> {
+    cancel: &CancellationToken,
+  ) -> Result<TranscriptionResult, DiktError> {
     self.transcribe_called.fetch_add(1, Ordering::SeqCst);
+    if self.wait_for_cancel.load(Ordering::SeqCst) {
+      cancel.cancelled().await;
+      return Err(DiktError::Cancelled);
+    }
+    let pending_failures = self.failures_before_success.load(Ordering::SeqCst);
+    if pending_failures > 0 {
+      self.failures_before_success.store(pending_failures - 1, Ordering::SeqCst);
+      return Err(DiktError::from_status(503, "Mock transient failure".to_string()));
+    }
+    if self.echo_audio.load(Ordering::SeqCst) {
+      return Ok(TranscriptionResult {
+        text: String::from_utf8_lossy(&audio.bytes).to_string(),
+        duration_secs: None,
+        language: Some("english".to_string()),
+        segments: None,
+        provider: None,
+        upload_ms: None,
+      });
+    }
     self.result.lock().unwrap().clone()
   }
 }
 
+/// Reports one partial per frame and the final text once the recorder stops.
+struct MockStreamingTranscriber {
+  final_result: Result<String, DiktError>,
+  frames_received: Arc<AtomicUsize>,
+}
+
+impl MockStreamingTranscriber {
+  fn new(text: &str) -> Self {
+    Self {
+      final_result: Ok(text.to_string()),
+      frames_received: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  fn with_failure(error: impl Into<DiktError>) -> Self {
+    Self {
+      final_result: Err(error.into()),
+      frames_received: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl StreamingTranscriber for MockStreamingTranscriber {
+  fn sample_rate(&self, _settings: &AppSettings) -> u32 {
+    16_000
+  }
+
+  async fn transcribe_stream(
+    &self,
+    _settings: &AppSettings,
+    mut frames: tokio::sync::mpsc::UnboundedReceiver<Vec<i16>>,
+    _prompt: Option<&str>,
+    on_partial: PartialTextSink,
+  ) -> Result<TranscriptionResult, DiktError> {
+    while frames.recv().await.is_some() {
+      let received = self.frames_received.fetch_add(1, Ordering::SeqCst) + 1;
+      on_partial(format!("partial {received}"));
+    }
+    self.final_result.clone().map(|text| TranscriptionResult {
+      text,
+      duration_secs: None,
+      language: None,
+      segments: None,
+      provider: None,
+      upload_ms: None,
+    })
+  }
+}
+
 struct MockPaster {
-  paste_called: AtomicUsize,
+  paste_called: Arc<AtomicUsize>,
   copy_called: AtomicUsize,
   last_text: Mutex<String>,
   last_copied_text: Mutex<String>,
   should_fail: AtomicBool,
+  failures_before_success: AtomicUsize,
 }
 
 impl MockPaster {
   fn new() -> Self {
     Self {
-      paste_called: AtomicUsize::new(0),
+      paste_called: Arc::new(AtomicUsize::new(0)),
       copy_called: AtomicUsize::new(0),
       last_text: Mutex::new(String::new()),
       last_copied_text: Mutex::new(String::new()),
       should_fail: AtomicBool::new(false),
+      failures_before_success: AtomicUsize::new(0),
     }
   }
 
@@ -160,19 +317,29 @@ impl MockPaster {
     mock.should_fail.store(true, Ordering::SeqCst);
     mock
   }
+
+  fn failing_first(self, failures: usize) -> Self {
+    self.failures_before_success.store(failures, Ordering::SeqCst);
+    self
+  }
 }
 
 impl Paster for MockPaster {
-  fn paste(&self, text: &str) -> Result<(), String> {
+  fn paste(&self, text: &str) -> Result<(), DiktError> {
     self.paste_called.fetch_add(1, Ordering::SeqCst);
     *self.last_text.lock().unwrap() = text.to_string();
     if self.should_fail.load(Ordering::SeqCst) {
-      return Err("Mock paste failure".to_string());
+      return Err("Mock paste failure".into());
+    }
+    let pending_failures = self.failures_before_success.load(Ordering::SeqCst);
+    if pending_failures > 0 {
+      self.failures_before_success.store(pending_failures - 1, Ordering::SeqCst);
+      return Err("Mock paste failure".into());
     }
     Ok(())
   }
 
-  fn copy(&self, text: &str) -> Result<(), String> {
+  fn copy(&self, text: &str) -> Result<(), DiktError> {
     self.copy_called.fetch_add(1, Ordering::SeqCst);
     *self.last_copied_text.lock().unwrap() = text.to_string();
     Ok(())
@@ -181,28 +348,65 @@ impl Paster for MockPaster {
 
 struct MockFormatter {
   should_fail: AtomicBool,
+  // Returned instead of formatting, when set.
+  error: Option<DiktError>,
   prefix: Mutex<String>,
+  // Answer returned instead of the prefixed input.
+  reply: Option<String>,
+  format_called: Arc<AtomicUsize>,
+  system_prompt: Arc<Mutex<Option<String>>>,
+  // Number of examples sent with each call.
+  examples_sent: Arc<Mutex<Vec<usize>>>,
+  wait_for_cancel: AtomicBool,
 }
 
 impl MockFormatter {
   fn new() -> Self {
     Self {
       should_fail: AtomicBool::new(false),
+      error: None,
       prefix: Mutex::new(String::new()),
+      reply: None,
+      format_called: Arc::new(AtomicUsize::new(0)),
+      system_prompt: Arc::new(Mutex::new(None)),
+      examples_sent: Arc::new(Mutex::new(Vec::new())),
+      wait_for_cancel: AtomicBool::new(false),
     }
   }
 
   fn with_prefix(prefix: &str) -> Self {
     Self {
-      should_fail: AtomicBool::new(false),
       prefix: Mutex::new(prefix.to_string()),
+      ..Self::new()
     }
   }
 
   fn with_failure() -> Self {
     Self {
       should_fail: AtomicBool::new(true),
-      prefix: Mutex::new(String::new()),
+      ..Self::new()
+    }
+  }
+
+  fn failing_with(error: DiktError) -> Self {
+    Self {
+      error: Some(error),
+      ..Self::new()
+    }
+  }
+
+  fn replying(reply: &str) -> Self {
+    Self {
+      reply: Some(reply.to_string()),
+      ..Self::new()
+    }
+  }
+
+  /// Streams its answer but never finishes; resolves only once the session is cancelled.
+  fn hanging() -> Self {
+    Self {
+      wait_for_cancel: AtomicBool::new(true),
+      ..Self::new()
     }
   }
 }
@@ -211,17 +415,149 @@ impl MockFormatter {
 impl Formatter for MockFormatter {
   async fn format(
     &self,
-    _base_url: &str,
-    _api_key: &str,
-    _model: &str,
-    _system_prompt: &str,
+    _settings: &AppSettings,
+    mode: &Mode,
     text: &str,
-  ) -> Result<String, String> {
+    cancel: &CancellationToken,
+    on_partial: PartialTextSink,
+  ) -> Result<String, DiktError> {
+    self.format_called.fetch_add(1, Ordering::SeqCst);
+    *self.system_prompt.lock().unwrap() = Some(mode.system_prompt.clone());
+    self.examples_sent.lock().unwrap().push(mode.examples.len());
     if self.should_fail.load(Ordering::SeqCst) {
-      return Err("Mock format failure".to_string());
+      return Err("Mock format failure".into());
+    }
+    if let Some(error) = &self.error {
+      return Err(error.clone());
     }
 
-    Ok(format!("{}{}", self.prefix.lock().unwrap().as_str(), text))
+    let formatted = self
+      .reply
+      .clone()
+      .unwrap_or_else(|| format!("{}{}", self.prefix.lock().unwrap().as_str(), text));
+    on_partial(formatted.clone());
+    if self.wait_for_cancel.load(Ordering::SeqCst) {
+      cancel.cancelled().await;
+      return Err(DiktError::Cancelled);
+    }
+    Ok(formatted)
+  }
+}
+
+struct MockDesktopContext {
+  selection: Option<String>,
+  clipboard: Option<String>,
+  window: Arc<Mutex<FocusedWindow>>,
+  selection_reads: Arc<AtomicUsize>,
+}
+
+impl MockDesktopContext {
+  fn new() -> Self {
+    Self {
+      selection: Some("selected text".to_string()),
+      clipboard: Some("clipboard text".to_string()),
+      window: Arc::new(Mutex::new(FocusedWindow {
+        app: Some("Editor".to_string()),
+        title: Some("notes.txt - Editor".to_string()),
+      })),
+      selection_reads: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  fn in_app(app: &str) -> Self {
+    let mock = Self::new();
+    *mock.window.lock().unwrap() = FocusedWindow {
+      app: Some(app.to_string()),
+      title: None,
+    };
+    mock
+  }
+
+  fn without_selection() -> Self {
+    Self {
+      selection: None,
+      ..Self::new()
+    }
+  }
+}
+
+impl DesktopContext for MockDesktopContext {
+  fn clipboard_text(&self) -> Option<String> {
+    self.clipboard.clone()
+  }
+
+  fn selected_text(&self) -> Option<String> {
+    self.selection_reads.fetch_add(1, Ordering::SeqCst);
+    self.selection.clone()
+  }
+
+  fn focused_window(&self) -> Option<FocusedWindow> {
+    Some(self.window.lock().unwrap().clone())
+  }
+
+  fn user_name(&self) -> Option<String> {
+    Some("sam".to_string())
+  }
+}
+
+type QueueEntries = Arc<Mutex<Vec<(QueuedDictation, Vec<AudioChunk>)>>>;
+
+#[derive(Default)]
+struct MockOfflineQueue {
+  entries: QueueEntries,
+}
+
+impl MockOfflineQueue {
+  fn with_entry(active_mode_id: Option<&str>) -> Self {
+    let queue = Self::default();
+    queue.entries.lock().unwrap().push((
+      QueuedDictation {
+        id: "queued-1".to_string(),
+        created_at_ms: 1,
+        attempts: 0,
+        active_mode_id: active_mode_id.map(str::to_string),
+        vocabulary: vec![],
+      },
+      MockRecorder::new().chunks.into_inner().unwrap(),
+    ));
+    queue
+  }
+}
+
+impl OfflineQueue for MockOfflineQueue {
+  fn enqueue(&self, chunks: &[AudioChunk], settings: &AppSettings) -> Result<(), DiktError> {
+    let mut entries = self.entries.lock().unwrap();
+    let id = format!("queued-{}", entries.len() + 1);
+    entries.push((
+      QueuedDictation {
+        id,
+        created_at_ms: 1,
+        attempts: 0,
+        active_mode_id: settings.active_mode_id.clone(),
+        vocabulary: settings.vocabulary.clone(),
+      },
+      chunks.to_vec(),
+    ));
+    Ok(())
+  }
+
+  fn next(&self) -> Result<Option<(QueuedDictation, Vec<AudioChunk>)>, DiktError> {
+    Ok(self.entries.lock().unwrap().first().cloned())
+  }
+
+  fn remove(&self, id: &str) -> Result<(), DiktError> {
+    self.entries.lock().unwrap().retain(|(queued, _)| queued.id != id);
+    Ok(())
+  }
+
+  fn record_attempt(&self, id: &str) -> Result<u32, DiktError> {
+    let mut entries = self.entries.lock().unwrap();
+    let (queued, _) = entries
+      .iter_mut()
+      .find(|(queued, _)| queued.id == id)
+      .ok_or("Not queued")?;
+    queued.attempts += 1;
+    Ok(queued.attempts)
   }
 }
 
@@ -304,6 +640,141 @@ fn test_save_settings_updates_store_and_memory() {
   assert_eq!(loaded.model, "new-model");
 }
 
+#[test]
+fn test_save_settings_rejects_provider_without_base_url() {
+  let manager = create_default_manager();
+  let mut mode = chat_mode("Email", "Fix it.");
+  mode.provider = Some("custom".to_string());
+
+  let error = manager.save_settings(settings_with_mode(mode.clone())).unwrap_err();
+  assert!(error.to_string().contains("Mode \"Email\""));
+  assert!(error.to_string().contains("base URL"));
+
+  mode.provider = Some("openai".to_string());
+  manager.save_settings(settings_with_mode(mode.clone())).unwrap();
+  mode.provider = Some("custom".to_string());
+  mode.base_url = Some("http://localhost:8080/v1".to_string());
+  manager.save_settings(settings_with_mode(mode)).unwrap();
+}
+
+#[test]
+fn test_save_settings_rejects_unknown_prompt_variables() {
+  let manager = create_default_manager();
+
+  let new_settings = AppSettings {
+    model: "new-model".to_string(),
+    modes: vec![Mode {
+      id: "mode-1".to_string(),
+      name: "Email".to_string(),
+      system_prompt: "Sign as {{user_name}} on {{today}}".to_string(),
+      ..Default::default()
+    }],
+    ..Default::default()
+  };
+
+  let error = manager.save_settings(new_settings).unwrap_err();
+  assert!(error.to_string().contains("Mode \"Email\""));
+  assert!(error.to_string().contains("{{today}}"));
+  assert_ne!(manager.get_settings().unwrap().model, "new-model");
+}
+
+#[test]
+fn test_add_mode_example_appends_to_mode_once() {
+  let settings = AppSettings {
+    modes: vec![Mode {
+      id: "mode-1".to_string(),
+      name: "Email".to_string(),
+      ..Default::default()
+    }],
+    ..Default::default()
+  };
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello"),
+    MockPaster::new(),
+  );
+  let example = ModeExample {
+    input: "hi sam".to_string(),
+    output: "Hi Sam,".to_string(),
+  };
+
+  let mode = manager.add_mode_example("mode-1", example.clone()).unwrap();
+  manager.add_mode_example("mode-1", example.clone()).unwrap();
+
+  assert_eq!(mode.examples, vec![example.clone()]);
+  assert_eq!(manager.get_settings().unwrap().modes[0].examples, vec![example.clone()]);
+  assert!(manager.add_mode_example("deleted-mode", example).is_err());
+}
+
+#[test]
+fn test_history_example_uses_the_last_chat_step_of_a_pipeline() {
+  let mut settings = pipeline_settings(vec![
+    ModeStep::Llm {
+      system_prompt: "Clean up".to_string(),
+      model: None,
+    },
+    ModeStep::Llm {
+      system_prompt: "Format as email".to_string(),
+      model: None,
+    },
+    ModeStep::Vocabulary,
+  ]);
+  settings.modes[0].name = "Renamed since".to_string();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello"),
+    MockPaster::new(),
+  );
+  let step = |step: &str, text: &str| StepOutput {
+    step: step.to_string(),
+    text: text.to_string(),
+    error: None,
+  };
+  let item = TranscriptionHistoryItem {
+    text: "Hi Sam, the demo moved to Dikt.".to_string(),
+    mode_id: Some("mode-1".to_string()),
+    mode_name: Some("Pipeline".to_string()),
+    original_text: Some("um tell sam the demo moved".to_string()),
+    step_outputs: Some(vec![
+      step("llm", "Tell Sam the demo moved."),
+      step("llm", "Hi Sam, the demo moved."),
+      step("vocabulary", "Hi Sam, the demo moved to Dikt."),
+    ]),
+    ..Default::default()
+  };
+
+  let mode = manager.add_history_example(&item).unwrap();
+
+  assert_eq!(
+    mode.examples,
+    vec![ModeExample {
+      input: "Tell Sam the demo moved.".to_string(),
+      output: "Hi Sam, the demo moved.".to_string(),
+    }]
+  );
+}
+
+#[test]
+fn test_transform_history_cannot_become_an_example() {
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(transform_settings()),
+    MockTranscriber::new("Hello"),
+    MockPaster::new(),
+  );
+  let item = TranscriptionHistoryItem {
+    text: "Bonjour".to_string(),
+    mode_id: Some("mode-1".to_string()),
+    original_text: Some("translate to french".to_string()),
+    ..Default::default()
+  };
+
+  assert!(manager.add_history_example(&item).is_err());
+  assert!(manager.get_settings().unwrap().modes[0].examples.is_empty());
+}
+
 #[test]
 fn test_save_vocabulary_updates_store_and_memory() {
   let manager = create_default_manager();
@@ -351,7 +822,7 @@ fn test_start_recording_fails_when_already_recording() {
   let result = manager.start_recording(|_| {});
 
   assert!(result.is_err());
-  assert_eq!(result.unwrap_err(), "Busy");
+  assert_eq!(result.unwrap_err(), DiktError::Busy);
 }
 
 #[test]
@@ -405,6 +876,44 @@ async fn test_stop_and_process_success() {
   assert_eq!(updates[2].text, Some("Hello world".to_string()));
 }
 
+#[tokio::test]
+async fn test_done_update_carries_stage_timings() {
+  let manager = create_default_manager();
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  manager
+    .stop_and_process(|update| updates.push(update))
+    .await
+    .unwrap();
+
+  let timings = updates.last().unwrap().timings_ms.unwrap();
+  assert!(timings.stop.is_some());
+  // The mock reports an upload, which is split out of the transcribe time.
+  assert!(timings.upload.is_some());
+  assert!(timings.transcribe.is_some());
+  assert!(timings.paste.is_some());
+  // No mode is active, so nothing was formatted.
+  assert_eq!(timings.format, None);
+}
+
+#[test]
+fn test_split_upload_never_exceeds_transcribe_time() {
+  let mut timings = StageTimings {
+    transcribe: Some(300),
+    ..StageTimings::default()
+  };
+  timings.split_upload(Some(120));
+  assert_eq!((timings.upload, timings.transcribe), (Some(120), Some(180)));
+
+  let mut timings = StageTimings {
+    transcribe: Some(50),
+    ..StageTimings::default()
+  };
+  timings.split_upload(Some(80));
+  assert_eq!((timings.upload, timings.transcribe), (Some(50), Some(0)));
+}
+
 #[tokio::test]
 async fn test_stop_and_process_fails_when_not_recording() {
   let manager = create_default_manager();
@@ -412,7 +921,7 @@ async fn test_stop_and_process_fails_when_not_recording() {
   let result = manager.stop_and_process(|_| {}).await;
 
   assert!(result.is_err());
-  assert_eq!(result.unwrap_err(), "Not recording");
+  assert_eq!(result.unwrap_err().to_string(), "Not recording");
 }
 
 #[tokio::test]
@@ -430,7 +939,7 @@ async fn test_stop_and_process_handles_transcription_failure() {
   let result = manager.stop_and_process(|update| updates.push(update)).await;
 
   assert!(result.is_err());
-  assert!(result.unwrap_err().contains("Transcription failed"));
+  assert!(result.unwrap_err().to_string().contains("Transcription failed"));
 
   // Should have: Transcribing -> Error
   let error_update = updates.iter().find(|u| u.state == DictationState::Error);
@@ -473,7 +982,7 @@ async fn test_stop_and_process_handles_recorder_stop_failure() {
   let result = manager.stop_and_process(|update| updates.push(update)).await;
 
   assert!(result.is_err());
-  assert!(result.unwrap_err().contains("Mock stop failure"));
+  assert!(result.unwrap_err().to_string().contains("Mock stop failure"));
 }
 
 #[tokio::test]
@@ -485,6 +994,7 @@ async fn test_stop_and_process_emits_formatting_when_mode_active() {
     name: "Formatter".to_string(),
     system_prompt: "Format this".to_string(),
     model: "chat-model".to_string(),
+    ..Default::default()
   }];
 
   let manager = create_manager_with_formatter(
@@ -521,6 +1031,7 @@ async fn test_stop_and_process_falls_back_when_formatting_fails() {
     name: "Formatter".to_string(),
     system_prompt: "Format this".to_string(),
     model: "chat-model".to_string(),
+    ..Default::default()
   }];
 
   let manager = create_manager_with_formatter(
@@ -542,37 +1053,1201 @@ async fn test_stop_and_process_falls_back_when_formatting_fails() {
   assert_eq!(updates.last().and_then(|update| update.text.clone()), Some("Hello world".to_string()));
 }
 
-// ============================================================================
-// Tests: State Transitions
-// ============================================================================
+/// Settings whose only mode, `mode`, is the active one.
+fn settings_with_mode(mode: Mode) -> AppSettings {
+  AppSettings {
+    active_mode_id: Some(mode.id.clone()),
+    modes: vec![mode],
+    ..Default::default()
+  }
+}
+
+/// A single-step chat mode with id `mode-1`.
+fn chat_mode(name: &str, system_prompt: &str) -> Mode {
+  Mode {
+    id: "mode-1".to_string(),
+    name: name.to_string(),
+    system_prompt: system_prompt.to_string(),
+    model: "chat-model".to_string(),
+    ..Default::default()
+  }
+}
+
+fn reply_check_settings(reply_check: bool) -> AppSettings {
+  settings_with_mode(Mode {
+    reply_check,
+    ..chat_mode("Cleanup", "Clean up the transcript")
+  })
+}
 
 #[tokio::test]
-async fn test_state_returns_to_idle_after_successful_flow() {
-  let manager = create_default_manager();
+async fn test_formatter_reply_is_rejected_and_transcript_pasted() {
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(reply_check_settings(true)),
+    MockTranscriber::new("what is the capital of france"),
+    MockPaster::new(),
+    MockFormatter::replying("Sure! The capital of France is Paris."),
+  );
 
   manager.start_recording(|_| {}).unwrap();
-  manager.stop_and_process(|_| {}).await.unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
 
-  // Should be able to start recording again
-  let result = manager.start_recording(|_| {});
-  assert!(result.is_ok());
+  assert_eq!(result, "what is the capital of france");
 }
 
 #[tokio::test]
-async fn test_state_returns_to_idle_after_error() {
+async fn test_reply_check_is_opt_in() {
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(reply_check_settings(false)),
+    MockTranscriber::new("tell sam the meeting moved"),
+    MockPaster::new(),
+    MockFormatter::replying(
+      "Hi Sam,\n\nQuick heads-up: our meeting has moved. I will send the new time shortly.\n\nBest regards",
+    ),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert!(result.starts_with("Hi Sam,"));
+}
+
+#[tokio::test]
+async fn test_pipeline_without_chat_step_is_not_reply_checked() {
+  let mut settings = pipeline_settings(vec![ModeStep::Regex {
+    pattern: r"\b(um|uh),?\s*".to_string(),
+    replacement: String::new(),
+  }]);
+  settings.modes[0].reply_check = true;
   let manager = create_manager(
     MockRecorder::new(),
-    MockSettingsStore::new(),
-    MockTranscriber::with_failure("Error"),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("um uh um, uh, um, uh, um so ship it"),
     MockPaster::new(),
   );
 
   manager.start_recording(|_| {}).unwrap();
-  let _ = manager.stop_and_process(|_| {}).await;
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
 
-  // Should be able to start recording again after error
-  let result = manager.start_recording(|_| {});
-  assert!(result.is_ok());
+  assert_eq!(result, "so ship it");
+}
+
+#[tokio::test]
+async fn test_reply_check_compares_chat_step_with_its_own_input() {
+  let mut settings = pipeline_settings(vec![
+    ModeStep::Regex {
+      pattern: r"^.*$".to_string(),
+      replacement: "the release notes are ready for review".to_string(),
+    },
+    ModeStep::Llm {
+      system_prompt: "Clean up".to_string(),
+      model: None,
+    },
+  ]);
+  settings.modes[0].reply_check = true;
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("something else entirely was said here"),
+    MockPaster::new(),
+    MockFormatter::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(result, "the release notes are ready for review");
+}
+
+#[tokio::test]
+async fn test_json_schema_output_is_not_reply_checked() {
+  let mut settings = reply_check_settings(true);
+  settings.modes[0].params.json_schema = Some(serde_json::json!({ "type": "object" }));
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("remind me to call the dentist on friday"),
+    MockPaster::new(),
+    MockFormatter::replying(r#"{"task":"Call dentist","due":"2026-10-23"}"#),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(result, r#"{"task":"Call dentist","due":"2026-10-23"}"#);
+}
+
+#[tokio::test]
+async fn test_mode_prompt_variables_are_expanded_before_formatting() {
+  let settings = AppSettings {
+    vocabulary: vec![VocabularyEntry {
+      id: "entry-1".to_string(),
+      word: "Dikt".to_string(),
+      replacements: vec![],
+      enabled: true,
+    }],
+    ..settings_with_mode(chat_mode(
+      "Reply",
+      "Answer in {{language}} for {{user_name}} in {{active_app}} ({{vocabulary}}): {{selected_text}}",
+    ))
+  };
+
+  let formatter = MockFormatter::new();
+  let system_prompt = formatter.system_prompt.clone();
+  let desktop = MockDesktopContext::new();
+  let selection_reads = desktop.selection_reads.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  )
+  .with_desktop_context(Box::new(desktop));
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(
+    system_prompt.lock().unwrap().as_deref(),
+    Some("Answer in english for sam in Editor (Dikt): selected text")
+  );
+  assert_eq!(selection_reads.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_desktop_variables_are_read_when_recording_starts() {
+  let settings = settings_with_mode(chat_mode("Reply", "Reply in {{active_app}}"));
+
+  let formatter = MockFormatter::new();
+  let system_prompt = formatter.system_prompt.clone();
+  let desktop = MockDesktopContext::in_app("Mail");
+  let window = desktop.window.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  )
+  .with_desktop_context(Box::new(desktop));
+
+  manager.start_recording(|_| {}).unwrap();
+  window.lock().unwrap().app = Some("Terminal".to_string());
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(system_prompt.lock().unwrap().as_deref(), Some("Reply in Mail"));
+}
+
+#[tokio::test]
+async fn test_desktop_is_not_queried_for_unused_prompt_variables() {
+  let settings = settings_with_mode(chat_mode("Formatter", "Format this"));
+
+  let desktop = MockDesktopContext::new();
+  let selection_reads = desktop.selection_reads.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    MockFormatter::new(),
+  )
+  .with_desktop_context(Box::new(desktop));
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(selection_reads.load(Ordering::SeqCst), 0);
+}
+
+fn transform_settings() -> AppSettings {
+  settings_with_mode(Mode {
+    kind: ModeKind::Transform,
+    ..chat_mode("Transform", "Apply the instruction")
+  })
+}
+
+#[tokio::test]
+async fn test_transform_mode_replaces_selection_with_result() {
+  let paster = MockPaster::new();
+  let paste_called = paster.paste_called.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(transform_settings()),
+    MockTranscriber::new("make this formal"),
+    paster,
+    MockFormatter::with_prefix("Formatted: "),
+  )
+  .with_desktop_context(Box::new(MockDesktopContext::new()));
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(
+    result,
+    "Formatted: Instruction: make this formal\n\nText:\nselected text"
+  );
+  assert_eq!(paste_called.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_transform_mode_uses_clipboard_without_selection() {
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(transform_settings()),
+    MockTranscriber::new("make this formal"),
+    MockPaster::new(),
+    MockFormatter::with_prefix("Formatted: "),
+  )
+  .with_desktop_context(Box::new(MockDesktopContext::without_selection()));
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert!(result.ends_with("Text:\nclipboard text"));
+}
+
+#[tokio::test]
+async fn test_transform_mode_does_not_paste_instruction_when_formatting_fails() {
+  let paster = MockPaster::new();
+  let paste_called = paster.paste_called.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(transform_settings()),
+    MockTranscriber::new("make this formal"),
+    paster,
+    MockFormatter::with_failure(),
+  )
+  .with_desktop_context(Box::new(MockDesktopContext::new()));
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert!(result.is_err());
+  assert_eq!(paste_called.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_transform_mode_fails_without_text_to_transform() {
+  let paster = MockPaster::new();
+  let paste_called = paster.paste_called.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(transform_settings()),
+    MockTranscriber::new("make this formal"),
+    paster,
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let error = manager.stop_and_process(|_| {}).await.unwrap_err();
+
+  assert!(error.to_string().contains("Select or copy the text to transform"));
+  assert_eq!(paste_called.load(Ordering::SeqCst), 0);
+}
+
+fn pipeline_settings(steps: Vec<ModeStep>) -> AppSettings {
+  AppSettings {
+    vocabulary: vec![VocabularyEntry {
+      id: "entry-1".to_string(),
+      word: "Dikt".to_string(),
+      replacements: vec!["dicked".to_string()],
+      enabled: true,
+    }],
+    ..settings_with_mode(Mode {
+      steps,
+      ..chat_mode("Pipeline", "Unused when the mode has steps")
+    })
+  }
+}
+
+#[tokio::test]
+async fn test_mode_steps_run_in_order_on_previous_output() {
+  let formatter = MockFormatter::with_prefix("> ");
+  let format_called = formatter.format_called.clone();
+  let system_prompt = formatter.system_prompt.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(pipeline_settings(vec![
+      ModeStep::Regex {
+        pattern: r"\bum,?\s*".to_string(),
+        replacement: String::new(),
+      },
+      ModeStep::Llm {
+        system_prompt: "Clean up".to_string(),
+        model: None,
+      },
+      ModeStep::Vocabulary,
+      ModeStep::Llm {
+        system_prompt: "Translate to {{language}}".to_string(),
+        model: Some("translator".to_string()),
+      },
+    ])),
+    MockTranscriber::new("um, open dicked"),
+    MockPaster::new(),
+    formatter,
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  // Vocabulary replacements ran once on the transcript and again as a step.
+  assert_eq!(result, "> > open Dikt");
+  assert_eq!(format_called.load(Ordering::SeqCst), 2);
+  assert_eq!(system_prompt.lock().unwrap().as_deref(), Some("Translate to english"));
+}
+
+#[tokio::test]
+async fn test_mode_examples_go_with_the_last_chat_step() {
+  let mut settings = pipeline_settings(vec![
+    ModeStep::Llm {
+      system_prompt: "Clean up".to_string(),
+      model: None,
+    },
+    ModeStep::Llm {
+      system_prompt: "Format as email".to_string(),
+      model: None,
+    },
+    ModeStep::Vocabulary,
+  ]);
+  settings.modes[0].examples = vec![ModeExample {
+    input: "hi sam".to_string(),
+    output: "Hi Sam,".to_string(),
+  }];
+  let formatter = MockFormatter::new();
+  let examples_sent = formatter.examples_sent.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(*examples_sent.lock().unwrap(), vec![0, 1]);
+}
+
+#[tokio::test]
+async fn test_failing_mode_step_falls_back_to_transcript() {
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(pipeline_settings(vec![
+      ModeStep::Vocabulary,
+      ModeStep::Llm {
+        system_prompt: "Clean up".to_string(),
+        model: None,
+      },
+    ])),
+    MockTranscriber::new("open dicked"),
+    MockPaster::new(),
+    MockFormatter::with_failure(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(result, "open Dikt");
+}
+
+#[test]
+fn test_save_settings_rejects_invalid_regex_step() {
+  let manager = create_default_manager();
+
+  let error = manager
+    .save_settings(pipeline_settings(vec![ModeStep::Regex {
+      pattern: "(unclosed".to_string(),
+      replacement: String::new(),
+    }]))
+    .unwrap_err();
+
+  assert!(error.to_string().contains("Mode \"Pipeline\": Invalid regex step"));
+}
+
+fn mode_rule_settings() -> AppSettings {
+  let mode = |id: &str| Mode {
+    id: id.to_string(),
+    ..chat_mode(id, &format!("{id} prompt"))
+  };
+  let mut settings = settings_with_mode(mode("clean-draft"));
+  settings.modes.extend([mode("developer"), mode("email")]);
+  AppSettings {
+    mode_rules: vec![
+      ModeRule {
+        app: "terminal".to_string(),
+        mode_id: Some("developer".to_string()),
+        ..Default::default()
+      },
+      ModeRule {
+        title: "inbox".to_string(),
+        mode_id: Some("email".to_string()),
+        ..Default::default()
+      },
+      ModeRule {
+        app: "Slack".to_string(),
+        mode_id: None,
+        ..Default::default()
+      },
+    ],
+    ..settings
+  }
+}
+
+/// Formats one dictation started in `desktop`'s window and returns the system
+/// prompt the formatter saw, if it was called.
+async fn format_with_mode_rules(desktop: MockDesktopContext) -> Option<String> {
+  let formatter = MockFormatter::new();
+  let system_prompt = formatter.system_prompt.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(mode_rule_settings()),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  )
+  .with_desktop_context(Box::new(desktop));
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.stop_and_process(|_| {}).await.unwrap();
+  let prompt = system_prompt.lock().unwrap().clone();
+  prompt
+}
+
+#[tokio::test]
+async fn test_mode_rules_pick_mode_from_focused_app_or_title() {
+  assert_eq!(
+    format_with_mode_rules(MockDesktopContext::in_app("org.gnome.Terminal")).await,
+    Some("developer prompt".to_string())
+  );
+
+  let desktop = MockDesktopContext::in_app("Thunderbird");
+  desktop.window.lock().unwrap().title = Some("Inbox - Thunderbird".to_string());
+  assert_eq!(format_with_mode_rules(desktop).await, Some("email prompt".to_string()));
+}
+
+#[tokio::test]
+async fn test_mode_rule_without_mode_pastes_raw_text() {
+  assert_eq!(format_with_mode_rules(MockDesktopContext::in_app("Slack")).await, None);
+}
+
+#[tokio::test]
+async fn test_unknown_app_falls_back_to_active_mode() {
+  assert_eq!(
+    format_with_mode_rules(MockDesktopContext::in_app("Firefox")).await,
+    Some("clean-draft prompt".to_string())
+  );
+}
+
+#[tokio::test]
+async fn test_mode_rules_use_window_focused_when_recording_started() {
+  let formatter = MockFormatter::new();
+  let system_prompt = formatter.system_prompt.clone();
+  let desktop = MockDesktopContext::in_app("org.gnome.Terminal");
+  let window = desktop.window.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(mode_rule_settings()),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  )
+  .with_desktop_context(Box::new(desktop));
+
+  manager.start_recording(|_| {}).unwrap();
+  window.lock().unwrap().app = Some("Firefox".to_string());
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(system_prompt.lock().unwrap().as_deref(), Some("developer prompt"));
+}
+
+// ============================================================================
+// Tests: Chunked Recordings
+// ============================================================================
+
+#[tokio::test]
+async fn test_stop_and_process_stitches_chunks_in_order() {
+  let transcriber = MockTranscriber::echo();
+  let calls = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::with_chunks(&["first part of the", "of the second part", "and the end"]),
+    MockSettingsStore::new(),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(
+    result.unwrap(),
+    "first part of the second part and the end"
+  );
+  assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+/// Settings whose retry backoff is short enough for tests.
+fn fast_retry_settings() -> AppSettings {
+  let mut settings = AppSettings::default();
+  settings.retry_policy.base_delay_ms = 1;
+  settings
+}
+
+#[tokio::test]
+async fn test_failed_chunk_is_retried_on_its_own() {
+  let transcriber = MockTranscriber::echo().failing_first(1);
+  let calls = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::with_chunks(&["one", "two"]),
+    MockSettingsStore::with_settings(fast_retry_settings()),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(result.unwrap(), "one two");
+  // Two chunks plus a single retry for the one that failed.
+  assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_chunk_failing_every_attempt_fails_the_session() {
+  let manager = create_manager(
+    MockRecorder::with_chunks(&["one", "two"]),
+    MockSettingsStore::with_settings(fast_retry_settings()),
+    MockTranscriber::with_failure(DiktError::from_status(503, "Upload rejected".to_string())),
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  let err = result.unwrap_err().to_string();
+  assert!(err.contains("Chunk 1 of 2 failed"));
+  assert!(err.contains("Upload rejected"));
+}
+
+#[tokio::test]
+async fn test_chunk_retry_waits_for_the_backoff() {
+  let mut settings = fast_retry_settings();
+  settings.retry_policy.base_delay_ms = 10;
+  settings.retry_policy.jitter = 0.0;
+  let manager = create_manager(
+    MockRecorder::with_chunks(&["one", "two"]),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::echo().failing_first(1),
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let started = std::time::Instant::now();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(result.unwrap(), "one two");
+  // Continues the request-level backoff: the 4th delay of a 10ms base is 80ms.
+  assert!(started.elapsed() >= std::time::Duration::from_millis(80));
+}
+
+#[tokio::test]
+async fn test_rejected_chunk_is_not_retried() {
+  let transcriber = MockTranscriber::with_failure(DiktError::Auth("bad key".to_string()));
+  let calls = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::with_chunks(&["one", "two"]),
+    MockSettingsStore::new(),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(result.unwrap_err().code(), "auth");
+  // At most one request per chunk, since both may already be in flight.
+  assert!(calls.load(Ordering::SeqCst) <= 2);
+}
+
+#[tokio::test]
+async fn test_single_chunk_failure_is_not_retried() {
+  let transcriber = MockTranscriber::with_failure("Transcription failed");
+  let calls = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let _ = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+// ============================================================================
+// Tests: Streaming Transcription
+// ============================================================================
+
+fn streaming_settings() -> AppSettings {
+  AppSettings {
+    streaming_enabled: true,
+    ..Default::default()
+  }
+}
+
+#[tokio::test]
+async fn test_streaming_partials_are_reported_while_recording() {
+  let transcriber = MockTranscriber::new("Uploaded");
+  let uploads = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(streaming_settings()),
+    transcriber,
+    MockPaster::new(),
+  )
+  .with_streaming_transcriber(Arc::new(MockStreamingTranscriber::new("Streamed text")));
+
+  manager.start_recording(|_| {}).unwrap();
+
+  let mut partial = None;
+  for _ in 0..50 {
+    partial = manager.take_partial_update();
+    if partial.is_some() {
+      break;
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+  }
+  let partial = partial.expect("expected a partial update while recording");
+  assert_eq!(partial.state, DictationState::Recording);
+  assert_eq!(partial.partial_text, Some("partial 1".to_string()));
+
+  let mut updates = vec![];
+  let result = manager.stop_and_process(|update| updates.push(update)).await;
+
+  assert_eq!(result.unwrap(), "Streamed text");
+  assert_eq!(uploads.load(Ordering::SeqCst), 0);
+  assert_eq!(updates.last().unwrap().text, Some("Streamed text".to_string()));
+  assert!(manager.take_partial_update().is_none());
+}
+
+#[tokio::test]
+async fn test_streaming_failure_falls_back_to_upload() {
+  let transcriber = MockTranscriber::new("Uploaded");
+  let uploads = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(streaming_settings()),
+    transcriber,
+    MockPaster::new(),
+  )
+  .with_streaming_transcriber(Arc::new(MockStreamingTranscriber::with_failure("socket closed")));
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(result.unwrap(), "Uploaded");
+  assert_eq!(uploads.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_streaming_is_skipped_when_disabled() {
+  let streaming = MockStreamingTranscriber::new("Streamed text");
+  let frames = streaming.frames_received.clone();
+  let manager = create_default_manager().with_streaming_transcriber(Arc::new(streaming));
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(result.unwrap(), "Hello world");
+  assert_eq!(frames.load(Ordering::SeqCst), 0);
+}
+
+// ============================================================================
+// Tests: State Transitions
+// ============================================================================
+
+#[tokio::test]
+async fn test_state_returns_to_idle_after_successful_flow() {
+  let manager = create_default_manager();
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  // Should be able to start recording again
+  let result = manager.start_recording(|_| {});
+  assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_state_returns_to_idle_after_error() {
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    MockTranscriber::with_failure("Error"),
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let _ = manager.stop_and_process(|_| {}).await;
+
+  // Should be able to start recording again after error
+  let result = manager.start_recording(|_| {});
+  assert!(result.is_ok());
+}
+
+// ============================================================================
+// Tests: Cancellation
+// ============================================================================
+
+/// Wait until a mock port has been called, giving the session task room to run.
+async fn wait_for_call(calls: &AtomicUsize) {
+  while calls.load(Ordering::SeqCst) == 0 {
+    tokio::task::yield_now().await;
+  }
+}
+
+#[test]
+fn test_cancel_when_idle_is_a_no_op() {
+  let manager = create_default_manager();
+
+  let mut updates = vec![];
+  let result = manager.cancel(|update| updates.push(update.state));
+
+  assert!(result.is_ok());
+  assert!(updates.is_empty());
+}
+
+#[tokio::test]
+async fn test_cancel_while_recording_discards_the_recording() {
+  let transcriber = MockTranscriber::new("Hello world");
+  let calls = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  manager.cancel(|update| updates.push(update.state)).unwrap();
+
+  assert_eq!(updates, vec![DictationState::Cancelled]);
+  // Releasing the hotkey afterwards finds nothing to process.
+  assert_eq!(
+    manager.stop_and_process(|_| {}).await.unwrap_err().to_string(),
+    "Not recording"
+  );
+  assert_eq!(calls.load(Ordering::SeqCst), 0);
+  assert!(manager.start_recording(|_| {}).is_ok());
+}
+
+#[tokio::test]
+async fn test_cancel_while_transcribing_skips_paste() {
+  let transcriber = MockTranscriber::hanging();
+  let calls = transcriber.transcribe_called.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let (result, cancelled) = tokio::join!(
+    manager.stop_and_process(|update| updates.push(update.state)),
+    async {
+      wait_for_call(&calls).await;
+      manager.cancel(|_| {})
+    }
+  );
+
+  assert!(cancelled.is_ok());
+  assert_eq!(result.unwrap_err(), DiktError::Cancelled);
+  assert_eq!(
+    updates,
+    vec![DictationState::Transcribing, DictationState::Cancelled]
+  );
+  assert!(manager.start_recording(|_| {}).is_ok());
+}
+
+#[tokio::test]
+async fn test_cancel_while_formatting_skips_paste() {
+  let settings = settings_with_mode(chat_mode("Formatter", "Format this"));
+  let formatter = MockFormatter::hanging();
+  let calls = formatter.format_called.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let (result, _) = tokio::join!(
+    manager.stop_and_process(|update| updates.push(update.state)),
+    async {
+      wait_for_call(&calls).await;
+      manager.cancel(|_| {})
+    }
+  );
+
+  // Cancelling must not fall back to pasting the unformatted text.
+  assert_eq!(result.unwrap_err(), DiktError::Cancelled);
+  assert_eq!(updates.last(), Some(&DictationState::Cancelled));
+  assert!(!updates.contains(&DictationState::Pasting));
+}
+
+#[tokio::test]
+async fn test_formatter_partials_are_reported_while_formatting() {
+  let settings = settings_with_mode(chat_mode("Formatter", "Format this"));
+  let formatter = MockFormatter::hanging();
+  let calls = formatter.format_called.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+    formatter,
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let (_, partial) = tokio::join!(manager.stop_and_process(|_| {}), async {
+    wait_for_call(&calls).await;
+    let partial = manager.take_partial_update();
+    manager.cancel(|_| {}).unwrap();
+    partial
+  });
+
+  let partial = partial.expect("expected a partial update while formatting");
+  assert_eq!(partial.state, DictationState::Formatting);
+  assert_eq!(partial.partial_text, Some("Hello world".to_string()));
+  assert!(manager.take_partial_update().is_none());
+}
+
+#[tokio::test]
+async fn test_cancelled_token_does_not_leak_into_next_session() {
+  let manager = create_default_manager();
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.cancel(|_| {}).unwrap();
+
+  manager.start_recording(|_| {}).unwrap();
+  let result = manager.stop_and_process(|_| {}).await;
+
+  assert_eq!(result.unwrap(), "Hello world");
+}
+
+// ============================================================================
+// Tests: Retry Last Dictation
+// ============================================================================
+
+#[tokio::test]
+async fn test_failed_dictation_can_be_retried_without_re_recording() {
+  let transcriber = MockTranscriber::echo().failing_first(1);
+  let calls = transcriber.transcribe_called.clone();
+  let recorder = MockRecorder::with_chunks(&["two minutes of explanation"]);
+  let manager = create_manager(
+    recorder,
+    MockSettingsStore::new(),
+    transcriber,
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let first = manager.stop_and_process(|update| updates.push(update)).await;
+  assert!(first.is_err());
+  let error = updates.last().unwrap();
+  assert_eq!(error.state, DictationState::Error);
+  assert!(error.can_retry);
+
+  let mut updates = vec![];
+  let retried = manager
+    .retry_last_dictation(|update| updates.push(update.state))
+    .await;
+
+  assert_eq!(retried.unwrap(), "two minutes of explanation");
+  assert_eq!(calls.load(Ordering::SeqCst), 2);
+  assert_eq!(
+    updates,
+    vec![
+      DictationState::Transcribing,
+      DictationState::Pasting,
+      DictationState::Done
+    ]
+  );
+}
+
+#[tokio::test]
+async fn test_retry_after_failed_paste_pastes_without_transcribing_again() {
+  let transcriber = MockTranscriber::new("Hello world");
+  let calls = transcriber.transcribe_called.clone();
+  let paster = MockPaster::new().failing_first(1);
+  let pastes = paster.paste_called.clone();
+  let manager = create_manager(MockRecorder::new(), MockSettingsStore::new(), transcriber, paster);
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  assert!(manager.stop_and_process(|update| updates.push(update)).await.is_err());
+  assert!(updates.last().unwrap().can_retry);
+
+  let mut updates = vec![];
+  let retried = manager
+    .retry_last_dictation(|update| updates.push(update.state))
+    .await;
+
+  assert_eq!(retried.unwrap(), "Hello world");
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+  assert_eq!(pastes.load(Ordering::SeqCst), 2);
+  assert_eq!(updates, vec![DictationState::Pasting, DictationState::Done]);
+  assert!(manager.retry_last_dictation(|_| {}).await.is_err());
+}
+
+#[tokio::test]
+async fn test_retry_without_failure_is_rejected() {
+  let manager = create_default_manager();
+
+  manager.start_recording(|_| {}).unwrap();
+  manager.stop_and_process(|_| {}).await.unwrap();
+
+  assert_eq!(
+    manager.retry_last_dictation(|_| {}).await.unwrap_err().to_string(),
+    "No failed dictation to retry"
+  );
+}
+
+#[tokio::test]
+async fn test_successful_retry_clears_the_kept_recording() {
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    MockTranscriber::new("Hello world").failing_first(1),
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let _ = manager.stop_and_process(|_| {}).await;
+
+  assert!(manager.retry_last_dictation(|_| {}).await.is_ok());
+  assert!(manager.retry_last_dictation(|_| {}).await.is_err());
+}
+
+#[tokio::test]
+async fn test_retry_uses_settings_from_the_failed_dictation() {
+  let settings = settings_with_mode(chat_mode("Formatter", "Format this"));
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world").failing_first(1),
+    MockPaster::new(),
+    MockFormatter::with_prefix("Formatted: "),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let _ = manager.stop_and_process(|_| {}).await;
+  manager.save_settings(AppSettings::default()).unwrap();
+
+  let retried = manager.retry_last_dictation(|_| {}).await;
+
+  assert_eq!(retried.unwrap(), "Formatted: Hello world");
+}
+
+#[tokio::test]
+async fn test_recorder_failure_leaves_nothing_to_retry() {
+  let manager = create_manager(
+    MockRecorder::with_stop_failure(),
+    MockSettingsStore::new(),
+    MockTranscriber::new("Hello world"),
+    MockPaster::new(),
+  );
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let _ = manager.stop_and_process(|update| updates.push(update)).await;
+
+  assert!(!updates.last().unwrap().can_retry);
+  assert!(manager.retry_last_dictation(|_| {}).await.is_err());
+}
+
+// ============================================================================
+// Tests: Offline Queue
+// ============================================================================
+
+fn unreachable_error() -> DiktError {
+  DiktError::Network("connection refused".to_string())
+}
+
+#[tokio::test]
+async fn test_unreachable_provider_queues_the_recording() {
+  let queue = MockOfflineQueue::default();
+  let entries = queue.entries.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    MockTranscriber::with_failure(unreachable_error()),
+    MockPaster::new(),
+  )
+  .with_offline_queue(Box::new(queue));
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let result = manager.stop_and_process(|update| updates.push(update)).await;
+
+  assert!(result.unwrap_err().to_string().contains("queued"));
+  assert_eq!(entries.lock().unwrap().len(), 1);
+  // The queue owns the recording now, so it cannot also be retried by hand.
+  assert!(!updates.last().unwrap().can_retry);
+  assert!(manager.retry_last_dictation(|_| {}).await.is_err());
+}
+
+#[tokio::test]
+async fn test_unreachable_formatter_keeps_the_recording_for_retry() {
+  let queue = MockOfflineQueue::default();
+  let entries = queue.entries.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(transform_settings()),
+    MockTranscriber::new("make it shorter"),
+    MockPaster::new(),
+    MockFormatter::failing_with(unreachable_error()),
+  )
+  .with_offline_queue(Box::new(queue))
+  .with_desktop_context(Box::new(MockDesktopContext::new()));
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let result = manager.stop_and_process(|update| updates.push(update)).await;
+
+  assert!(!result.unwrap_err().to_string().contains("queued"));
+  assert!(entries.lock().unwrap().is_empty());
+  assert!(updates.last().unwrap().can_retry);
+}
+
+#[tokio::test]
+async fn test_rejected_request_is_not_queued() {
+  let queue = MockOfflineQueue::default();
+  let entries = queue.entries.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    MockTranscriber::with_failure(DiktError::from_status(401, "bad key".to_string())),
+    MockPaster::new(),
+  )
+  .with_offline_queue(Box::new(queue));
+
+  manager.start_recording(|_| {}).unwrap();
+  let mut updates = vec![];
+  let _ = manager.stop_and_process(|update| updates.push(update)).await;
+
+  assert!(entries.lock().unwrap().is_empty());
+  assert!(updates.last().unwrap().can_retry);
+  let error = updates.last().unwrap().error.as_ref().unwrap();
+  assert_eq!(error.code(), "auth");
+}
+
+#[tokio::test]
+async fn test_queued_dictation_lands_in_history_without_pasting() {
+  let settings = AppSettings {
+    modes: vec![Mode {
+      id: "mode-1".to_string(),
+      name: "Formatter".to_string(),
+      system_prompt: "Format this".to_string(),
+      model: "chat-model".to_string(),
+      ..Default::default()
+    }],
+    ..Default::default()
+  };
+  let queue = MockOfflineQueue::with_entry(Some("mode-1"));
+  let entries = queue.entries.clone();
+  let paster = MockPaster::new();
+  let pasted = paster.paste_called.clone();
+  let manager = create_manager_with_formatter(
+    MockRecorder::new(),
+    MockSettingsStore::with_settings(settings),
+    MockTranscriber::new("Hello world"),
+    paster,
+    MockFormatter::with_prefix("Formatted: "),
+  )
+  .with_offline_queue(Box::new(queue));
+
+  let result = manager.process_next_queued().await;
+
+  // The mode comes from the queued session even though none is active now.
+  assert_eq!(result.unwrap().as_deref(), Some("Formatted: Hello world"));
+  assert!(entries.lock().unwrap().is_empty());
+  assert_eq!(pasted.load(Ordering::SeqCst), 0);
+  assert_eq!(manager.process_next_queued().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_queued_dictation_waits_while_provider_is_unreachable() {
+  let queue = MockOfflineQueue::with_entry(None);
+  let entries = queue.entries.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    MockTranscriber::with_failure(unreachable_error()),
+    MockPaster::new(),
+  )
+  .with_offline_queue(Box::new(queue));
+
+  for _ in 0..10 {
+    assert!(manager.process_next_queued().await.is_err());
+  }
+
+  let entries = entries.lock().unwrap();
+  assert_eq!(entries.len(), 1);
+  assert_eq!(entries[0].0.attempts, 0);
+}
+
+#[tokio::test]
+async fn test_queued_dictation_is_dropped_after_repeated_rejections() {
+  let queue = MockOfflineQueue::with_entry(None);
+  let entries = queue.entries.clone();
+  let manager = create_manager(
+    MockRecorder::new(),
+    MockSettingsStore::new(),
+    MockTranscriber::with_failure("API error 400 Bad Request: corrupt audio"),
+    MockPaster::new(),
+  )
+  .with_offline_queue(Box::new(queue));
+
+  for _ in 0..4 {
+    let _ = manager.process_next_queued().await;
+  }
+  assert_eq!(entries.lock().unwrap().len(), 1);
+
+  let err = manager.process_next_queued().await.unwrap_err().to_string();
+  assert!(err.contains("Dropped a queued dictation"));
+  assert!(entries.lock().unwrap().is_empty());
+}
+
+// ============================================================================
+// Tests: Provider Routing
+// ============================================================================
+
+#[tokio::test]
+async fn test_local_provider_uses_local_transcriber() {
+  use super::impls::ProviderTranscriber;
+
+  let settings = AppSettings {
+    provider: crate::transcribe_local::LOCAL_PROVIDER.to_string(),
+    ..Default::default()
+  };
+
+  let result = ProviderTranscriber::default()
+    .transcribe(&settings, mock_audio(b"RIFF"), None, &CancellationToken::new())
+    .await;
+
+  assert_eq!(result.unwrap_err().to_string(), "Missing local model path");
 }
 
 // ============================================================================
@@ -1,5 +1,9 @@
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
 
+use super::error::DiktError;
+
 fn default_true() -> bool {
   true
 }
@@ -14,6 +18,7 @@ pub enum DictationState {
   Pasting,
   Done,
   Error,
+  Cancelled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +28,18 @@ pub struct DictationUpdate {
   pub message: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub text: Option<String>,
+  /// Live transcript so far, from a streaming transcriber.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub partial_text: Option<String>,
+  /// Set on errors when the recording was kept and `retry_last_dictation` can run.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub can_retry: bool,
+  /// Set on errors; `message` carries the same text for older listeners.
+  #[serde(default, skip_serializing_if = "Option::is_none", skip_deserializing)]
+  pub error: Option<DiktError>,
+  /// Set on `Done`: how long each stage of the dictation took.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub timings_ms: Option<StageTimings>,
 }
 
 impl DictationUpdate {
@@ -31,6 +48,10 @@ impl DictationUpdate {
       state,
       message: None,
       text: None,
+      partial_text: None,
+      can_retry: false,
+      error: None,
+      timings_ms: None,
     }
   }
 
@@ -39,10 +60,319 @@ impl DictationUpdate {
     self
   }
 
+  pub fn error(mut self, error: DiktError) -> Self {
+    self.message = Some(error.to_string());
+    self.error = Some(error);
+    self
+  }
+
   pub fn text(mut self, text: impl Into<String>) -> Self {
     self.text = Some(text.into());
     self
   }
+
+  pub fn partial_text(mut self, partial_text: impl Into<String>) -> Self {
+    self.partial_text = Some(partial_text.into());
+    self
+  }
+
+  pub fn can_retry(mut self) -> Self {
+    self.can_retry = true;
+    self
+  }
+
+  pub fn timings(mut self, timings: StageTimings) -> Self {
+    self.timings_ms = Some(timings);
+    self
+  }
+}
+
+/// Milliseconds spent in each stage of a dictation. Stages that did not run
+/// (no recorder to stop on a retry, no active mode, nothing pasted) stay `None`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageTimings {
+  /// Stopping the recorder and encoding the recording.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub stop: Option<u64>,
+  /// Sending the audio to the provider.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub upload: Option<u64>,
+  /// Waiting for the transcript, after the upload when it was timed.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub transcribe: Option<u64>,
+  /// Running the active mode's formatter.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub format: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub paste: Option<u64>,
+}
+
+impl StageTimings {
+  /// Split the upload out of a `transcribe` time that included it.
+  pub fn split_upload(&mut self, upload_ms: Option<u64>) {
+    if let (Some(upload), Some(total)) = (upload_ms, self.transcribe) {
+      let upload = upload.min(total);
+      self.upload = Some(upload);
+      self.transcribe = Some(total - upload);
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+  #[default]
+  Wav,
+  Flac,
+  Opus,
+}
+
+impl AudioFormat {
+  pub fn mime_type(self) -> &'static str {
+    match self {
+      AudioFormat::Wav => "audio/wav",
+      AudioFormat::Flac => "audio/flac",
+      AudioFormat::Opus => "audio/ogg",
+    }
+  }
+
+  pub fn file_extension(self) -> &'static str {
+    match self {
+      AudioFormat::Wav => "wav",
+      AudioFormat::Flac => "flac",
+      AudioFormat::Opus => "ogg",
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamingProtocol {
+  /// OpenAI Realtime transcription sessions (base64 PCM16 at 24 kHz in JSON events).
+  #[default]
+  OpenaiRealtime,
+  /// Binary PCM16 frames at 16 kHz in, `partial`/`final` JSON messages out.
+  Generic,
+}
+
+impl StreamingProtocol {
+  pub fn sample_rate(self) -> u32 {
+    match self {
+      StreamingProtocol::OpenaiRealtime => 24_000,
+      StreamingProtocol::Generic => 16_000,
+    }
+  }
+
+  /// Model requested when `streaming_model` is empty.
+  pub fn default_model(self) -> &'static str {
+    match self {
+      StreamingProtocol::OpenaiRealtime => "gpt-4o-transcribe",
+      StreamingProtocol::Generic => "",
+    }
+  }
+
+  /// Entry in `provider_api_keys` used when `streaming_api_key_ref` is unset.
+  pub fn key_provider(self) -> Option<&'static str> {
+    match self {
+      StreamingProtocol::OpenaiRealtime => Some("openai"),
+      StreamingProtocol::Generic => None,
+    }
+  }
+}
+
+/// Window that had focus, as far as the platform reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FocusedWindow {
+  /// Application class, e.g. `firefox` or `org.gnome.Terminal`.
+  pub app: Option<String>,
+  pub title: Option<String>,
+}
+
+/// Selects a mode for dictations started in matching windows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ModeRule {
+  #[serde(default)]
+  pub id: String,
+  /// Case-insensitive substring of the application class; empty matches any app.
+  #[serde(default)]
+  pub app: String,
+  /// Case-insensitive substring of the window title; empty matches any title.
+  #[serde(default)]
+  pub title: String,
+  /// Mode to use; `None` pastes the raw transcript.
+  #[serde(default)]
+  pub mode_id: Option<String>,
+}
+
+impl ModeRule {
+  /// Rules with neither an app nor a title never match.
+  pub fn matches(&self, window: &FocusedWindow) -> bool {
+    let contains = |value: &Option<String>, pattern: &str| {
+      let pattern = pattern.trim().to_lowercase();
+      pattern.is_empty()
+        || value
+          .as_deref()
+          .is_some_and(|value| value.to_lowercase().contains(&pattern))
+    };
+    let constrained = !self.app.trim().is_empty() || !self.title.trim().is_empty();
+    constrained && contains(&window.app, &self.app) && contains(&window.title, &self.title)
+  }
+}
+
+/// What a mode does with the transcript.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ModeKind {
+  /// Rewrite the transcript itself.
+  #[default]
+  Format,
+  /// Treat the transcript as an instruction applied to the selected text (or
+  /// the clipboard when nothing is selected); the result replaces the selection.
+  Transform,
+}
+
+/// Chat API a mode formats with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatterBackend {
+  /// `/chat/completions` and `/models` on the transcription provider, bearer auth.
+  #[default]
+  OpenaiCompatible,
+  /// Anthropic `/messages` and `/models` with an `x-api-key` header; the key is
+  /// `provider_api_keys["anthropic"]` unless the mode names another.
+  AnthropicMessages,
+  /// Ollama `/api/chat` and `/api/tags` on a local server, no auth.
+  Ollama,
+}
+
+impl FormatterBackend {
+  /// Server used instead of the transcription provider's, if any.
+  pub fn default_base_url(self) -> Option<&'static str> {
+    match self {
+      FormatterBackend::OpenaiCompatible => None,
+      FormatterBackend::AnthropicMessages => Some("https://api.anthropic.com/v1"),
+      FormatterBackend::Ollama => Some("http://localhost:11434"),
+    }
+  }
+
+  /// Entry in `provider_api_keys` holding the key when the mode names none.
+  pub fn key_provider(self) -> Option<&'static str> {
+    match self {
+      FormatterBackend::AnthropicMessages => Some("anthropic"),
+      FormatterBackend::OpenaiCompatible | FormatterBackend::Ollama => None,
+    }
+  }
+}
+
+/// Base URL of a hosted provider, as in `PROVIDERS` in `src/constants.ts`;
+/// `None` for providers whose server the user enters.
+pub fn provider_base_url(provider: &str) -> Option<&'static str> {
+  match provider {
+    "groq" => Some("https://api.groq.com/openai/v1"),
+    "openai" => Some("https://api.openai.com/v1"),
+    _ => None,
+  }
+}
+
+/// How provider HTTP calls are retried on rate limits, gateway errors and
+/// dropped connections.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+  /// Total tries per request, including the first; 1 disables retries.
+  #[serde(default = "default_max_attempts")]
+  pub max_attempts: u32,
+  /// Delay before the first retry, doubled for each one after it.
+  #[serde(default = "default_base_delay_ms")]
+  pub base_delay_ms: u64,
+  /// Random spread applied to each delay, as a fraction of it (0.0 to 1.0).
+  #[serde(default = "default_jitter")]
+  pub jitter: f64,
+}
+
+fn default_max_attempts() -> u32 {
+  3
+}
+
+fn default_base_delay_ms() -> u64 {
+  500
+}
+
+fn default_jitter() -> f64 {
+  0.2
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: default_max_attempts(),
+      base_delay_ms: default_base_delay_ms(),
+      jitter: default_jitter(),
+    }
+  }
+}
+
+/// Live mono PCM frames forwarded by the recorder while it is still recording.
+///
+/// Dropping the sender marks the end of the audio.
+#[derive(Debug, Clone)]
+pub struct FrameSink {
+  pub sender: tokio::sync::mpsc::UnboundedSender<Vec<i16>>,
+  pub sample_rate: u32,
+}
+
+/// A finished recording, encoded and ready to upload.
+#[derive(Debug, Clone)]
+pub struct EncodedAudio {
+  pub bytes: Vec<u8>,
+  pub mime_type: String,
+  pub file_extension: String,
+  pub duration_secs: Option<f64>,
+}
+
+/// Where a chunk sits on the timeline of the recording it was cut from.
+///
+/// Chunks overlap their neighbours; `keep_from_secs..keep_until_secs` is the
+/// part this chunk is authoritative for when transcripts are stitched.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkWindow {
+  pub offset_secs: f64,
+  pub keep_from_secs: f64,
+  pub keep_until_secs: Option<f64>,
+}
+
+/// One uploadable piece of a recording. Short recordings are a single chunk.
+#[derive(Debug, Clone)]
+pub struct AudioChunk {
+  pub audio: EncodedAudio,
+  pub window: ChunkWindow,
+}
+
+/// A recording saved while the provider was unreachable, with the parts of the
+/// session it was captured under. Credentials are read from current settings
+/// when it is finally transcribed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedDictation {
+  pub id: String,
+  pub created_at_ms: i64,
+  /// Failed attempts that were not just the provider being unreachable.
+  #[serde(default)]
+  pub attempts: u32,
+  #[serde(default)]
+  pub active_mode_id: Option<String>,
+  #[serde(default)]
+  pub vocabulary: Vec<VocabularyEntry>,
+}
+
+/// A transcription API tried after the primary provider, using the key saved
+/// for its provider in `provider_api_keys`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TranscriptionEndpoint {
+  #[serde(default)]
+  pub provider: String,
+  #[serde(default)]
+  pub base_url: String,
+  #[serde(default)]
+  pub model: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -67,4 +397,156 @@ pub struct Mode {
   pub system_prompt: String,
   #[serde(default)]
   pub model: String,
+  #[serde(default)]
+  pub kind: ModeKind,
+  #[serde(default)]
+  pub backend: FormatterBackend,
+  /// Provider the chat requests go to, e.g. `"openai"`; `None` keeps the
+  /// backend's default (the transcription provider for `openai_compatible`).
+  #[serde(default)]
+  pub provider: Option<String>,
+  /// Overrides the backend's default base URL.
+  #[serde(default)]
+  pub base_url: Option<String>,
+  /// Entry in `provider_api_keys` with the key; defaults to `provider`.
+  #[serde(default)]
+  pub api_key_ref: Option<String>,
+  #[serde(default, flatten)]
+  pub params: GenerationParams,
+  /// Run in order, each on the previous output; empty means a single chat
+  /// request with `system_prompt`.
+  #[serde(default)]
+  pub steps: Vec<ModeStep>,
+  /// Few-shot pairs sent ahead of the transcript, with the last chat step of a pipeline.
+  #[serde(default)]
+  pub examples: Vec<ModeExample>,
+  /// Paste the transcript instead when the output reads like a reply to it.
+  /// Only for cleanup modes, whose output stays close to the dictation; modes
+  /// that rewrite freely, such as emails or translations, would always fail.
+  #[serde(default)]
+  pub reply_check: bool,
+}
+
+/// A transcript and the text the mode should have made of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct ModeExample {
+  #[serde(default)]
+  pub input: String,
+  #[serde(default)]
+  pub output: String,
+}
+
+/// One step of a mode's pipeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModeStep {
+  /// Chat request on the mode's endpoint; `model` overrides the mode's.
+  Llm {
+    #[serde(default)]
+    system_prompt: String,
+    #[serde(default)]
+    model: Option<String>,
+  },
+  /// The vocabulary's replacement rules.
+  Vocabulary,
+  /// Replace every match of `pattern`; `replacement` may use `$1` or `${name}`.
+  Regex {
+    #[serde(default)]
+    pattern: String,
+    #[serde(default)]
+    replacement: String,
+  },
+}
+
+impl ModeStep {
+  pub fn system_prompt(&self) -> Option<&str> {
+    match self {
+      ModeStep::Llm { system_prompt, .. } => Some(system_prompt),
+      _ => None,
+    }
+  }
+
+  /// Names the step in history.
+  pub fn label(&self) -> &'static str {
+    match self {
+      ModeStep::Llm { .. } => "llm",
+      ModeStep::Vocabulary => "vocabulary",
+      ModeStep::Regex { .. } => "regex",
+    }
+  }
+}
+
+/// What one pipeline step produced, kept in history to find the step that
+/// went wrong.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepOutput {
+  pub step: String,
+  #[serde(default)]
+  pub text: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// Mode output that was not pasted because it read like a reply to the
+/// dictation; the transcript was pasted instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedFormat {
+  pub text: String,
+  #[serde(default)]
+  pub reason: String,
+}
+
+/// Sampling settings sent with a mode's chat request; `None` leaves the
+/// provider's default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct GenerationParams {
+  #[serde(default)]
+  pub temperature: Option<f32>,
+  #[serde(default)]
+  pub max_tokens: Option<u32>,
+  #[serde(default)]
+  pub top_p: Option<f32>,
+  /// Sequences that end the output early.
+  #[serde(default)]
+  pub stop: Vec<String>,
+  /// JSON schema the output must match. Sent as `response_format` to
+  /// OpenAI-compatible APIs and `format` to Ollama; the Anthropic Messages API
+  /// has no equivalent, so it is left to the prompt there.
+  #[serde(default)]
+  pub json_schema: Option<serde_json::Value>,
+}
+
+impl Mode {
+  /// Entry in `provider_api_keys` this mode authenticates with, if not the
+  /// transcription provider's key.
+  pub fn key_ref(&self) -> Option<&str> {
+    non_empty(&self.api_key_ref)
+      .or_else(|| non_empty(&self.provider))
+      .or_else(|| self.backend.key_provider())
+  }
+
+  pub fn custom_base_url(&self) -> Option<&str> {
+    non_empty(&self.base_url)
+  }
+
+  /// Provider the mode names, if it is not the transcription provider.
+  pub fn other_provider(&self, transcription_provider: &str) -> Option<&str> {
+    non_empty(&self.provider).filter(|provider| *provider != transcription_provider)
+  }
+
+  /// Steps to run: `steps`, or a single chat request with the mode's own prompt.
+  pub fn pipeline(&self) -> Cow<'_, [ModeStep]> {
+    if self.steps.is_empty() {
+      Cow::Owned(vec![ModeStep::Llm {
+        system_prompt: self.system_prompt.clone(),
+        model: None,
+      }])
+    } else {
+      Cow::Borrowed(&self.steps)
+    }
+  }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+  value.as_deref().map(str::trim).filter(|value| !value.is_empty())
 }
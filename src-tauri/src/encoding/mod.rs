@@ -0,0 +1,109 @@
+mod flac;
+#[cfg(feature = "opus")]
+mod ogg_opus;
+#[cfg(not(feature = "opus"))]
+mod ogg_opus {
+    pub fn encode(_samples: &[i16], _sample_rate: u32) -> Result<Vec<u8>, String> {
+        Err(super::OPUS_UNAVAILABLE.to_string())
+    }
+}
+
+use std::io::Cursor;
+
+use hound::{SampleFormat as WavSampleFormat, WavSpec, WavWriter};
+
+use crate::domain::types::{AudioFormat, EncodedAudio};
+
+pub const OPUS_UNAVAILABLE: &str =
+    "This build does not include Opus encoding (enable the opus feature), use WAV or FLAC";
+
+/// Sample rates the Ogg/Opus encoder accepts, highest first.
+const OPUS_SAMPLE_RATES: [u32; 5] = [48_000, 24_000, 16_000, 12_000, 8_000];
+
+/// Clamp a sample rate to one the given format can encode.
+///
+/// Opus only accepts a fixed set of rates, so pick the highest supported rate
+/// that does not exceed the requested one. WAV and FLAC accept any rate.
+pub fn supported_sample_rate(format: AudioFormat, sample_rate: u32) -> u32 {
+    match format {
+        AudioFormat::Wav | AudioFormat::Flac => sample_rate,
+        AudioFormat::Opus => OPUS_SAMPLE_RATES
+            .iter()
+            .copied()
+            .find(|&rate| rate <= sample_rate)
+            .unwrap_or(8_000),
+    }
+}
+
+/// Whether this build can encode `format`; Opus needs the `opus` cargo feature.
+pub fn is_available(format: AudioFormat) -> bool {
+    format != AudioFormat::Opus || cfg!(feature = "opus")
+}
+
+/// Encode 16-bit mono samples into an upload-ready blob.
+pub fn encode(samples: &[i16], sample_rate: u32, format: AudioFormat) -> Result<EncodedAudio, String> {
+    let bytes = match format {
+        AudioFormat::Wav => encode_wav(samples, sample_rate)?,
+        AudioFormat::Flac => flac::encode(samples, sample_rate),
+        AudioFormat::Opus => ogg_opus::encode(samples, sample_rate)?,
+    };
+
+    Ok(EncodedAudio {
+        bytes,
+        mime_type: format.mime_type().to_string(),
+        file_extension: format.file_extension().to_string(),
+        duration_secs: (sample_rate > 0).then(|| samples.len() as f64 / sample_rate as f64),
+    })
+}
+
+fn encode_wav(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>, String> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: sample_rate.max(1),
+        bits_per_sample: 16,
+        sample_format: WavSampleFormat::Int,
+    };
+
+    let mut wav_buffer = Vec::new();
+    {
+        let mut writer =
+            WavWriter::new(Cursor::new(&mut wav_buffer), spec).map_err(|e| e.to_string())?;
+        for &sample in samples {
+            writer.write_sample(sample).map_err(|e| e.to_string())?;
+        }
+        writer.finalize().map_err(|e| e.to_string())?;
+    }
+
+    Ok(wav_buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opus_rate_is_clamped_to_supported_values() {
+        assert_eq!(supported_sample_rate(AudioFormat::Opus, 16_000), 16_000);
+        assert_eq!(supported_sample_rate(AudioFormat::Opus, 44_100), 24_000);
+        assert_eq!(supported_sample_rate(AudioFormat::Opus, 4_000), 8_000);
+        assert_eq!(supported_sample_rate(AudioFormat::Flac, 44_100), 44_100);
+    }
+
+    #[cfg(not(feature = "opus"))]
+    #[test]
+    fn opus_is_refused_without_the_feature() {
+        assert!(!is_available(AudioFormat::Opus));
+        assert!(is_available(AudioFormat::Flac));
+        let error = encode(&[0i16; 160], 16_000, AudioFormat::Opus).unwrap_err();
+        assert!(error.contains("opus feature"));
+    }
+
+    #[test]
+    fn wav_encoding_reports_mime_and_duration() {
+        let audio = encode(&vec![0i16; 8_000], 16_000, AudioFormat::Wav).unwrap();
+        assert_eq!(audio.mime_type, "audio/wav");
+        assert_eq!(audio.file_extension, "wav");
+        assert_eq!(audio.duration_secs, Some(0.5));
+        assert_eq!(&audio.bytes[0..4], b"RIFF");
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::Cursor;
+
+use audiopus::{coder::Encoder, Application, Bitrate, Channels, SampleRate};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+
+const FRAME_MS: u32 = 20;
+const BITRATE_BPS: i32 = 24_000;
+const MAX_PACKET_BYTES: usize = 4000;
+// Ogg Opus granule positions are always counted at 48 kHz.
+const GRANULE_RATE: u64 = 48_000;
+const STREAM_SERIAL: u32 = 0x6469_6b74;
+
+pub fn encode(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>, String> {
+    let opus_rate = SampleRate::try_from(sample_rate as i32)
+        .map_err(|_| format!("Opus does not support {sample_rate} Hz audio"))?;
+    let mut encoder = Encoder::new(opus_rate, Channels::Mono, Application::Voip)
+        .map_err(|e| format!("Failed to create Opus encoder: {e}"))?;
+    encoder
+        .set_bitrate(Bitrate::BitsPerSecond(BITRATE_BPS))
+        .map_err(|e| format!("Failed to set Opus bitrate: {e}"))?;
+    let lookahead = encoder
+        .lookahead()
+        .map_err(|e| format!("Failed to read Opus lookahead: {e}"))?;
+
+    let to_granule = |count: u64| count * GRANULE_RATE / sample_rate as u64;
+    let pre_skip = to_granule(lookahead as u64);
+
+    let mut writer = PacketWriter::new(Cursor::new(Vec::new()));
+    write_header_packet(&mut writer, opus_head(pre_skip as u16, sample_rate))?;
+    write_header_packet(&mut writer, opus_tags())?;
+
+    let frame_len = (sample_rate * FRAME_MS / 1000) as usize;
+    let frame_count = samples.len().div_ceil(frame_len).max(1);
+    let mut frame = vec![0i16; frame_len];
+    let mut packet = vec![0u8; MAX_PACKET_BYTES];
+
+    for index in 0..frame_count {
+        let start = index * frame_len;
+        let end = (start + frame_len).min(samples.len());
+        // The final frame is zero-padded; the end granule trims it on decode.
+        frame.fill(0);
+        frame[..end.saturating_sub(start)].copy_from_slice(&samples[start.min(end)..end]);
+
+        let len = encoder
+            .encode(&frame, &mut packet)
+            .map_err(|e| format!("Opus encoding failed: {e}"))?;
+
+        let is_last = index + 1 == frame_count;
+        let (end_info, granule) = if is_last {
+            (
+                PacketWriteEndInfo::EndStream,
+                pre_skip + to_granule(samples.len() as u64),
+            )
+        } else {
+            (
+                PacketWriteEndInfo::NormalPacket,
+                pre_skip + to_granule(((index + 1) * frame_len) as u64),
+            )
+        };
+        writer
+            .write_packet(packet[..len].to_vec().into_boxed_slice(), STREAM_SERIAL, end_info, granule)
+            .map_err(|e| format!("Failed to write Ogg page: {e}"))?;
+    }
+
+    Ok(writer.into_inner().into_inner())
+}
+
+fn write_header_packet(
+    writer: &mut PacketWriter<Cursor<Vec<u8>>>,
+    packet: Vec<u8>,
+) -> Result<(), String> {
+    // Each header packet must sit alone on its own page.
+    writer
+        .write_packet(packet.into_boxed_slice(), STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(|e| format!("Failed to write Ogg header: {e}"))
+}
+
+/// Identification header (RFC 7845, section 5.1), mono with mapping family 0.
+fn opus_head(pre_skip: u16, input_sample_rate: u32) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channel count
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    head
+}
+
+/// Comment header (RFC 7845, section 5.2) with a vendor string and no tags.
+fn opus_tags() -> Vec<u8> {
+    let vendor = b"dikt";
+    let mut tags = Vec::with_capacity(16 + vendor.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{opus_head, opus_tags};
+
+    #[test]
+    fn opus_head_layout() {
+        let head = opus_head(312, 16_000);
+        assert_eq!(head.len(), 19);
+        assert_eq!(&head[0..8], b"OpusHead");
+        assert_eq!(u16::from_le_bytes([head[10], head[11]]), 312);
+        assert_eq!(
+            u32::from_le_bytes([head[12], head[13], head[14], head[15]]),
+            16_000
+        );
+    }
+
+    #[test]
+    fn opus_tags_layout() {
+        let tags = opus_tags();
+        assert_eq!(&tags[0..8], b"OpusTags");
+        assert_eq!(u32::from_le_bytes([tags[8], tags[9], tags[10], tags[11]]), 4);
+        assert_eq!(&tags[12..16], b"dikt");
+    }
+}
@@ -1,49 +1,734 @@
+use futures_util::StreamExt;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::domain::error::DiktError;
+use crate::domain::types::{
+  provider_base_url, FormatterBackend, GenerationParams, Mode, ModeExample, RetryPolicy,
+};
+use crate::retry;
+use crate::settings::AppSettings;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Anthropic requires `max_tokens`; generous enough for any dictation.
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
+/// Name OpenAI requires for a `json_schema` response format.
+const RESPONSE_SCHEMA_NAME: &str = "formatted_text";
+
+/// What to ask the chat model for.
+pub struct ChatRequest<'a> {
+  pub model: &'a str,
+  pub system_prompt: &'a str,
+  /// Sent as user/assistant turns before `text`.
+  pub examples: &'a [ModeExample],
+  pub text: &'a str,
+  pub params: &'a GenerationParams,
+}
+
+impl<'a> ChatRequest<'a> {
+  pub fn for_mode(mode: &'a Mode, text: &'a str) -> Self {
+    Self {
+      model: &mode.model,
+      system_prompt: &mode.system_prompt,
+      examples: &mode.examples,
+      text,
+      params: &mode.params,
+    }
+  }
+}
+
+/// Chat server a mode formats with.
+pub struct ChatEndpoint<'a> {
+  pub backend: FormatterBackend,
+  pub base_url: &'a str,
+  pub api_key: &'a str,
+}
+
+impl<'a> ChatEndpoint<'a> {
+  /// The mode's own base URL and key if it has them, else its backend's
+  /// defaults; OpenAI-compatible modes default to the provider they name, or
+  /// the transcription provider.
+  pub fn for_mode(settings: &'a AppSettings, mode: &'a Mode) -> Self {
+    let backend = mode.backend;
+    let base_url = match mode.custom_base_url().or(backend.default_base_url()) {
+      Some(base_url) => base_url,
+      // Never send another provider's key to the transcription server;
+      // `save_settings` refuses modes whose provider has no known server.
+      None => match mode.other_provider(&settings.provider) {
+        Some(provider) => provider_base_url(provider).unwrap_or_default(),
+        None => &settings.base_url,
+      },
+    };
+    let api_key = match mode.key_ref() {
+      // The transcription key may have been edited since it was saved per provider.
+      Some(provider) if provider == settings.provider => settings.api_key.as_str(),
+      Some(provider) => settings
+        .provider_api_keys
+        .get(provider)
+        .map_or("", String::as_str),
+      None if backend == FormatterBackend::OpenaiCompatible => settings.api_key.as_str(),
+      None => "",
+    };
+    Self {
+      backend,
+      base_url,
+      api_key,
+    }
+  }
+
+  /// Authenticate `request` the way the backend expects.
+  pub fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match self.backend {
+      FormatterBackend::OpenaiCompatible => request.bearer_auth(self.api_key),
+      FormatterBackend::AnthropicMessages => request
+        .header("x-api-key", self.api_key)
+        .header("anthropic-version", ANTHROPIC_VERSION),
+      FormatterBackend::Ollama if self.api_key.is_empty() => request,
+      // Ollama itself ignores auth, but proxies in front of it may not.
+      FormatterBackend::Ollama => request.bearer_auth(self.api_key),
+    }
+  }
+
+  pub fn url(&self, path: &str) -> String {
+    format!("{}/{path}", self.base_url.trim_end_matches('/'))
+  }
+}
+
+/// Rewrite the request's text with a chat request to `endpoint`, streamed so
+/// `on_partial` sees the formatted text grow. Servers that ignore `stream` and
+/// answer with a plain response work too.
 pub async fn format_text(
-  base_url: &str,
-  api_key: &str,
-  model: &str,
-  system_prompt: &str,
-  text: &str,
-) -> Result<String, String> {
-  let trimmed = base_url.trim_end_matches('/');
-  let url = format!("{trimmed}/chat/completions");
-
-  let body = serde_json::json!({
-    "model": model,
-    "messages": [
-      { "role": "system", "content": system_prompt },
-      { "role": "user", "content": text }
-    ]
+  endpoint: &ChatEndpoint<'_>,
+  request: &ChatRequest<'_>,
+  retry: &RetryPolicy,
+  on_partial: &(dyn Fn(String) + Send + Sync),
+) -> Result<String, DiktError> {
+  let url = endpoint.url(match endpoint.backend {
+    FormatterBackend::OpenaiCompatible => "chat/completions",
+    FormatterBackend::AnthropicMessages => "messages",
+    FormatterBackend::Ollama => "api/chat",
   });
+  let body = request_body(endpoint.backend, request);
 
+  // A read timeout rather than a total one, so long drafts can keep streaming.
   let client = reqwest::Client::builder()
-    .timeout(std::time::Duration::from_secs(30))
+    .connect_timeout(std::time::Duration::from_secs(10))
+    .read_timeout(std::time::Duration::from_secs(30))
     .build()
     .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
 
-  let response = client
-    .post(&url)
-    .bearer_auth(api_key)
-    .json(&body)
-    .send()
+  let response = retry::send(retry, || endpoint.authorize(client.post(&url)).json(&body).send())
     .await
-    .map_err(|e| format!("Format request failed: {e}"))?;
+    .map_err(|e| retry::request_error("Format request failed", e))?;
 
   let status = response.status();
+  let is_stream = response
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| {
+      value.starts_with("text/event-stream") || value.starts_with("application/x-ndjson")
+    });
+
+  if status.is_success() && is_stream {
+    return read_stream(endpoint.backend, response, on_partial).await;
+  }
+
   let response_body = response
     .text()
     .await
     .map_err(|e| format!("Failed to read format response: {e}"))?;
 
   if !status.is_success() {
-    return Err(format!("Format API error {status}: {response_body}"));
+    return Err(DiktError::from_status(status.as_u16(), response_body));
   }
 
   let json: serde_json::Value = serde_json::from_str(&response_body)
     .map_err(|e| format!("Failed to parse format response: {e}"))?;
+  response_content(endpoint.backend, &json)
+}
+
+/// Request body in the backend's shape, with only the parameters the mode sets.
+fn request_body(backend: FormatterBackend, request: &ChatRequest<'_>) -> serde_json::Value {
+  let params = request.params;
+  let stop = (!params.stop.is_empty()).then_some(&params.stop);
+  let mut body = serde_json::json!({ "model": request.model, "stream": true });
+
+  match backend {
+    FormatterBackend::OpenaiCompatible => {
+      body["messages"] = messages(request, true);
+      set_optional(&mut body, "temperature", params.temperature);
+      set_optional(&mut body, "max_tokens", params.max_tokens);
+      set_optional(&mut body, "top_p", params.top_p);
+      set_optional(&mut body, "stop", stop);
+      // Not `strict`: OpenAI then rejects schemas without `additionalProperties:
+      // false` and a full `required` list, which most hand-written ones lack.
+      if let Some(schema) = &params.json_schema {
+        body["response_format"] = serde_json::json!({
+          "type": "json_schema",
+          "json_schema": { "name": RESPONSE_SCHEMA_NAME, "schema": schema }
+        });
+      }
+    }
+    FormatterBackend::AnthropicMessages => {
+      body["system"] = request.system_prompt.into();
+      body["messages"] = messages(request, false);
+      body["max_tokens"] = params.max_tokens.unwrap_or(ANTHROPIC_MAX_TOKENS).into();
+      set_optional(&mut body, "temperature", params.temperature);
+      set_optional(&mut body, "top_p", params.top_p);
+      set_optional(&mut body, "stop_sequences", stop);
+    }
+    FormatterBackend::Ollama => {
+      body["messages"] = messages(request, true);
+      let mut options = serde_json::json!({});
+      set_optional(&mut options, "temperature", params.temperature);
+      set_optional(&mut options, "num_predict", params.max_tokens);
+      set_optional(&mut options, "top_p", params.top_p);
+      set_optional(&mut options, "stop", stop);
+      if options.as_object().is_some_and(|options| !options.is_empty()) {
+        body["options"] = options;
+      }
+      set_optional(&mut body, "format", params.json_schema.as_ref());
+    }
+  }
+  body
+}
+
+/// The conversation: the examples as alternating user/assistant turns, then the
+/// transcript. Anthropic takes the system prompt as a separate field.
+fn messages(request: &ChatRequest<'_>, with_system: bool) -> serde_json::Value {
+  let system = with_system.then(|| serde_json::json!({ "role": "system", "content": request.system_prompt }));
+  let examples = request.examples.iter().flat_map(|example| {
+    [
+      serde_json::json!({ "role": "user", "content": example.input }),
+      serde_json::json!({ "role": "assistant", "content": example.output }),
+    ]
+  });
+  let text = serde_json::json!({ "role": "user", "content": request.text });
+  system.into_iter().chain(examples).chain([text]).collect()
+}
+
+fn set_optional<T: serde::Serialize>(object: &mut serde_json::Value, key: &str, value: Option<T>) {
+  if let Some(value) = value.and_then(|value| serde_json::to_value(value).ok()) {
+    object[key] = value;
+  }
+}
+
+/// Text of a non-streamed response, for servers that ignore `stream`. Like a
+/// stream, one that stopped for a reason like the token limit is an error.
+fn response_content(backend: FormatterBackend, json: &serde_json::Value) -> Result<String, DiktError> {
+  let (content, reason) = match backend {
+    FormatterBackend::OpenaiCompatible => {
+      let choice = &json["choices"][0];
+      let content = choice["message"]["content"]
+        .as_str()
+        .ok_or("Format response missing choices[0].message.content")?;
+      (content.to_string(), choice["finish_reason"].as_str())
+    }
+    FormatterBackend::AnthropicMessages => {
+      let blocks = json["content"]
+        .as_array()
+        .ok_or("Format response missing content blocks")?;
+      let content = blocks
+        .iter()
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+      (content, json["stop_reason"].as_str())
+    }
+    FormatterBackend::Ollama => {
+      let content = json["message"]["content"]
+        .as_str()
+        .ok_or("Format response missing message.content")?;
+      (content.to_string(), json["done_reason"].as_str())
+    }
+  };
+  // Servers that send no reason are taken at their word.
+  finished(content, reason.map_or(Finish::Complete, Finish::from_reason))
+}
+
+/// Collect the deltas of a streamed response. A stream that stops before its
+/// end marker, or ends for a reason like the token limit, is an error, so a
+/// cut-off draft is never pasted.
+async fn read_stream(
+  backend: FormatterBackend,
+  response: reqwest::Response,
+  on_partial: &(dyn Fn(String) + Send + Sync),
+) -> Result<String, DiktError> {
+  let mut stream = response.bytes_stream();
+  let mut buffer: Vec<u8> = Vec::new();
+  let mut content = String::new();
+  let mut finish = None;
+  let mut ended = false;
+
+  while !ended {
+    match stream.next().await {
+      Some(chunk) => {
+        let chunk = chunk.map_err(|e| format!("Format stream interrupted: {e}"))?;
+        buffer.extend_from_slice(&chunk);
+      }
+      // The last line may come without a newline after it.
+      None => {
+        ended = true;
+        if !buffer.is_empty() {
+          buffer.push(b'\n');
+        }
+      }
+    }
+
+    while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+      let line: Vec<u8> = buffer.drain(..=end).collect();
+      match parse_stream_line(backend, String::from_utf8_lossy(&line).trim_end())? {
+        StreamEvent::Delta { text, finish: reason } => {
+          finish = reason.or(finish);
+          if !text.is_empty() {
+            content.push_str(&text);
+            on_partial(content.clone());
+          }
+        }
+        // Servers that send no finish reason still mark the end.
+        StreamEvent::Done => return finished(content, finish.unwrap_or(Finish::Complete)),
+        StreamEvent::Ignored => {}
+      }
+    }
+  }
+
+  match finish {
+    Some(finish) => finished(content, finish),
+    None => Err("Format stream ended before the completion finished".into()),
+  }
+}
 
-  json["choices"][0]["message"]["content"]
-    .as_str()
-    .map(|s| s.to_string())
-    .ok_or_else(|| "Format response missing choices[0].message.content".to_string())
+fn finished(content: String, finish: Finish) -> Result<String, DiktError> {
+  match finish {
+    Finish::Complete => Ok(content),
+    Finish::Truncated(reason) => Err(DiktError::Other(format!(
+      "Format response stopped before the completion finished ({reason})"
+    ))),
+  }
+}
+
+#[derive(Debug, PartialEq)]
+enum StreamEvent {
+  Delta { text: String, finish: Option<Finish> },
+  Done,
+  /// Blank lines, comments, event names and events without text.
+  Ignored,
+}
+
+/// Why the model stopped generating.
+#[derive(Debug, PartialEq)]
+enum Finish {
+  Complete,
+  /// Cut off, e.g. by `max_tokens`, with the provider's reason.
+  Truncated(String),
+}
+
+impl Finish {
+  fn from_reason(reason: &str) -> Self {
+    match reason {
+      "stop" | "end_turn" | "stop_sequence" => Finish::Complete,
+      other => Finish::Truncated(other.to_string()),
+    }
+  }
+}
+
+/// Parse one line of a stream: server-sent events for OpenAI and Anthropic,
+/// one JSON object per line for Ollama.
+fn parse_stream_line(backend: FormatterBackend, line: &str) -> Result<StreamEvent, DiktError> {
+  let data = match backend {
+    FormatterBackend::Ollama => line.trim(),
+    _ => match line.strip_prefix("data:") {
+      Some(data) => data.trim(),
+      None => return Ok(StreamEvent::Ignored),
+    },
+  };
+  if data.is_empty() {
+    return Ok(StreamEvent::Ignored);
+  }
+  if data == "[DONE]" {
+    return Ok(StreamEvent::Done);
+  }
+
+  let json: serde_json::Value = serde_json::from_str(data)
+    .map_err(|e| format!("Unreadable format stream event: {e}"))?;
+  let error = json["error"]["message"].as_str().or(json["error"].as_str());
+  if let Some(message) = error {
+    return Err(DiktError::Other(format!("Format stream failed: {message}")));
+  }
+
+  match backend {
+    FormatterBackend::OpenaiCompatible => {
+      let choice = &json["choices"][0];
+      Ok(StreamEvent::Delta {
+        text: choice["delta"]["content"].as_str().unwrap_or("").to_string(),
+        finish: choice["finish_reason"].as_str().map(Finish::from_reason),
+      })
+    }
+    FormatterBackend::AnthropicMessages => match json["type"].as_str() {
+      Some("content_block_delta") => Ok(StreamEvent::Delta {
+        text: json["delta"]["text"].as_str().unwrap_or("").to_string(),
+        finish: None,
+      }),
+      Some("message_delta") => Ok(StreamEvent::Delta {
+        text: String::new(),
+        finish: json["delta"]["stop_reason"].as_str().map(Finish::from_reason),
+      }),
+      Some("message_stop") => Ok(StreamEvent::Done),
+      _ => Ok(StreamEvent::Ignored),
+    },
+    FormatterBackend::Ollama => Ok(StreamEvent::Delta {
+      text: json["message"]["content"].as_str().unwrap_or("").to_string(),
+      // Servers older than `done_reason` only say they are done.
+      finish: (json["done"] == true)
+        .then(|| Finish::from_reason(json["done_reason"].as_str().unwrap_or("stop"))),
+    }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+  use crate::mock_http::{self, Reply};
+
+  const EVENT_STREAM: &str = "Content-Type: text/event-stream";
+
+  async fn format_collecting(
+    backend: FormatterBackend,
+    url: &str,
+  ) -> (Result<String, DiktError>, Vec<String>) {
+    let partials = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&partials);
+    let on_partial = move |text: String| sink.lock().unwrap().push(text);
+    let endpoint = ChatEndpoint {
+      backend,
+      base_url: url,
+      api_key: "key",
+    };
+    let request = ChatRequest {
+      model: "model",
+      system_prompt: "Fix it.",
+      examples: &[],
+      text: "hello",
+      params: &GenerationParams::default(),
+    };
+    let retry = RetryPolicy::default();
+    let result = format_text(&endpoint, &request, &retry, &on_partial).await;
+    let partials = partials.lock().unwrap().clone();
+    (result, partials)
+  }
+
+  #[tokio::test]
+  async fn rate_limited_requests_are_retried() {
+    let server = mock_http::serve(vec![
+      Reply::status("429 Too Many Requests", "").header("Retry-After: 0"),
+      Reply::status("200 OK", r#"{"choices":[{"message":{"content":"Hello."}}]}"#),
+    ])
+    .await;
+
+    let (formatted, partials) = format_collecting(FormatterBackend::OpenaiCompatible, &server.url).await;
+    assert_eq!(formatted.unwrap(), "Hello.");
+    assert!(partials.is_empty());
+    assert_eq!(server.requests(), 2);
+  }
+
+  #[tokio::test]
+  async fn streamed_deltas_are_reported_as_they_arrive() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      ": keep-alive\n\
+       data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n\
+       data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n\
+       data: {\"choices\":[{\"delta\":{\"content\":\"lo.\"}}]}\n\n\
+       data: [DONE]\n\n",
+    )
+    .header(EVENT_STREAM)])
+    .await;
+
+    let (formatted, partials) = format_collecting(FormatterBackend::OpenaiCompatible, &server.url).await;
+    assert_eq!(formatted.unwrap(), "Hello.");
+    assert_eq!(partials, vec!["Hel", "Hello."]);
+  }
+
+  #[tokio::test]
+  async fn truncated_stream_is_an_error() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      "data: {\"choices\":[{\"delta\":{\"content\":\"Dear Sam,\"}}]}\n\n",
+    )
+    .header(EVENT_STREAM)])
+    .await;
+
+    let (formatted, _) = format_collecting(FormatterBackend::OpenaiCompatible, &server.url).await;
+    assert!(formatted.is_err());
+  }
+
+  #[tokio::test]
+  async fn stream_cut_off_by_the_token_limit_is_an_error() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      "data: {\"choices\":[{\"delta\":{\"content\":\"Dear Sam,\"}}]}\n\n\
+       data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"length\"}]}\n\n\
+       data: [DONE]\n\n",
+    )
+    .header(EVENT_STREAM)])
+    .await;
+
+    let (formatted, _) = format_collecting(FormatterBackend::OpenaiCompatible, &server.url).await;
+    assert!(formatted.unwrap_err().to_string().contains("(length)"));
+  }
+
+  #[tokio::test]
+  async fn reply_cut_off_by_the_token_limit_is_an_error_without_streaming() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      r#"{"choices":[{"message":{"content":"Dear Sam,"},"finish_reason":"length"}]}"#,
+    )])
+    .await;
+
+    let (formatted, _) = format_collecting(FormatterBackend::OpenaiCompatible, &server.url).await;
+    assert!(formatted.unwrap_err().to_string().contains("(length)"));
+  }
+
+  #[test]
+  fn only_a_natural_stop_completes_the_stream() {
+    assert_eq!(
+      parse_stream_line(
+        FormatterBackend::AnthropicMessages,
+        r#"data: {"type":"message_delta","delta":{"stop_reason":"max_tokens"}}"#
+      )
+      .unwrap(),
+      StreamEvent::Delta {
+        text: String::new(),
+        finish: Some(Finish::Truncated("max_tokens".to_string()))
+      }
+    );
+    assert_eq!(
+      parse_stream_line(
+        FormatterBackend::Ollama,
+        r#"{"message":{"content":""},"done":true,"done_reason":"length"}"#
+      )
+      .unwrap(),
+      StreamEvent::Delta {
+        text: String::new(),
+        finish: Some(Finish::Truncated("length".to_string()))
+      }
+    );
+    assert_eq!(Finish::from_reason("end_turn"), Finish::Complete);
+    assert_eq!(Finish::from_reason("stop_sequence"), Finish::Complete);
+  }
+
+  #[tokio::test]
+  async fn last_line_without_newline_is_read() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      "{\"message\":{\"role\":\"assistant\",\"content\":\"Okay.\"},\"done\":false}\n\
+       {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\"}",
+    )
+    .header("Content-Type: application/x-ndjson")])
+    .await;
+
+    let (formatted, _) = format_collecting(FormatterBackend::Ollama, &server.url).await;
+    assert_eq!(formatted.unwrap(), "Okay.");
+  }
+
+  #[tokio::test]
+  async fn anthropic_message_stream_is_read() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      "event: message_start\n\
+       data: {\"type\":\"message_start\",\"message\":{\"content\":[]}}\n\n\
+       event: content_block_delta\n\
+       data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi \"}}\n\n\
+       event: content_block_delta\n\
+       data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"there.\"}}\n\n\
+       event: message_stop\n\
+       data: {\"type\":\"message_stop\"}\n\n",
+    )
+    .header(EVENT_STREAM)])
+    .await;
+
+    let (formatted, partials) = format_collecting(FormatterBackend::AnthropicMessages, &server.url).await;
+    assert_eq!(formatted.unwrap(), "Hi there.");
+    assert_eq!(partials, vec!["Hi ", "Hi there."]);
+  }
+
+  #[tokio::test]
+  async fn ollama_ndjson_stream_is_read() {
+    let server = mock_http::serve(vec![Reply::status(
+      "200 OK",
+      "{\"message\":{\"role\":\"assistant\",\"content\":\"Ok\"},\"done\":false}\n\
+       {\"message\":{\"role\":\"assistant\",\"content\":\"ay.\"},\"done\":false}\n\
+       {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n",
+    )
+    .header("Content-Type: application/x-ndjson")])
+    .await;
+
+    let (formatted, partials) = format_collecting(FormatterBackend::Ollama, &server.url).await;
+    assert_eq!(formatted.unwrap(), "Okay.");
+    assert_eq!(partials, vec!["Ok", "Okay."]);
+  }
+
+  #[test]
+  fn finish_reason_without_done_completes_the_stream() {
+    let backend = FormatterBackend::OpenaiCompatible;
+    assert_eq!(
+      parse_stream_line(backend, r#"data: {"choices":[{"delta":{},"finish_reason":"stop"}]}"#)
+        .unwrap(),
+      StreamEvent::Delta {
+        text: String::new(),
+        finish: Some(Finish::Complete)
+      }
+    );
+    assert!(parse_stream_line(backend, "data: {not json").is_err());
+    assert_eq!(parse_stream_line(backend, "event: ping").unwrap(), StreamEvent::Ignored);
+  }
+
+  #[test]
+  fn mode_endpoint_falls_back_to_backend_then_transcription_provider() {
+    let mut settings = AppSettings {
+      api_key: "groq-key".to_string(),
+      ..AppSettings::default()
+    };
+    settings
+      .provider_api_keys
+      .insert("anthropic".to_string(), "anthropic-key".to_string());
+    settings
+      .provider_api_keys
+      .insert("openai".to_string(), "openai-key".to_string());
+
+    let mode = Mode::default();
+    let endpoint = ChatEndpoint::for_mode(&settings, &mode);
+    assert_eq!((endpoint.base_url, endpoint.api_key), (settings.base_url.as_str(), "groq-key"));
+
+    let mode = Mode {
+      backend: FormatterBackend::AnthropicMessages,
+      ..Mode::default()
+    };
+    let endpoint = ChatEndpoint::for_mode(&settings, &mode);
+    assert_eq!(
+      (endpoint.base_url, endpoint.api_key),
+      ("https://api.anthropic.com/v1", "anthropic-key")
+    );
+
+    let mode = Mode {
+      provider: Some("openai".to_string()),
+      base_url: Some("https://api.openai.com/v1".to_string()),
+      ..Mode::default()
+    };
+    let endpoint = ChatEndpoint::for_mode(&settings, &mode);
+    assert_eq!(
+      (endpoint.base_url, endpoint.api_key),
+      ("https://api.openai.com/v1", "openai-key")
+    );
+
+    // Without a base URL the named provider's own server is used.
+    let mode = Mode {
+      provider: Some("openai".to_string()),
+      ..Mode::default()
+    };
+    let endpoint = ChatEndpoint::for_mode(&settings, &mode);
+    assert_eq!(
+      (endpoint.base_url, endpoint.api_key),
+      ("https://api.openai.com/v1", "openai-key")
+    );
+
+    // An explicit reference wins over the provider, and a missing key is sent empty.
+    let mode = Mode {
+      provider: Some("openai".to_string()),
+      api_key_ref: Some("work-openai".to_string()),
+      ..Mode::default()
+    };
+    assert_eq!(ChatEndpoint::for_mode(&settings, &mode).api_key, "");
+  }
+
+  #[test]
+  fn generation_params_are_mapped_per_backend() {
+    let params = GenerationParams {
+      temperature: Some(0.25),
+      max_tokens: Some(300),
+      top_p: None,
+      stop: vec!["###".to_string()],
+      json_schema: Some(serde_json::json!({ "type": "object" })),
+    };
+    let request = ChatRequest {
+      model: "model",
+      system_prompt: "Fix it.",
+      examples: &[],
+      text: "hello",
+      params: &params,
+    };
+
+    let openai = request_body(FormatterBackend::OpenaiCompatible, &request);
+    assert_eq!(openai["temperature"], 0.25);
+    assert_eq!(openai["max_tokens"], 300);
+    assert!(openai.get("top_p").is_none());
+    assert_eq!(openai["stop"], serde_json::json!(["###"]));
+    assert_eq!(openai["response_format"]["json_schema"]["schema"]["type"], "object");
+    assert!(openai["response_format"]["json_schema"].get("strict").is_none());
+
+    let anthropic = request_body(FormatterBackend::AnthropicMessages, &request);
+    assert_eq!(anthropic["max_tokens"], 300);
+    assert_eq!(anthropic["stop_sequences"], serde_json::json!(["###"]));
+    assert!(anthropic.get("response_format").is_none());
+
+    let ollama = request_body(FormatterBackend::Ollama, &request);
+    assert_eq!(ollama["options"]["num_predict"], 300);
+    assert_eq!(ollama["format"]["type"], "object");
+
+    // Unset parameters leave the provider defaults alone.
+    let plain = ChatRequest {
+      params: &GenerationParams::default(),
+      ..request
+    };
+    let openai = request_body(FormatterBackend::OpenaiCompatible, &plain);
+    assert!(openai.get("temperature").is_none() && openai.get("stop").is_none());
+    assert!(request_body(FormatterBackend::Ollama, &plain).get("options").is_none());
+  }
+
+  #[test]
+  fn examples_are_sent_as_turns_before_the_transcript() {
+    let examples = [ModeExample {
+      input: "um send it friday".to_string(),
+      output: "Send it on Friday.".to_string(),
+    }];
+    let request = ChatRequest {
+      model: "model",
+      system_prompt: "Fix it.",
+      examples: &examples,
+      text: "hello",
+      params: &GenerationParams::default(),
+    };
+
+    let roles = |body: &serde_json::Value| -> Vec<String> {
+      body["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| message["role"].as_str().unwrap().to_string())
+        .collect()
+    };
+    let openai = request_body(FormatterBackend::OpenaiCompatible, &request);
+    assert_eq!(roles(&openai), ["system", "user", "assistant", "user"]);
+    assert_eq!(openai["messages"][2]["content"], "Send it on Friday.");
+    assert_eq!(openai["messages"][3]["content"], "hello");
+
+    let anthropic = request_body(FormatterBackend::AnthropicMessages, &request);
+    assert_eq!(roles(&anthropic), ["user", "assistant", "user"]);
+    assert_eq!(anthropic["system"], "Fix it.");
+  }
+
+  #[test]
+  fn complete_responses_are_mapped_per_backend() {
+    let anthropic = serde_json::json!({
+      "content": [{ "type": "text", "text": "Dear Sam," }, { "type": "text", "text": " hi." }]
+    });
+    assert_eq!(
+      response_content(FormatterBackend::AnthropicMessages, &anthropic).unwrap(),
+      "Dear Sam, hi."
+    );
+    let ollama = serde_json::json!({ "message": { "content": "Hi." }, "done": true });
+    assert_eq!(response_content(FormatterBackend::Ollama, &ollama).unwrap(), "Hi.");
+  }
 }
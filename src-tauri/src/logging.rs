@@ -0,0 +1,289 @@
+//! Log file in the dikt config dir, rotated by size, with API keys redacted.
+//!
+//! Bundled builds have no console (and none at all on Windows), so failures
+//! are only visible through this file. Every line also goes to stderr for
+//! development.
+
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::domain::error::DiktError;
+use crate::settings::AppSettings;
+
+const LOG_FILE_NAME: &str = "dikt.log";
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+/// Rotated files kept next to the current one, as `dikt.log.1` (newest) and up.
+const ROTATED_LOGS: usize = 2;
+/// Shorter keys are not redacted by value, so a stray short string cannot blank out log lines.
+const MIN_SECRET_LEN: usize = 8;
+const REDACTED: &str = "[REDACTED]";
+
+/// Key formats used by the supported providers, and bearer tokens in headers.
+static KEY_PATTERN: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(r"\b(?:sk|gsk|xai)[-_][A-Za-z0-9_\-]{16,}|(?i:bearer)\s+[A-Za-z0-9._~+/\-]+=*")
+    .expect("valid key pattern")
+});
+/// Keys from the settings, redacted wherever they appear.
+static SECRETS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+pub fn log_path() -> PathBuf {
+  crate::transcription_history::data_dir().join(LOG_FILE_NAME)
+}
+
+/// Install the global subscriber writing to the log file and stderr.
+pub fn init() {
+  let path = log_path();
+  let file_layer = match LogFile::open(&path) {
+    Ok(file) => Some(tracing_subscriber::fmt::layer().with_writer(file)),
+    Err(e) => {
+      eprintln!("Failed to open log file {}: {e}", path.display());
+      None
+    }
+  };
+  let stderr_layer = tracing_subscriber::fmt::layer().with_writer(|| Redacted(io::stderr()));
+
+  let _ = tracing_subscriber::registry()
+    .with(LevelFilter::INFO)
+    .with(file_layer)
+    .with(stderr_layer)
+    .try_init();
+}
+
+/// Redact the API keys in `settings` from every later log line.
+pub fn register_secrets(settings: &AppSettings) {
+  let Ok(mut secrets) = SECRETS.write() else {
+    return;
+  };
+  let keys = std::iter::once(&settings.api_key).chain(settings.provider_api_keys.values());
+  for key in keys {
+    let key = key.trim();
+    if key.len() >= MIN_SECRET_LEN && !secrets.iter().any(|secret| secret == key) {
+      secrets.push(key.to_string());
+    }
+  }
+}
+
+pub fn redact(text: &str) -> Cow<'_, str> {
+  let mut text = Cow::Borrowed(text);
+  if let Ok(secrets) = SECRETS.read() {
+    for secret in secrets.iter() {
+      if text.contains(secret.as_str()) {
+        text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+      }
+    }
+  }
+  match KEY_PATTERN.replace_all(&text, REDACTED) {
+    Cow::Borrowed(_) => text,
+    Cow::Owned(replaced) => Cow::Owned(replaced),
+  }
+}
+
+/// The current log and the rotated ones, oldest first, as one text for bug reports.
+pub fn export() -> Result<String, DiktError> {
+  read_logs(&log_path()).map_err(DiktError::Storage)
+}
+
+/// Show the log file in the system file manager.
+pub fn reveal() -> Result<(), DiktError> {
+  let path = log_path();
+  if !path.exists() {
+    return Err(DiktError::Storage("No log file yet".to_string()));
+  }
+
+  #[cfg(target_os = "macos")]
+  let status = std::process::Command::new("open").arg("-R").arg(&path).status();
+  #[cfg(target_os = "windows")]
+  let status = std::process::Command::new("explorer")
+    .arg(format!("/select,{}", path.display()))
+    .status();
+  #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+  let status = std::process::Command::new("xdg-open")
+    .arg(path.parent().unwrap_or(&path))
+    .status();
+
+  status
+    .map(|_| ())
+    .map_err(|e| DiktError::Other(format!("Failed to open file manager: {e}")))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+  let mut name = path.as_os_str().to_owned();
+  name.push(format!(".{index}"));
+  PathBuf::from(name)
+}
+
+fn read_logs(path: &Path) -> Result<String, String> {
+  let mut text = String::new();
+  let paths = (1..=ROTATED_LOGS)
+    .rev()
+    .map(|index| rotated_path(path, index))
+    .chain(std::iter::once(path.to_path_buf()));
+  for path in paths {
+    match fs::read_to_string(&path) {
+      Ok(contents) => text.push_str(&contents),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+      Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+    }
+  }
+  Ok(text)
+}
+
+/// Redacts keys from each formatted line before it reaches `W`.
+struct Redacted<W>(W);
+
+impl<W: Write> Write for Redacted<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let text = String::from_utf8_lossy(buf);
+    self.0.write_all(redact(&text).as_bytes())?;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.0.flush()
+  }
+}
+
+/// Append-only log that moves itself aside once it grows past `MAX_LOG_BYTES`.
+#[derive(Clone)]
+struct LogFile(Arc<Mutex<RotatingFile>>);
+
+struct RotatingFile {
+  path: PathBuf,
+  max_bytes: u64,
+  // Closed while rotating, since Windows cannot rename a file that is open.
+  file: Option<File>,
+  len: u64,
+}
+
+impl LogFile {
+  fn open(path: &Path) -> io::Result<Self> {
+    Ok(Self(Arc::new(Mutex::new(RotatingFile::open(
+      path,
+      MAX_LOG_BYTES,
+    )?))))
+  }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+  type Writer = Redacted<LogFile>;
+
+  fn make_writer(&'a self) -> Self::Writer {
+    Redacted(self.clone())
+  }
+}
+
+impl Write for LogFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut file = self
+      .0
+      .lock()
+      .map_err(|_| io::Error::other("Log file lock poisoned"))?;
+    file.write_all(buf)?;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl RotatingFile {
+  fn open(path: &Path, max_bytes: u64) -> io::Result<Self> {
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let len = file.metadata()?.len();
+    Ok(Self {
+      path: path.to_path_buf(),
+      max_bytes,
+      file: Some(file),
+      len,
+    })
+  }
+
+  fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+    if self.len > 0 && self.len + buf.len() as u64 > self.max_bytes {
+      self.rotate()?;
+    }
+    let Some(file) = self.file.as_mut() else {
+      return Err(io::Error::other("Log file is closed"));
+    };
+    file.write_all(buf)?;
+    self.len += buf.len() as u64;
+    Ok(())
+  }
+
+  fn rotate(&mut self) -> io::Result<()> {
+    self.file = None;
+    let shifted = self.shift_rotated();
+    // Reopened even when shifting failed, so logging carries on in the same file.
+    *self = Self::open(&self.path, self.max_bytes)?;
+    shifted
+  }
+
+  fn shift_rotated(&self) -> io::Result<()> {
+    let oldest = rotated_path(&self.path, ROTATED_LOGS);
+    if oldest.exists() {
+      fs::remove_file(oldest)?;
+    }
+    for index in (1..ROTATED_LOGS).rev() {
+      let from = rotated_path(&self.path, index);
+      if from.exists() {
+        fs::rename(&from, rotated_path(&self.path, index + 1))?;
+      }
+    }
+    fs::rename(&self.path, rotated_path(&self.path, 1))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dikt-logging-{name}-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn redacts_registered_keys_and_key_formats() {
+    register_secrets(&AppSettings {
+      api_key: "my-custom-secret-value".to_string(),
+      ..AppSettings::default()
+    });
+
+    let line = "key=my-custom-secret-value auth=Bearer abc.def-123 groq=gsk_0123456789abcdefABCD";
+    assert_eq!(
+      redact(line),
+      "key=[REDACTED] auth=[REDACTED] groq=[REDACTED]"
+    );
+    assert!(matches!(redact("nothing to hide"), Cow::Borrowed(_)));
+  }
+
+  #[test]
+  fn rotates_by_size_and_exports_oldest_first() {
+    let dir = test_dir("rotate");
+    let path = dir.join(LOG_FILE_NAME);
+    let mut file = RotatingFile::open(&path, 10).unwrap();
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+      file.write_all(line.as_bytes()).unwrap();
+    }
+
+    // Only `ROTATED_LOGS` old files are kept, so the first line is gone.
+    assert!(!rotated_path(&path, ROTATED_LOGS + 1).exists());
+    assert_eq!(read_logs(&path).unwrap(), "second\nthird\nfourth\n");
+    let _ = fs::remove_dir_all(dir);
+  }
+}
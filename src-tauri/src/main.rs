@@ -3,16 +3,25 @@
     windows_subsystem = "windows"
 )]
 
+mod active_window;
 mod audio;
 mod click_through;
 mod clipboard;
 mod commands;
 mod domain;
+mod encoding;
 mod format_text;
+mod logging;
 mod models_api;
+#[cfg(test)]
+mod mock_http;
+mod offline_queue;
+mod retry;
 mod settings;
 mod state;
 mod transcribe;
+mod transcribe_local;
+mod transcribe_stream;
 mod transcription_history;
 
 use tauri::{
@@ -22,6 +31,9 @@ use tauri::{
 };
 
 fn main() {
+    logging::init();
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "dikt starting");
+
     tauri::Builder::default()
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .manage(state::AppState::default())
@@ -79,6 +91,7 @@ fn main() {
                 click_through::setup(&window);
             }
             commands::start_audio_level_emitter(app.handle());
+            commands::start_offline_queue_worker(app.handle());
 
             if let Some(settings_window) = app.get_webview_window("settings") {
                 let app_handle = app.handle().clone();
@@ -95,6 +108,8 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             commands::start_recording,
             commands::stop_and_transcribe,
+            commands::cancel_dictation,
+            commands::retry_last_dictation,
             commands::get_settings,
             commands::save_settings,
             commands::save_vocabulary,
@@ -104,9 +119,14 @@ fn main() {
             commands::hide_settings_window,
             commands::update_hit_region,
             commands::fetch_provider_models,
+            commands::list_input_devices,
             commands::get_transcription_history,
+            commands::add_mode_example_from_history,
             commands::delete_transcription_history_item,
             commands::clear_transcription_history,
+            commands::get_latency_summary,
+            commands::reveal_log_file,
+            commands::export_log,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
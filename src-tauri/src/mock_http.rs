@@ -0,0 +1,117 @@
+//! A scripted HTTP server on a local port for tests of the provider clients.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub enum Reply {
+  Response {
+    status: &'static str,
+    headers: Vec<&'static str>,
+    body: &'static str,
+  },
+  /// Close the connection without answering.
+  HangUp,
+}
+
+impl Reply {
+  pub fn status(status: &'static str, body: &'static str) -> Self {
+    Reply::Response {
+      status,
+      headers: Vec::new(),
+      body,
+    }
+  }
+
+  /// Add a raw header line; a `Content-Type` replaces the default JSON one.
+  pub fn header(mut self, header: &'static str) -> Self {
+    if let Reply::Response { headers, .. } = &mut self {
+      headers.push(header);
+    }
+    self
+  }
+}
+
+pub struct MockServer {
+  /// Base URL, ending in `/v1` like the provider defaults.
+  pub url: String,
+  requests: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+  pub fn requests(&self) -> usize {
+    self.requests.load(Ordering::SeqCst)
+  }
+}
+
+/// Answer requests with `replies` in order, repeating the last one once they run out.
+pub async fn serve(replies: Vec<Reply>) -> MockServer {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let addr = listener.local_addr().unwrap();
+  let requests = Arc::new(AtomicUsize::new(0));
+  let counter = Arc::clone(&requests);
+
+  tokio::spawn(async move {
+    while let Ok((mut socket, _)) = listener.accept().await {
+      read_request(&mut socket).await;
+      let index = counter.fetch_add(1, Ordering::SeqCst).min(replies.len() - 1);
+      let Reply::Response {
+        status,
+        headers,
+        body,
+      } = &replies[index]
+      else {
+        continue;
+      };
+      let mut response = format!("HTTP/1.1 {status}\r\n");
+      let has_content_type = headers
+        .iter()
+        .any(|header| header.to_ascii_lowercase().starts_with("content-type:"));
+      if !has_content_type {
+        response.push_str("Content-Type: application/json\r\n");
+      }
+      for header in headers {
+        response.push_str(header);
+        response.push_str("\r\n");
+      }
+      response.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+      ));
+      let _ = socket.write_all(response.as_bytes()).await;
+    }
+  });
+
+  MockServer {
+    url: format!("http://{addr}/v1"),
+    requests,
+  }
+}
+
+/// Read the whole request so the client is not reset mid-upload.
+async fn read_request(socket: &mut TcpStream) {
+  let mut request = Vec::new();
+  let mut buf = [0u8; 4096];
+  loop {
+    let n = socket.read(&mut buf).await.unwrap_or(0);
+    if n == 0 {
+      return;
+    }
+    request.extend_from_slice(&buf[..n]);
+    let text = String::from_utf8_lossy(&request);
+    if let Some(header_end) = text.find("\r\n\r\n") {
+      let content_length = text[..header_end]
+        .lines()
+        .find_map(|line| {
+          let line = line.to_ascii_lowercase();
+          line.strip_prefix("content-length:")?.trim().parse::<usize>().ok()
+        })
+        .unwrap_or(0);
+      if request.len() >= header_end + 4 + content_length {
+        return;
+      }
+    }
+  }
+}
@@ -1,18 +1,26 @@
-pub async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>, String> {
-  let trimmed = base_url.trim_end_matches('/');
-  let url = format!("{trimmed}/models");
+use crate::domain::error::DiktError;
+use crate::domain::types::{FormatterBackend, RetryPolicy};
+use crate::format_text::ChatEndpoint;
+use crate::retry;
+
+/// Chat models offered by `endpoint`, sorted, without audio or embedding models.
+pub async fn fetch_models(
+  endpoint: &ChatEndpoint<'_>,
+  retry: &RetryPolicy,
+) -> Result<Vec<String>, DiktError> {
+  let url = match endpoint.backend {
+    FormatterBackend::OpenaiCompatible | FormatterBackend::AnthropicMessages => endpoint.url("models"),
+    FormatterBackend::Ollama => endpoint.url("api/tags"),
+  };
 
   let client = reqwest::Client::builder()
     .timeout(std::time::Duration::from_secs(10))
     .build()
     .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
 
-  let response = client
-    .get(&url)
-    .bearer_auth(api_key)
-    .send()
+  let response = retry::send(retry, || endpoint.authorize(client.get(&url)).send())
     .await
-    .map_err(|e| format!("Failed to fetch models: {e}"))?;
+    .map_err(|e| retry::request_error("Failed to fetch models", e))?;
 
   let status = response.status();
   let body = response
@@ -21,17 +29,14 @@ pub async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>,
     .map_err(|e| format!("Failed to read models response: {e}"))?;
 
   if !status.is_success() {
-    return Err(format!("Models API error {status}: {body}"));
+    return Err(DiktError::from_status(status.as_u16(), body));
   }
 
   let json: serde_json::Value =
     serde_json::from_str(&body).map_err(|e| format!("Failed to parse models response: {e}"))?;
 
-  let mut models: Vec<String> = json["data"]
-    .as_array()
-    .ok_or("Models response missing data array".to_string())?
-    .iter()
-    .filter_map(|item| item["id"].as_str().map(|s| s.to_string()))
+  let mut models: Vec<String> = model_ids(endpoint.backend, &json)?
+    .into_iter()
     .filter(|model_id| is_chat_model(model_id))
     .collect();
 
@@ -39,12 +44,29 @@ pub async fn fetch_models(base_url: &str, api_key: &str) -> Result<Vec<String>,
   models.dedup();
 
   if models.is_empty() {
-    return Err("No chat-capable models returned by provider".to_string());
+    return Err("No chat-capable models returned by provider".into());
   }
 
   Ok(models)
 }
 
+/// OpenAI and Anthropic list `data[].id`; Ollama lists its local `models[].name`.
+fn model_ids(backend: FormatterBackend, json: &serde_json::Value) -> Result<Vec<String>, DiktError> {
+  let (list, field) = match backend {
+    FormatterBackend::OpenaiCompatible | FormatterBackend::AnthropicMessages => ("data", "id"),
+    FormatterBackend::Ollama => ("models", "name"),
+  };
+  let items = json[list]
+    .as_array()
+    .ok_or_else(|| DiktError::Other(format!("Models response missing {list} array")))?;
+  Ok(
+    items
+      .iter()
+      .filter_map(|item| item[field].as_str().map(|s| s.to_string()))
+      .collect(),
+  )
+}
+
 fn is_chat_model(model_id: &str) -> bool {
   const EXCLUDED_TOKENS: [&str; 12] = [
     "whisper",
@@ -69,7 +91,10 @@ fn is_chat_model(model_id: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
-  use super::is_chat_model;
+  use super::{fetch_models, is_chat_model, model_ids};
+  use crate::domain::types::{FormatterBackend, RetryPolicy};
+  use crate::format_text::ChatEndpoint;
+  use crate::mock_http::{self, Reply};
 
   #[test]
   fn chat_model_filter_keeps_text_models() {
@@ -83,4 +108,42 @@ mod tests {
     assert!(!is_chat_model("gpt-4o-mini-transcribe"));
     assert!(!is_chat_model("text-embedding-3-large"));
   }
+
+  #[tokio::test]
+  async fn unavailable_provider_is_retried() {
+    let server = mock_http::serve(vec![
+      Reply::status("503 Service Unavailable", ""),
+      Reply::status("200 OK", r#"{"data":[{"id":"whisper-1"},{"id":"gpt-4o-mini"}]}"#),
+    ])
+    .await;
+    let retry = RetryPolicy {
+      base_delay_ms: 1,
+      ..RetryPolicy::default()
+    };
+
+    let endpoint = ChatEndpoint {
+      backend: FormatterBackend::OpenaiCompatible,
+      base_url: &server.url,
+      api_key: "key",
+    };
+
+    let models = fetch_models(&endpoint, &retry).await.unwrap();
+    assert_eq!(models, vec!["gpt-4o-mini".to_string()]);
+    assert_eq!(server.requests(), 2);
+  }
+
+  #[test]
+  fn model_lists_are_read_per_backend() {
+    let anthropic = serde_json::json!({ "data": [{ "id": "claude-haiku-4-5", "type": "model" }] });
+    assert_eq!(
+      model_ids(FormatterBackend::AnthropicMessages, &anthropic).unwrap(),
+      vec!["claude-haiku-4-5".to_string()]
+    );
+    let ollama = serde_json::json!({ "models": [{ "name": "llama3.2:latest", "size": 1 }] });
+    assert_eq!(
+      model_ids(FormatterBackend::Ollama, &ollama).unwrap(),
+      vec!["llama3.2:latest".to_string()]
+    );
+    assert!(model_ids(FormatterBackend::Ollama, &anthropic).is_err());
+  }
 }
@@ -0,0 +1,357 @@
+//! Recordings waiting for the transcription provider to become reachable.
+//!
+//! The index lives in `offline_queue.json` next to the transcription history,
+//! and each chunk's audio is kept in its own file under `offline_queue/`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::types::{AudioChunk, ChunkWindow, EncodedAudio, QueuedDictation};
+use crate::settings::AppSettings;
+
+const INITIAL_BACKOFF_SECS: u64 = 5;
+const MAX_BACKOFF_SECS: u64 = 300;
+
+// A failing dictation and the drain worker can touch the queue at the same time.
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueueEntry {
+    #[serde(flatten)]
+    dictation: QueuedDictation,
+    chunks: Vec<StoredChunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredChunk {
+    file_name: String,
+    mime_type: String,
+    file_extension: String,
+    #[serde(default)]
+    duration_secs: Option<f64>,
+    window: ChunkWindow,
+}
+
+/// Delay before the next drain attempt after `failures` failures in a row.
+pub fn backoff_delay(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    Duration::from_secs((INITIAL_BACKOFF_SECS << doublings).min(MAX_BACKOFF_SECS))
+}
+
+pub fn enqueue(chunks: &[AudioChunk], settings: &AppSettings) -> Result<(), String> {
+    let _guard = QUEUE_LOCK.lock().map_err(|_| "Offline queue lock poisoned".to_string())?;
+    enqueue_in(&crate::transcription_history::data_dir(), chunks, settings)
+}
+
+pub fn next() -> Result<Option<(QueuedDictation, Vec<AudioChunk>)>, String> {
+    let _guard = QUEUE_LOCK.lock().map_err(|_| "Offline queue lock poisoned".to_string())?;
+    next_in(&crate::transcription_history::data_dir())
+}
+
+pub fn remove(id: &str) -> Result<(), String> {
+    let _guard = QUEUE_LOCK.lock().map_err(|_| "Offline queue lock poisoned".to_string())?;
+    remove_in(&crate::transcription_history::data_dir(), id)
+}
+
+pub fn record_attempt(id: &str) -> Result<u32, String> {
+    let _guard = QUEUE_LOCK.lock().map_err(|_| "Offline queue lock poisoned".to_string())?;
+    record_attempt_in(&crate::transcription_history::data_dir(), id)
+}
+
+fn index_path(dir: &Path) -> PathBuf {
+    dir.join("offline_queue.json")
+}
+
+fn audio_dir(dir: &Path) -> PathBuf {
+    dir.join("offline_queue")
+}
+
+fn load_index(dir: &Path) -> Result<Vec<QueueEntry>, String> {
+    let path = index_path(dir);
+    let contents = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(format!(
+                "Failed to read offline queue '{}': {e}",
+                path.display()
+            ))
+        }
+    };
+    match serde_json::from_str(&contents) {
+        Ok(entries) => Ok(entries),
+        Err(e) => {
+            // Set aside rather than failing, or nothing could ever be queued again.
+            let aside = path.with_extension("json.corrupt");
+            tracing::warn!(
+                error = %e,
+                path = %aside.display(),
+                "offline queue unreadable, starting a new one"
+            );
+            fs::rename(&path, &aside).map_err(|e| {
+                format!(
+                    "Failed to move aside unreadable offline queue '{}': {e}",
+                    path.display()
+                )
+            })?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn save_index(dir: &Path, entries: &[QueueEntry]) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let path = index_path(dir);
+    let contents = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents).map_err(|e| {
+        format!(
+            "Failed to write temporary offline queue '{}': {e}",
+            tmp_path.display()
+        )
+    })?;
+    if fs::rename(&tmp_path, &path).is_err() {
+        let _ = fs::remove_file(&path);
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("Failed to move offline queue '{}' into place: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn enqueue_in(dir: &Path, chunks: &[AudioChunk], settings: &AppSettings) -> Result<(), String> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let audio_dir = audio_dir(dir);
+    fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
+
+    let mut stored = Vec::with_capacity(chunks.len());
+    for (index, chunk) in chunks.iter().enumerate() {
+        let file_name = format!("{id}-{index}.{}", chunk.audio.file_extension);
+        fs::write(audio_dir.join(&file_name), &chunk.audio.bytes)
+            .map_err(|e| format!("Failed to save queued audio: {e}"))?;
+        stored.push(StoredChunk {
+            file_name,
+            mime_type: chunk.audio.mime_type.clone(),
+            file_extension: chunk.audio.file_extension.clone(),
+            duration_secs: chunk.audio.duration_secs,
+            window: chunk.window,
+        });
+    }
+
+    let created_at_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis() as i64;
+
+    let mut entries = load_index(dir)?;
+    entries.push(QueueEntry {
+        dictation: QueuedDictation {
+            id,
+            created_at_ms,
+            attempts: 0,
+            active_mode_id: settings.active_mode_id.clone(),
+            vocabulary: settings.vocabulary.clone(),
+        },
+        chunks: stored,
+    });
+    save_index(dir, &entries)
+}
+
+/// The oldest entry whose audio can still be read. Entries with missing or
+/// unreadable audio are dropped, so they cannot hold up the ones behind them.
+fn next_in(dir: &Path) -> Result<Option<(QueuedDictation, Vec<AudioChunk>)>, String> {
+    let mut entries = load_index(dir)?;
+    let audio_dir = audio_dir(dir);
+    let mut dropped = false;
+    let mut next = None;
+    while !entries.is_empty() {
+        match read_chunks(&audio_dir, &entries[0]) {
+            Ok(chunks) => {
+                next = Some((entries[0].dictation.clone(), chunks));
+                break;
+            }
+            Err(e) => {
+                let entry = entries.remove(0);
+                tracing::warn!(
+                    id = %entry.dictation.id,
+                    error = %e,
+                    "dropping queued dictation with unreadable audio"
+                );
+                remove_audio(&audio_dir, &entry);
+                dropped = true;
+            }
+        }
+    }
+
+    if dropped {
+        save_index(dir, &entries)?;
+    }
+    Ok(next)
+}
+
+fn read_chunks(audio_dir: &Path, entry: &QueueEntry) -> Result<Vec<AudioChunk>, String> {
+    entry
+        .chunks
+        .iter()
+        .map(|chunk| {
+            let bytes = fs::read(audio_dir.join(&chunk.file_name))
+                .map_err(|e| format!("Failed to read queued audio '{}': {e}", chunk.file_name))?;
+            Ok(AudioChunk {
+                audio: EncodedAudio {
+                    bytes,
+                    mime_type: chunk.mime_type.clone(),
+                    file_extension: chunk.file_extension.clone(),
+                    duration_secs: chunk.duration_secs,
+                },
+                window: chunk.window,
+            })
+        })
+        .collect()
+}
+
+fn remove_audio(audio_dir: &Path, entry: &QueueEntry) {
+    for chunk in &entry.chunks {
+        let _ = fs::remove_file(audio_dir.join(&chunk.file_name));
+    }
+}
+
+fn remove_in(dir: &Path, id: &str) -> Result<(), String> {
+    let mut entries = load_index(dir)?;
+    let audio_dir = audio_dir(dir);
+    for entry in entries.iter().filter(|entry| entry.dictation.id == id) {
+        remove_audio(&audio_dir, entry);
+    }
+    entries.retain(|entry| entry.dictation.id != id);
+    save_index(dir, &entries)
+}
+
+fn record_attempt_in(dir: &Path, id: &str) -> Result<u32, String> {
+    let mut entries = load_index(dir)?;
+    let entry = entries
+        .iter_mut()
+        .find(|entry| entry.dictation.id == id)
+        .ok_or_else(|| format!("Queued dictation {id} not found"))?;
+    entry.dictation.attempts += 1;
+    let attempts = entry.dictation.attempts;
+    save_index(dir, &entries)?;
+    Ok(attempts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("dikt_test_{}", uuid::Uuid::new_v4()))
+            .join("dikt")
+    }
+
+    fn chunk(bytes: &[u8], offset_secs: f64) -> AudioChunk {
+        AudioChunk {
+            audio: EncodedAudio {
+                bytes: bytes.to_vec(),
+                mime_type: "audio/flac".to_string(),
+                file_extension: "flac".to_string(),
+                duration_secs: Some(60.0),
+            },
+            window: ChunkWindow {
+                offset_secs,
+                keep_from_secs: offset_secs,
+                keep_until_secs: None,
+            },
+        }
+    }
+
+    #[test]
+    fn queued_audio_and_session_survive_a_roundtrip() {
+        let dir = test_dir();
+        let settings = AppSettings {
+            active_mode_id: Some("mode-1".to_string()),
+            api_key: "sk-secret".to_string(),
+            ..Default::default()
+        };
+        enqueue_in(&dir, &[chunk(b"first", 0.0), chunk(b"second", 59.5)], &settings).unwrap();
+
+        let (dictation, chunks) = next_in(&dir).unwrap().unwrap();
+        assert_eq!(dictation.active_mode_id.as_deref(), Some("mode-1"));
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].audio.bytes, b"second");
+        assert_eq!(chunks[1].audio.mime_type, "audio/flac");
+        assert_eq!(chunks[1].window.offset_secs, 59.5);
+
+        // Credentials are never written to the queue.
+        let index = fs::read_to_string(index_path(&dir)).unwrap();
+        assert!(!index.contains("sk-secret"));
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn queue_drains_oldest_first_and_removes_audio() {
+        let dir = test_dir();
+        enqueue_in(&dir, &[chunk(b"one", 0.0)], &AppSettings::default()).unwrap();
+        enqueue_in(&dir, &[chunk(b"two", 0.0)], &AppSettings::default()).unwrap();
+
+        let (first, chunks) = next_in(&dir).unwrap().unwrap();
+        assert_eq!(chunks[0].audio.bytes, b"one");
+        remove_in(&dir, &first.id).unwrap();
+
+        let (_, chunks) = next_in(&dir).unwrap().unwrap();
+        assert_eq!(chunks[0].audio.bytes, b"two");
+        assert_eq!(fs::read_dir(audio_dir(&dir)).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn attempts_are_persisted() {
+        let dir = test_dir();
+        enqueue_in(&dir, &[chunk(b"one", 0.0)], &AppSettings::default()).unwrap();
+        let (dictation, _) = next_in(&dir).unwrap().unwrap();
+
+        assert_eq!(record_attempt_in(&dir, &dictation.id).unwrap(), 1);
+        assert_eq!(record_attempt_in(&dir, &dictation.id).unwrap(), 2);
+        assert_eq!(next_in(&dir).unwrap().unwrap().0.attempts, 2);
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn entry_with_missing_audio_is_dropped() {
+        let dir = test_dir();
+        let chunks = [chunk(b"one", 0.0), chunk(b"two", 59.5)];
+        enqueue_in(&dir, &chunks, &AppSettings::default()).unwrap();
+        enqueue_in(&dir, &[chunk(b"three", 0.0)], &AppSettings::default()).unwrap();
+        let (broken, _) = next_in(&dir).unwrap().unwrap();
+        fs::remove_file(audio_dir(&dir).join(format!("{}-1.flac", broken.id))).unwrap();
+
+        let (_, chunks) = next_in(&dir).unwrap().unwrap();
+        assert_eq!(chunks[0].audio.bytes, b"three");
+        assert_eq!(load_index(&dir).unwrap().len(), 1);
+        assert_eq!(fs::read_dir(audio_dir(&dir)).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn unreadable_index_is_set_aside() {
+        let dir = test_dir();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(index_path(&dir), "{ not json").unwrap();
+
+        assert!(next_in(&dir).unwrap().is_none());
+        enqueue_in(&dir, &[chunk(b"one", 0.0)], &AppSettings::default()).unwrap();
+        assert!(next_in(&dir).unwrap().is_some());
+        assert!(index_path(&dir).with_extension("json.corrupt").exists());
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_cap() {
+        assert_eq!(backoff_delay(1), Duration::from_secs(5));
+        assert_eq!(backoff_delay(2), Duration::from_secs(10));
+        assert_eq!(backoff_delay(4), Duration::from_secs(40));
+        assert_eq!(backoff_delay(10), Duration::from_secs(MAX_BACKOFF_SECS));
+        assert_eq!(backoff_delay(u32::MAX), Duration::from_secs(MAX_BACKOFF_SECS));
+    }
+}
@@ -0,0 +1,204 @@
+//! Retries for provider HTTP calls that failed for reasons worth waiting out.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+
+use crate::domain::error::DiktError;
+use crate::domain::types::RetryPolicy;
+
+/// Longest wait between attempts. A longer `Retry-After` is not waited out, so a
+/// dictation does not hang on a provider that asks for minutes.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Call `send` until it succeeds, fails permanently or `policy.max_attempts` is used up.
+///
+/// Rate limits, gateway errors and connection failures are retried after an
+/// exponential backoff with jitter, or after the server's `Retry-After`. The
+/// last response or error is returned as is, so callers keep their own error
+/// messages.
+pub async fn send<F, Fut>(policy: &RetryPolicy, mut send: F) -> reqwest::Result<Response>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = reqwest::Result<Response>>,
+{
+  let mut attempt = 1;
+  loop {
+    let outcome = send().await;
+    if attempt >= policy.max_attempts {
+      return outcome;
+    }
+
+    let delay = match &outcome {
+      Ok(response) if is_transient_status(response.status()) => {
+        match retry_after(response.headers()) {
+          Some(delay) if delay > MAX_DELAY => return outcome,
+          Some(delay) => delay,
+          None => backoff_delay(policy, attempt, random_unit()),
+        }
+      }
+      Err(error) if is_transient_error(error) => backoff_delay(policy, attempt, random_unit()),
+      _ => return outcome,
+    };
+
+    match &outcome {
+      Ok(response) => tracing::warn!(status = %response.status(), ?delay, attempt, "retrying"),
+      Err(error) => tracing::warn!(%error, ?delay, attempt, "retrying after request error"),
+    }
+    tokio::time::sleep(delay).await;
+    attempt += 1;
+  }
+}
+
+/// Classify a request that got no response, after any retries.
+pub fn request_error(context: &str, error: reqwest::Error) -> DiktError {
+  if error.is_connect() || error.is_timeout() {
+    DiktError::Network(format!("{context}: {error}"))
+  } else {
+    DiktError::Other(format!("{context}: {error}"))
+  }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+  matches!(
+    status,
+    StatusCode::TOO_MANY_REQUESTS
+      | StatusCode::BAD_GATEWAY
+      | StatusCode::SERVICE_UNAVAILABLE
+      | StatusCode::GATEWAY_TIMEOUT
+  )
+}
+
+fn is_transient_error(error: &reqwest::Error) -> bool {
+  // `is_request` covers connections reset or closed while the request was in flight.
+  error.is_connect() || error.is_timeout() || error.is_request()
+}
+
+/// `Retry-After` in delta-seconds; HTTP dates fall back to the backoff.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+  let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+  value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Delay before retry number `attempt` under `policy`, with jitter, for retries
+/// of work that spans several requests.
+pub fn delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+  backoff_delay(policy, attempt, random_unit())
+}
+
+/// Delay before retry number `attempt`, with `unit` a random value in `[0, 1)`.
+fn backoff_delay(policy: &RetryPolicy, attempt: u32, unit: f64) -> Duration {
+  let doublings = attempt.saturating_sub(1).min(16);
+  let base = Duration::from_millis(policy.base_delay_ms.saturating_mul(1 << doublings)).min(MAX_DELAY);
+  let jitter = policy.jitter.clamp(0.0, 1.0);
+  base.mul_f64(1.0 + jitter * (2.0 * unit - 1.0))
+}
+
+fn random_unit() -> f64 {
+  let bits = RandomState::new().build_hasher().finish();
+  (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use super::*;
+  use crate::mock_http::{self, Reply};
+
+  const FAST: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay_ms: 1,
+    jitter: 0.0,
+  };
+
+  async fn get(policy: &RetryPolicy, url: &str) -> reqwest::Result<Response> {
+    let client = reqwest::Client::new();
+    send(policy, || client.get(url).send()).await
+  }
+
+  #[test]
+  fn backoff_doubles_and_spreads_by_jitter() {
+    let policy = RetryPolicy {
+      max_attempts: 5,
+      base_delay_ms: 100,
+      jitter: 0.5,
+    };
+    assert_eq!(backoff_delay(&policy, 1, 0.5), Duration::from_millis(100));
+    assert_eq!(backoff_delay(&policy, 3, 0.5), Duration::from_millis(400));
+    assert_eq!(backoff_delay(&policy, 1, 0.0), Duration::from_millis(50));
+    assert_eq!(backoff_delay(&policy, 30, 0.5), MAX_DELAY);
+  }
+
+  #[tokio::test]
+  async fn transient_statuses_are_retried_until_success() {
+    let server = mock_http::serve(vec![
+      Reply::status("429 Too Many Requests", ""),
+      Reply::status("503 Service Unavailable", ""),
+      Reply::status("200 OK", "{}"),
+    ])
+    .await;
+
+    let response = get(&FAST, &server.url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.requests(), 3);
+  }
+
+  #[tokio::test]
+  async fn gives_up_after_max_attempts() {
+    let server = mock_http::serve(vec![Reply::status("502 Bad Gateway", "")]).await;
+
+    let response = get(&FAST, &server.url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(server.requests(), 3);
+  }
+
+  #[tokio::test]
+  async fn permanent_errors_are_not_retried() {
+    let server = mock_http::serve(vec![Reply::status("401 Unauthorized", "")]).await;
+
+    let response = get(&FAST, &server.url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(server.requests(), 1);
+  }
+
+  #[tokio::test]
+  async fn dropped_connections_are_retried() {
+    let server = mock_http::serve(vec![Reply::HangUp, Reply::status("200 OK", "{}")]).await;
+
+    let response = get(&FAST, &server.url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.requests(), 2);
+  }
+
+  #[tokio::test]
+  async fn retry_after_is_honored() {
+    let server = mock_http::serve(vec![
+      Reply::status("429 Too Many Requests", "").header("Retry-After: 1"),
+      Reply::status("200 OK", "{}"),
+    ])
+    .await;
+
+    let started = Instant::now();
+    let response = get(&FAST, &server.url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+  }
+
+  #[tokio::test]
+  async fn long_retry_after_is_not_waited_out() {
+    let server = mock_http::serve(vec![
+      Reply::status("503 Service Unavailable", "").header("Retry-After: 120"),
+      Reply::status("200 OK", "{}"),
+    ])
+    .await;
+
+    let response = get(&FAST, &server.url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(server.requests(), 1);
+  }
+}
@@ -6,7 +6,9 @@ use std::collections::HashMap;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
 
-use crate::domain::types::{Mode, VocabularyEntry};
+use crate::domain::types::{
+  AudioFormat, Mode, ModeRule, RetryPolicy, StreamingProtocol, TranscriptionEndpoint, VocabularyEntry,
+};
 
 const SERVICE_NAME: &str = "dikt";
 // Use Tauri's canonical modifier name. This resolves to Ctrl on Windows/Linux and Cmd on macOS.
@@ -23,6 +25,8 @@ pub struct AppSettings {
   #[serde(default = "default_copy_to_clipboard_on_success")]
   pub copy_to_clipboard_on_success: bool,
   pub api_key: String,
+  /// Keys by provider, including the ones modes refer to with `Mode::key_ref`.
+  /// Saved encrypted, like the transcription key.
   #[serde(default)]
   pub provider_api_keys: HashMap<String, String>,
   #[serde(default)]
@@ -31,6 +35,35 @@ pub struct AppSettings {
   pub active_mode_id: Option<String>,
   #[serde(default)]
   pub modes: Vec<Mode>,
+  #[serde(default)]
+  pub input_device: Option<String>,
+  #[serde(default = "default_target_sample_rate")]
+  pub target_sample_rate: u32,
+  #[serde(default)]
+  pub audio_format: AudioFormat,
+  #[serde(default)]
+  pub streaming_enabled: bool,
+  #[serde(default)]
+  pub streaming_url: String,
+  #[serde(default)]
+  pub streaming_protocol: StreamingProtocol,
+  /// Transcription model for live streaming; empty uses the protocol's default.
+  #[serde(default)]
+  pub streaming_model: String,
+  /// Entry in `provider_api_keys` with the streaming service's key; defaults
+  /// to the protocol's provider.
+  #[serde(default)]
+  pub streaming_api_key_ref: Option<String>,
+  #[serde(default)]
+  pub local_model_path: String,
+  #[serde(default)]
+  pub transcription_fallbacks: Vec<TranscriptionEndpoint>,
+  #[serde(default)]
+  pub retry_policy: RetryPolicy,
+  /// Modes picked by the focused window when recording starts; the first match
+  /// wins over `active_mode_id`.
+  #[serde(default)]
+  pub mode_rules: Vec<ModeRule>,
 }
 
 fn default_provider() -> String {
@@ -45,6 +78,10 @@ fn default_copy_to_clipboard_on_success() -> bool {
   false
 }
 
+fn default_target_sample_rate() -> u32 {
+  16_000
+}
+
 fn default_modes(provider: &str) -> Vec<Mode> {
   let model = match provider {
     "groq" => "llama-3.3-70b-versatile",
@@ -59,12 +96,15 @@ fn default_modes(provider: &str) -> Vec<Mode> {
       name: "Grammar & Punctuation".to_string(),
       system_prompt: "Fix grammar, punctuation, and spelling. Preserve the original meaning and tone. Return only the corrected text.".to_string(),
       model: model.clone(),
+      reply_check: true,
+      ..Mode::default()
     },
     Mode {
       id: uuid::Uuid::new_v4().to_string(),
       name: "Email Draft".to_string(),
       system_prompt: "Rewrite the following dictation as a professional email. Keep the same intent and key points. Return only the email body.".to_string(),
       model,
+      ..Mode::default()
     },
   ]
 }
@@ -90,6 +130,30 @@ struct StoredSettings {
   active_mode_id: Option<String>,
   #[serde(default)]
   modes: Vec<Mode>,
+  #[serde(default)]
+  input_device: Option<String>,
+  #[serde(default = "default_target_sample_rate")]
+  target_sample_rate: u32,
+  #[serde(default)]
+  audio_format: AudioFormat,
+  #[serde(default)]
+  streaming_enabled: bool,
+  #[serde(default)]
+  streaming_url: String,
+  #[serde(default)]
+  streaming_protocol: StreamingProtocol,
+  #[serde(default)]
+  streaming_model: String,
+  #[serde(default)]
+  streaming_api_key_ref: Option<String>,
+  #[serde(default)]
+  local_model_path: String,
+  #[serde(default)]
+  transcription_fallbacks: Vec<TranscriptionEndpoint>,
+  #[serde(default)]
+  retry_policy: RetryPolicy,
+  #[serde(default)]
+  mode_rules: Vec<ModeRule>,
 }
 
 impl Default for AppSettings {
@@ -106,6 +170,18 @@ impl Default for AppSettings {
       vocabulary: Vec::new(),
       active_mode_id: None,
       modes: Vec::new(),
+      input_device: None,
+      target_sample_rate: default_target_sample_rate(),
+      audio_format: AudioFormat::default(),
+      streaming_enabled: false,
+      streaming_url: String::new(),
+      streaming_protocol: StreamingProtocol::default(),
+      streaming_model: String::new(),
+      streaming_api_key_ref: None,
+      local_model_path: String::new(),
+      transcription_fallbacks: Vec::new(),
+      retry_policy: RetryPolicy::default(),
+      mode_rules: Vec::new(),
     }
   }
 }
@@ -143,6 +219,18 @@ pub fn load_settings() -> AppSettings {
           vocabulary,
           active_mode_id,
           modes,
+          input_device,
+          target_sample_rate,
+          audio_format,
+          streaming_enabled,
+          streaming_url,
+          streaming_protocol,
+          streaming_model,
+          streaming_api_key_ref,
+          local_model_path,
+          transcription_fallbacks,
+          retry_policy,
+          mode_rules,
         } = stored;
 
         settings.provider = provider;
@@ -154,11 +242,19 @@ pub fn load_settings() -> AppSettings {
         settings.vocabulary = vocabulary;
         settings.active_mode_id = active_mode_id;
         settings.modes = modes;
-        for (provider, encrypted) in encrypted_provider_api_keys {
-          if let Some(decrypted) = decrypt_api_key(&encrypted) {
-            settings.provider_api_keys.insert(provider, decrypted);
-          }
-        }
+        settings.input_device = input_device;
+        settings.target_sample_rate = target_sample_rate;
+        settings.audio_format = audio_format;
+        settings.streaming_enabled = streaming_enabled;
+        settings.streaming_url = streaming_url;
+        settings.streaming_protocol = streaming_protocol;
+        settings.streaming_model = streaming_model;
+        settings.streaming_api_key_ref = streaming_api_key_ref;
+        settings.local_model_path = local_model_path;
+        settings.transcription_fallbacks = transcription_fallbacks;
+        settings.retry_policy = retry_policy;
+        settings.mode_rules = mode_rules;
+        settings.provider_api_keys = decrypt_provider_api_keys(encrypted_provider_api_keys);
         should_seed_default_modes = !has_modes_field;
       }
     }
@@ -197,13 +293,7 @@ pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
     provider_api_keys.insert(settings.provider.clone(), settings.api_key.clone());
   }
 
-  let mut encrypted_provider_api_keys = HashMap::new();
-  for (provider, api_key) in provider_api_keys {
-    if api_key.trim().is_empty() {
-      continue;
-    }
-    encrypted_provider_api_keys.insert(provider, encrypt_api_key(&api_key));
-  }
+  let encrypted_provider_api_keys = encrypt_provider_api_keys(&provider_api_keys);
 
   let stored = StoredSettings {
     provider: settings.provider.clone(),
@@ -217,6 +307,18 @@ pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
     vocabulary: settings.vocabulary.clone(),
     active_mode_id: settings.active_mode_id.clone(),
     modes: settings.modes.clone(),
+    input_device: settings.input_device.clone(),
+    target_sample_rate: settings.target_sample_rate,
+    audio_format: settings.audio_format,
+    streaming_enabled: settings.streaming_enabled,
+    streaming_url: settings.streaming_url.clone(),
+    streaming_protocol: settings.streaming_protocol,
+    streaming_model: settings.streaming_model.clone(),
+    streaming_api_key_ref: settings.streaming_api_key_ref.clone(),
+    local_model_path: settings.local_model_path.clone(),
+    transcription_fallbacks: settings.transcription_fallbacks.clone(),
+    retry_policy: settings.retry_policy,
+    mode_rules: settings.mode_rules.clone(),
   };
 
   let path = settings_path()?;
@@ -300,6 +402,21 @@ fn decrypt_api_key(encrypted: &str) -> Option<String> {
   String::from_utf8(decrypted).ok()
 }
 
+fn encrypt_provider_api_keys(keys: &HashMap<String, String>) -> HashMap<String, String> {
+  keys
+    .iter()
+    .filter(|(_, api_key)| !api_key.trim().is_empty())
+    .map(|(provider, api_key)| (provider.clone(), encrypt_api_key(api_key)))
+    .collect()
+}
+
+fn decrypt_provider_api_keys(encrypted: HashMap<String, String>) -> HashMap<String, String> {
+  encrypted
+    .into_iter()
+    .filter_map(|(provider, encrypted)| Some((provider, decrypt_api_key(&encrypted)?)))
+    .collect()
+}
+
 fn store_api_key(api_key: &str) -> Result<(), String> {
   // Always store encrypted fallback (keyring may not persist on some systems like WSL)
   store_encrypted_api_key_fallback(api_key)?;
@@ -352,6 +469,18 @@ fn store_encrypted_api_key_fallback(api_key: &str) -> Result<(), String> {
       vocabulary: Vec::new(),
       active_mode_id: None,
       modes: Vec::new(),
+      input_device: None,
+      target_sample_rate: default_target_sample_rate(),
+      audio_format: AudioFormat::default(),
+      streaming_enabled: false,
+      streaming_url: String::new(),
+      streaming_protocol: StreamingProtocol::default(),
+      streaming_model: String::new(),
+      streaming_api_key_ref: None,
+      local_model_path: String::new(),
+      transcription_fallbacks: Vec::new(),
+      retry_policy: RetryPolicy::default(),
+      mode_rules: Vec::new(),
     })
   } else {
     StoredSettings {
@@ -366,6 +495,18 @@ fn store_encrypted_api_key_fallback(api_key: &str) -> Result<(), String> {
       vocabulary: Vec::new(),
       active_mode_id: None,
       modes: Vec::new(),
+      input_device: None,
+      target_sample_rate: default_target_sample_rate(),
+      audio_format: AudioFormat::default(),
+      streaming_enabled: false,
+      streaming_url: String::new(),
+      streaming_protocol: StreamingProtocol::default(),
+      streaming_model: String::new(),
+      streaming_api_key_ref: None,
+      local_model_path: String::new(),
+      transcription_fallbacks: Vec::new(),
+      retry_policy: RetryPolicy::default(),
+      mode_rules: Vec::new(),
     }
   };
 
@@ -407,7 +548,13 @@ fn clear_encrypted_api_key_fallback() {
 
 #[cfg(test)]
 mod tests {
-  use super::{json_has_modes_field, StoredSettings};
+  use std::collections::HashMap;
+
+  use super::{
+    decrypt_provider_api_keys, encrypt_provider_api_keys, json_has_modes_field,
+    StoredSettings,
+  };
+  use crate::domain::types::{FormatterBackend, GenerationParams, Mode, ModeStep};
 
   #[test]
   fn legacy_settings_without_vocabulary_deserialize() {
@@ -420,6 +567,7 @@ mod tests {
 
     let parsed: StoredSettings = serde_json::from_str(legacy_json).unwrap();
     assert!(parsed.vocabulary.is_empty());
+    assert_eq!(parsed.target_sample_rate, 16_000);
   }
 
   #[test]
@@ -437,6 +585,83 @@ mod tests {
     assert!(parsed.active_mode_id.is_none());
   }
 
+  #[test]
+  fn legacy_modes_deserialize_without_endpoint() {
+    let legacy_json = r#"{
+      "provider": "groq",
+      "base_url": "https://api.groq.com/openai/v1",
+      "model": "whisper-large-v3-turbo",
+      "hotkey": "CommandOrControl+Space",
+      "modes": [{ "id": "m", "name": "Clean", "system_prompt": "Fix it.", "model": "llama" }]
+    }"#;
+
+    let parsed: StoredSettings = serde_json::from_str(legacy_json).unwrap();
+    let mode = &parsed.modes[0];
+    assert_eq!(mode.backend, FormatterBackend::OpenaiCompatible);
+    assert_eq!(mode.key_ref(), None);
+    assert_eq!(mode.custom_base_url(), None);
+    assert_eq!(mode.params, GenerationParams::default());
+  }
+
+  #[test]
+  fn mode_generation_params_round_trip() {
+    let mode = Mode {
+      params: GenerationParams {
+        temperature: Some(0.2),
+        max_tokens: Some(512),
+        top_p: Some(0.9),
+        stop: vec!["\n\n".to_string()],
+        json_schema: Some(serde_json::json!({ "type": "object", "required": ["subject"] })),
+      },
+      ..Mode::default()
+    };
+
+    let json = serde_json::to_value(&mode).unwrap();
+    // Stored flat on the mode, next to its other fields.
+    assert_eq!(json["max_tokens"], 512);
+    let parsed: Mode = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.params, mode.params);
+  }
+
+  #[test]
+  fn mode_steps_are_tagged_by_type() {
+    let json = serde_json::json!({
+      "id": "pipeline",
+      "steps": [
+        { "type": "llm", "system_prompt": "Remove fillers" },
+        { "type": "vocabulary" },
+        { "type": "regex", "pattern": "\\s+", "replacement": " " },
+        { "type": "llm", "system_prompt": "Translate to German", "model": "gpt-4o" }
+      ]
+    });
+
+    let mode: Mode = serde_json::from_value(json).unwrap();
+    assert_eq!(mode.steps.len(), 4);
+    assert_eq!(mode.steps[1], ModeStep::Vocabulary);
+    assert_eq!(mode.steps[3].system_prompt(), Some("Translate to German"));
+
+    let stored = serde_json::to_value(&mode).unwrap();
+    assert_eq!(stored["steps"][2]["type"], "regex");
+    let parsed: Mode = serde_json::from_value(stored).unwrap();
+    assert_eq!(parsed.steps, mode.steps);
+  }
+
+  #[test]
+  fn mode_api_keys_are_stored_encrypted() {
+    let keys = HashMap::from([
+      ("work-openai".to_string(), "sk-mode-secret".to_string()),
+      ("blank".to_string(), "  ".to_string()),
+    ]);
+
+    let encrypted = encrypt_provider_api_keys(&keys);
+    assert!(!encrypted.contains_key("blank"));
+    assert_ne!(encrypted["work-openai"], "sk-mode-secret");
+    assert_eq!(
+      decrypt_provider_api_keys(encrypted)["work-openai"],
+      "sk-mode-secret"
+    );
+  }
+
   #[test]
   fn modes_field_detection_returns_false_when_missing() {
     let json = r#"{
@@ -1,7 +1,9 @@
+use std::sync::Arc;
+
 use crate::domain::{
   impls::{
-    ClipboardPaster, CpalRecorder, FileAndKeyringSettingsStore, OpenAiCompatibleFormatter,
-    OpenAiCompatibleTranscriber,
+    ClipboardPaster, CpalRecorder, FileAndKeyringSettingsStore, FileOfflineQueue,
+    ChatApiFormatter, ProviderTranscriber, SystemDesktopContext, WebSocketStreamingTranscriber,
   },
   manager::DictationSessionManager,
 };
@@ -16,10 +18,13 @@ impl Default for AppState {
       manager: DictationSessionManager::new(
         Box::new(CpalRecorder::default()),
         Box::new(FileAndKeyringSettingsStore),
-        Box::new(OpenAiCompatibleTranscriber),
+        Box::new(ProviderTranscriber::default()),
         Box::new(ClipboardPaster),
-        Box::new(OpenAiCompatibleFormatter),
-      ),
+        Box::new(ChatApiFormatter),
+      )
+      .with_streaming_transcriber(Arc::new(WebSocketStreamingTranscriber))
+      .with_offline_queue(Box::new(FileOfflineQueue))
+      .with_desktop_context(Box::new(SystemDesktopContext)),
     }
   }
 }
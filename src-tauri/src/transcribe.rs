@@ -1,6 +1,13 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
 use reqwest::multipart;
 use serde::{Deserialize, Serialize};
 
+use crate::domain::error::DiktError;
+use crate::domain::types::{EncodedAudio, RetryPolicy};
+use crate::retry;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranscriptionSegment {
   pub start: f64,
@@ -15,31 +22,76 @@ pub struct TranscriptionResult {
   pub language: Option<String>,
   #[allow(dead_code)] // Parsed from API, not yet stored in history — future use
   pub segments: Option<Vec<TranscriptionSegment>>,
+  /// Provider that served the request, when known.
+  pub provider: Option<String>,
+  /// Time spent sending the audio, when the request went over HTTP.
+  pub upload_ms: Option<u64>,
+}
+
+/// One transcription API to try, with the key to send to it.
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint<'a> {
+  pub provider: &'a str,
+  pub base_url: &'a str,
+  pub model: &'a str,
+  pub api_key: &'a str,
 }
 
+/// Transcribe with the first endpoint, failing over to the next one in order
+/// when it cannot be reached, is rate limited or returns a server error.
+/// Each endpoint is retried per `retry` before moving on.
 pub async fn transcribe(
-  base_url: &str,
-  api_key: &str,
-  model: &str,
-  provider: &str,
-  audio_data: Vec<u8>,
+  endpoints: &[Endpoint<'_>],
+  audio: EncodedAudio,
   prompt: Option<&str>,
-) -> Result<TranscriptionResult, String> {
-  if api_key.trim().is_empty() {
-    return Err("Missing API key".to_string());
+  retry: &RetryPolicy,
+) -> Result<TranscriptionResult, DiktError> {
+  let client = reqwest::Client::new();
+  let mut endpoints = endpoints.iter().peekable();
+  while let Some(endpoint) = endpoints.next() {
+    match transcribe_with(&client, retry, endpoint, audio.clone(), prompt).await {
+      Ok(mut result) => {
+        result.provider = Some(endpoint.provider.to_string());
+        return Ok(result);
+      }
+      Err(error) => match endpoints.peek() {
+        Some(next) if should_fail_over(&error) => {
+          tracing::warn!(
+            provider = endpoint.provider,
+            next = next.provider,
+            error = %DiktError::from(error),
+            "transcription failed, trying the next provider"
+          );
+        }
+        _ => return Err(error.into()),
+      },
+    }
   }
+  Err(DiktError::Other("No transcription provider configured".to_string()))
+}
 
-  let url = build_transcription_url(base_url);
-  let client = reqwest::Client::new();
-  let prompt_to_send = prompt.filter(|p| supports_prompt(provider, model) && !p.trim().is_empty());
+async fn transcribe_with(
+  client: &reqwest::Client,
+  retry: &RetryPolicy,
+  endpoint: &Endpoint<'_>,
+  audio: EncodedAudio,
+  prompt: Option<&str>,
+) -> Result<TranscriptionResult, ApiError> {
+  if endpoint.api_key.trim().is_empty() {
+    return Err(ApiError::MissingKey);
+  }
+
+  let url = build_transcription_url(endpoint.base_url);
+  let prompt_to_send =
+    prompt.filter(|p| supports_prompt(endpoint.provider, endpoint.model) && !p.trim().is_empty());
 
   if let Some(prompt_value) = prompt_to_send {
     let first_attempt = send_transcription_request(
-      &client,
+      client,
+      retry,
       &url,
-      api_key,
-      model,
-      audio_data.clone(),
+      endpoint,
+      audio.clone(),
       Some(prompt_value),
       true,
     )
@@ -48,39 +100,35 @@ pub async fn transcribe(
       Ok(result) => return Ok(result),
       Err(error) => {
         if should_retry_without_prompt(&error) {
-          return send_transcription_request(&client, &url, api_key, model, audio_data, None, true)
-            .await
-            .map_err(|retry_error| retry_error.to_string());
+          return send_transcription_request(client, retry, &url, endpoint, audio, None, true).await;
         }
         // If verbose_json caused the error, retry without it
         if should_retry_without_verbose(&error) {
           return send_transcription_request(
-            &client,
+            client,
+            retry,
             &url,
-            api_key,
-            model,
-            audio_data,
+            endpoint,
+            audio,
             Some(prompt_value),
             false,
           )
-          .await
-          .map_err(|retry_error| retry_error.to_string());
+          .await;
         }
-        return Err(error.to_string());
+        return Err(error);
       }
     }
   }
 
-  let result = send_transcription_request(&client, &url, api_key, model, audio_data.clone(), None, true).await;
+  let result =
+    send_transcription_request(client, retry, &url, endpoint, audio.clone(), None, true).await;
   match result {
     Ok(result) => Ok(result),
     Err(error) => {
       if should_retry_without_verbose(&error) {
-        send_transcription_request(&client, &url, api_key, model, audio_data, None, false)
-          .await
-          .map_err(|retry_error| retry_error.to_string())
+        send_transcription_request(client, retry, &url, endpoint, audio, None, false).await
       } else {
-        Err(error.to_string())
+        Err(error)
       }
     }
   }
@@ -104,8 +152,19 @@ fn supports_prompt(provider: &str, model: &str) -> bool {
   }
 }
 
+/// Transport failures, rate limits and server errors are worth another provider;
+/// anything else (bad key, bad request) would fail there too or needs fixing.
+fn should_fail_over(error: &ApiError) -> bool {
+  match error {
+    ApiError::Status { status, .. } => {
+      *status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    }
+    _ => true,
+  }
+}
+
 fn should_retry_without_verbose(error: &ApiError) -> bool {
-  let Some(status) = error.status else {
+  let ApiError::Status { status, body } = error else {
     return false;
   };
 
@@ -113,7 +172,7 @@ fn should_retry_without_verbose(error: &ApiError) -> bool {
     return false;
   }
 
-  let body = error.body.to_ascii_lowercase();
+  let body = body.to_ascii_lowercase();
   body.contains("response_format")
     || body.contains("verbose")
     || body.contains("unknown parameter")
@@ -122,7 +181,7 @@ fn should_retry_without_verbose(error: &ApiError) -> bool {
 }
 
 fn should_retry_without_prompt(error: &ApiError) -> bool {
-  let Some(status) = error.status else {
+  let ApiError::Status { status, body } = error else {
     return false;
   };
 
@@ -130,7 +189,7 @@ fn should_retry_without_prompt(error: &ApiError) -> bool {
     return false;
   }
 
-  let body = error.body.to_ascii_lowercase();
+  let body = body.to_ascii_lowercase();
   body.contains("prompt")
     || body.contains("unknown parameter")
     || body.contains("not allowed")
@@ -139,51 +198,43 @@ fn should_retry_without_prompt(error: &ApiError) -> bool {
 
 async fn send_transcription_request(
   client: &reqwest::Client,
+  retry: &RetryPolicy,
   url: &str,
-  api_key: &str,
-  model: &str,
-  audio_data: Vec<u8>,
+  endpoint: &Endpoint<'_>,
+  audio: EncodedAudio,
   prompt: Option<&str>,
   verbose: bool,
 ) -> Result<TranscriptionResult, ApiError> {
-  let mut form = multipart::Form::new()
-    .part(
-      "file",
-      multipart::Part::bytes(audio_data)
-        .file_name("audio.wav")
-        .mime_str("audio/wav")
-        .map_err(|error| ApiError::transport(error.to_string()))?,
-    )
-    .text("model", model.to_string());
-
-  if verbose {
-    form = form.text("response_format", "verbose_json");
-  }
-
-  if let Some(prompt_value) = prompt {
-    form = form.text("prompt", prompt_value.to_string());
-  }
-
-  let response = client
-    .post(url)
-    .bearer_auth(api_key)
-    .multipart(form)
-    .send()
-    .await
-    .map_err(|error| ApiError::transport(error.to_string()))?;
+  let api_key = endpoint.api_key;
+  let mut attempt_started = Instant::now();
+  let uploaded_at = Arc::new(Mutex::new(None));
+  let response = retry::send(retry, || {
+    attempt_started = Instant::now();
+    // A multipart body is consumed by sending it, so every attempt builds its own.
+    let form = transcription_form(&audio, endpoint.model, prompt, verbose, &uploaded_at);
+    async move { client.post(url).bearer_auth(api_key).multipart(form?).send().await }
+  })
+  .await
+  .map_err(|error| {
+    if error.is_connect() || error.is_timeout() {
+      ApiError::Unreachable(error.to_string())
+    } else {
+      ApiError::Transport(error.to_string())
+    }
+  })?;
 
   let status = response.status();
   let body = response
     .text()
     .await
-    .map_err(|error| ApiError::transport(error.to_string()))?;
+    .map_err(|error| ApiError::Transport(error.to_string()))?;
 
   if !status.is_success() {
-    return Err(ApiError::api(status, body));
+    return Err(ApiError::Status { status, body });
   }
 
   let json: serde_json::Value = serde_json::from_str(&body)
-    .map_err(|error| ApiError::transport(error.to_string()))?;
+    .map_err(|error| ApiError::Transport(error.to_string()))?;
 
   let text = json["text"].as_str().unwrap_or("").to_string();
   let duration_secs = json["duration"].as_f64();
@@ -205,43 +256,193 @@ async fn send_transcription_request(
     duration_secs,
     language,
     segments,
+    provider: None,
+    upload_ms: uploaded_at
+      .lock()
+      .ok()
+      .and_then(|uploaded_at| *uploaded_at)
+      .map(|uploaded_at: Instant| uploaded_at.duration_since(attempt_started).as_millis() as u64),
   })
 }
 
-#[derive(Debug)]
-struct ApiError {
-  status: Option<reqwest::StatusCode>,
-  body: String,
-}
+fn transcription_form(
+  audio: &EncodedAudio,
+  model: &str,
+  prompt: Option<&str>,
+  verbose: bool,
+  uploaded_at: &Arc<Mutex<Option<Instant>>>,
+) -> reqwest::Result<multipart::Form> {
+  let file_name = format!("audio.{}", audio.file_extension);
+  let mut form = multipart::Form::new()
+    .part(
+      "file",
+      multipart::Part::stream_with_length(
+        timed_body(audio.bytes.clone(), Arc::clone(uploaded_at)),
+        audio.bytes.len() as u64,
+      )
+      .file_name(file_name)
+      .mime_str(&audio.mime_type)?,
+    )
+    .text("model", model.to_string());
 
-impl ApiError {
-  fn api(status: reqwest::StatusCode, body: String) -> Self {
-    Self {
-      status: Some(status),
-      body,
-    }
+  if verbose {
+    form = form.text("response_format", "verbose_json");
   }
 
-  fn transport(message: String) -> Self {
-    Self {
-      status: None,
-      body: message,
-    }
+  if let Some(prompt_value) = prompt {
+    form = form.text("prompt", prompt_value.to_string());
   }
+
+  Ok(form)
 }
 
-impl std::fmt::Display for ApiError {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    match self.status {
-      Some(status) => write!(f, "API error {status}: {}", self.body),
-      None => write!(f, "{}", self.body),
+/// The audio as a request body that notes in `uploaded_at` when the
+/// connection has taken its last byte.
+fn timed_body(bytes: Vec<u8>, uploaded_at: Arc<Mutex<Option<Instant>>>) -> reqwest::Body {
+  const CHUNK_BYTES: usize = 64 * 1024;
+
+  if let Ok(mut uploaded_at) = uploaded_at.lock() {
+    *uploaded_at = None;
+  }
+  let chunks = futures_util::stream::unfold(0, move |offset| {
+    let end = (offset + CHUNK_BYTES).min(bytes.len());
+    let chunk = (offset < end).then(|| bytes[offset..end].to_vec());
+    if chunk.is_none() {
+      if let Ok(mut uploaded_at) = uploaded_at.lock() {
+        *uploaded_at = Some(Instant::now());
+      }
+    }
+    std::future::ready(chunk.map(|chunk| (Ok::<_, std::io::Error>(chunk), end)))
+  });
+  reqwest::Body::wrap_stream(chunks)
+}
+
+/// How one request failed, kept detailed so the retry and failover decisions
+/// above can look at it before it becomes a `DiktError`.
+#[derive(Debug)]
+enum ApiError {
+  Status {
+    status: reqwest::StatusCode,
+    body: String,
+  },
+  Unreachable(String),
+  MissingKey,
+  Transport(String),
+}
+
+impl From<ApiError> for DiktError {
+  fn from(error: ApiError) -> Self {
+    match error {
+      ApiError::Status { status, body } => DiktError::from_status(status.as_u16(), body),
+      ApiError::Unreachable(message) => DiktError::Network(message),
+      ApiError::MissingKey => DiktError::Auth("Missing API key".to_string()),
+      ApiError::Transport(message) => DiktError::Other(message),
     }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use super::{build_transcription_url, supports_prompt};
+  use super::{build_transcription_url, supports_prompt, transcribe, Endpoint};
+  use crate::domain::types::{EncodedAudio, RetryPolicy};
+  use crate::mock_http::{self, Reply};
+
+  const NO_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 1,
+    base_delay_ms: 0,
+    jitter: 0.0,
+  };
+
+  fn endpoint<'a>(provider: &'a str, base_url: &'a str, api_key: &'a str) -> Endpoint<'a> {
+    Endpoint {
+      provider,
+      base_url,
+      model: "whisper-large-v3",
+      api_key,
+    }
+  }
+
+  fn audio() -> EncodedAudio {
+    EncodedAudio {
+      bytes: vec![0; 64],
+      mime_type: "audio/wav".to_string(),
+      file_extension: "wav".to_string(),
+      duration_secs: None,
+    }
+  }
+
+  #[tokio::test]
+  async fn fails_over_on_server_errors_and_rate_limits() {
+    let down = mock_http::serve(vec![Reply::status("503 Service Unavailable", "")]).await;
+    let limited = mock_http::serve(vec![Reply::status("429 Too Many Requests", "")]).await;
+    let up = mock_http::serve(vec![Reply::status("200 OK", r#"{"text":"hello"}"#)]).await;
+
+    let result = transcribe(
+      &[
+        endpoint("groq", &down.url, "key"),
+        endpoint("custom", &limited.url, "key"),
+        endpoint("openai", &up.url, "key"),
+      ],
+      audio(),
+      None,
+      &NO_RETRY,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(result.text, "hello");
+    assert_eq!(result.provider.as_deref(), Some("openai"));
+    // The mock server reads the whole body, so the upload finished and was timed.
+    assert!(result.upload_ms.is_some());
+  }
+
+  #[tokio::test]
+  async fn missing_key_fails_over_but_rejected_key_does_not() {
+    let up = mock_http::serve(vec![Reply::status("200 OK", r#"{"text":"hello"}"#)]).await;
+    let unauthorized = mock_http::serve(vec![Reply::status("401 Unauthorized", "bad key")]).await;
+
+    let result = transcribe(
+      &[endpoint("groq", &up.url, ""), endpoint("openai", &up.url, "key")],
+      audio(),
+      None,
+      &NO_RETRY,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.provider.as_deref(), Some("openai"));
+
+    let error = transcribe(
+      &[endpoint("groq", &unauthorized.url, "key"), endpoint("openai", &up.url, "key")],
+      audio(),
+      None,
+      &NO_RETRY,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error.code(), "auth");
+    assert!(error.to_string().contains("401"), "{error}");
+    assert_eq!(up.requests(), 1);
+  }
+
+  #[tokio::test]
+  async fn transient_errors_are_retried_before_failing_over() {
+    let flaky = mock_http::serve(vec![
+      Reply::status("502 Bad Gateway", ""),
+      Reply::status("200 OK", r#"{"text":"hello"}"#),
+    ])
+    .await;
+    let retry = RetryPolicy {
+      max_attempts: 2,
+      base_delay_ms: 1,
+      jitter: 0.0,
+    };
+
+    let result = transcribe(&[endpoint("groq", &flaky.url, "key")], audio(), None, &retry)
+      .await
+      .unwrap();
+    assert_eq!(result.text, "hello");
+    assert_eq!(flaky.requests(), 2);
+  }
 
   #[test]
   fn url_builder_appends_endpoint() {
@@ -0,0 +1,210 @@
+//! Offline transcription with a local Whisper GGML/GGUF model on the CPU.
+//!
+//! The whisper.cpp bindings are behind the `local-whisper` cargo feature so
+//! default builds do not need a C++ toolchain and CMake.
+
+use std::io::Cursor;
+
+use crate::domain::error::DiktError;
+use crate::domain::types::EncodedAudio;
+use crate::transcribe::TranscriptionResult;
+
+pub const LOCAL_PROVIDER: &str = "local";
+/// Whisper models are trained on 16 kHz mono audio.
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Every failure here is local (a missing or broken model, or audio the
+/// recorder should not have produced), so none is `Network` and none is worth
+/// retrying or queueing.
+pub async fn transcribe(
+  model_path: &str,
+  audio: EncodedAudio,
+  prompt: Option<&str>,
+) -> Result<TranscriptionResult, DiktError> {
+  validate_model_path(model_path)?;
+
+  let samples = decode_for_whisper(&audio)?;
+  let model_path = model_path.trim().to_string();
+  let prompt = prompt.map(str::to_string);
+  tokio::task::spawn_blocking(move || whisper::run(&model_path, &samples, prompt.as_deref()))
+    .await
+    .map_err(|e| DiktError::Other(format!("Local transcription task failed: {e}")))?
+    .map_err(DiktError::Other)
+}
+
+/// Check that a model file exists before the user relies on it.
+pub fn validate_model_path(model_path: &str) -> Result<(), String> {
+  let model_path = model_path.trim();
+  if model_path.is_empty() {
+    return Err("Missing local model path".to_string());
+  }
+  if !std::path::Path::new(model_path).is_file() {
+    return Err(format!("Local model not found at {model_path}"));
+  }
+  Ok(())
+}
+
+/// Decode a WAV recording into the normalized 16 kHz float samples Whisper expects.
+fn decode_for_whisper(audio: &EncodedAudio) -> Result<Vec<f32>, String> {
+  if audio.mime_type != "audio/wav" {
+    return Err(format!(
+      "Local transcription needs WAV audio, got {}",
+      audio.mime_type
+    ));
+  }
+
+  let reader = hound::WavReader::new(Cursor::new(&audio.bytes))
+    .map_err(|e| format!("Failed to read recording: {e}"))?;
+  let spec = reader.spec();
+  if spec.channels != 1 || spec.bits_per_sample != 16 {
+    return Err("Local transcription needs 16-bit mono audio".to_string());
+  }
+
+  let samples = reader
+    .into_samples::<i16>()
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to read recording: {e}"))?;
+  let samples = crate::audio::resample(&samples, spec.sample_rate, WHISPER_SAMPLE_RATE);
+
+  Ok(
+    samples
+      .iter()
+      .map(|&sample| sample as f32 / i16::MAX as f32)
+      .collect(),
+  )
+}
+
+#[cfg(feature = "local-whisper")]
+mod whisper {
+  use std::sync::{Arc, Mutex};
+
+  use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+  use crate::transcribe::{TranscriptionResult, TranscriptionSegment};
+
+  // Loading a model takes seconds, so keep the last one around.
+  static MODEL: Mutex<Option<(String, Arc<WhisperContext>)>> = Mutex::new(None);
+
+  fn load_model(model_path: &str) -> Result<Arc<WhisperContext>, String> {
+    let mut cached = MODEL.lock().map_err(|_| "Model cache lock poisoned".to_string())?;
+    if let Some((path, context)) = cached.as_ref() {
+      if path == model_path {
+        return Ok(Arc::clone(context));
+      }
+    }
+
+    super::validate_model_path(model_path)?;
+    let context = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+      .map_err(|e| format!("Failed to load local model: {e}"))?;
+    let context = Arc::new(context);
+    *cached = Some((model_path.to_string(), Arc::clone(&context)));
+    Ok(context)
+  }
+
+  pub fn run(
+    model_path: &str,
+    samples: &[f32],
+    prompt: Option<&str>,
+  ) -> Result<TranscriptionResult, String> {
+    let context = load_model(model_path)?;
+    let mut state = context
+      .create_state()
+      .map_err(|e| format!("Failed to create whisper state: {e}"))?;
+
+    let threads = std::thread::available_parallelism()
+      .map(|n| n.get())
+      .unwrap_or(4)
+      .min(8);
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_n_threads(threads as i32);
+    params.set_language(None);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_special(false);
+    params.set_print_timestamps(false);
+    if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+      params.set_initial_prompt(prompt);
+    }
+
+    state
+      .full(params, samples)
+      .map_err(|e| format!("Local transcription failed: {e}"))?;
+
+    let mut segments = Vec::new();
+    for segment in state.as_iter() {
+      let text = segment
+        .to_str_lossy()
+        .map_err(|e| format!("Failed to read transcript: {e}"))?;
+      // Timestamps are in centiseconds.
+      segments.push(TranscriptionSegment {
+        start: segment.start_timestamp() as f64 / 100.0,
+        end: segment.end_timestamp() as f64 / 100.0,
+        text: text.trim().to_string(),
+      });
+    }
+
+    let text = segments
+      .iter()
+      .map(|segment| segment.text.as_str())
+      .filter(|text| !text.is_empty())
+      .collect::<Vec<_>>()
+      .join(" ");
+    let language = whisper_rs::get_lang_str_full(state.full_lang_id_from_state()).map(str::to_string);
+
+    Ok(TranscriptionResult {
+      text,
+      duration_secs: Some(samples.len() as f64 / super::WHISPER_SAMPLE_RATE as f64),
+      language,
+      segments: Some(segments),
+      provider: Some(super::LOCAL_PROVIDER.to_string()),
+      upload_ms: None,
+    })
+  }
+}
+
+#[cfg(not(feature = "local-whisper"))]
+mod whisper {
+  use crate::transcribe::TranscriptionResult;
+
+  pub fn run(
+    _model_path: &str,
+    _samples: &[f32],
+    _prompt: Option<&str>,
+  ) -> Result<TranscriptionResult, String> {
+    Err("This build does not include local transcription (enable the local-whisper feature)".to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn wav(samples: &[i16], sample_rate: u32) -> EncodedAudio {
+    crate::encoding::encode(samples, sample_rate, crate::domain::types::AudioFormat::Wav).unwrap()
+  }
+
+  #[test]
+  fn wav_is_resampled_and_normalized_for_whisper() {
+    let samples = decode_for_whisper(&wav(&vec![i16::MAX; 48_000], 48_000)).unwrap();
+    assert_eq!(samples.len(), 16_000);
+    assert!(samples.iter().all(|&s| (s - 1.0).abs() < f32::EPSILON));
+  }
+
+  #[test]
+  fn compressed_audio_is_rejected() {
+    let mut audio = wav(&[0; 160], 16_000);
+    audio.mime_type = "audio/flac".to_string();
+    assert!(decode_for_whisper(&audio).unwrap_err().contains("needs WAV"));
+  }
+
+  #[test]
+  fn missing_model_is_reported() {
+    assert_eq!(
+      validate_model_path("  ").unwrap_err(),
+      "Missing local model path"
+    );
+    assert!(validate_model_path("/nonexistent/ggml-base.en.bin")
+      .unwrap_err()
+      .contains("not found"));
+  }
+}
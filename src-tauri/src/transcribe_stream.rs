@@ -0,0 +1,417 @@
+//! Live transcription over a WebSocket while recording is still in progress.
+//!
+//! Two wire protocols are supported:
+//!
+//! - `openai_realtime`: an OpenAI Realtime transcription session. Audio goes
+//!   out as base64 PCM16 in `input_audio_buffer.append` events and is
+//!   committed once recording stops; transcript deltas and the completed
+//!   transcript come back as `conversation.item.input_audio_transcription.*`.
+//! - `generic`: binary little-endian PCM16 frames followed by a
+//!   `{"type":"end"}` text message. The server answers with
+//!   `{"type":"partial","text":...}` messages carrying the transcript so far
+//!   and a single `{"type":"final","text":...}`, or `{"type":"error","message":...}`.
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue, Error, Message};
+
+use crate::domain::error::DiktError;
+use crate::domain::types::StreamingProtocol;
+use crate::transcribe::TranscriptionResult;
+
+// How long to wait for the final transcript once all audio has been sent.
+const FINAL_TRANSCRIPT_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct StreamRequest<'a> {
+  pub url: &'a str,
+  pub api_key: &'a str,
+  pub model: &'a str,
+  pub protocol: StreamingProtocol,
+  pub prompt: Option<&'a str>,
+}
+
+/// What the server told us, normalized across protocols.
+#[derive(Debug, PartialEq)]
+enum ServerEvent {
+  Partial(String),
+  Delta(String),
+  Final(String),
+  Error(String),
+  Ignored,
+}
+
+pub async fn transcribe_stream(
+  request: StreamRequest<'_>,
+  mut frames: UnboundedReceiver<Vec<i16>>,
+  on_partial: &(dyn Fn(String) + Send + Sync),
+) -> Result<TranscriptionResult, DiktError> {
+  if request.url.trim().is_empty() {
+    return Err("Missing streaming URL".into());
+  }
+
+  let mut client_request = request
+    .url
+    .trim()
+    .into_client_request()
+    .map_err(|e| DiktError::Other(format!("Invalid streaming URL: {e}")))?;
+  if !request.api_key.trim().is_empty() {
+    let bearer = HeaderValue::from_str(&format!("Bearer {}", request.api_key.trim()))
+      .map_err(|_| DiktError::from("API key contains invalid characters"))?;
+    client_request.headers_mut().insert("Authorization", bearer);
+  }
+  if request.protocol == StreamingProtocol::OpenaiRealtime {
+    client_request
+      .headers_mut()
+      .insert("OpenAI-Beta", HeaderValue::from_static("realtime=v1"));
+  }
+
+  let (socket, _) = tokio_tungstenite::connect_async(client_request)
+    .await
+    .map_err(connect_error)?;
+  let (mut outgoing, mut incoming) = socket.split();
+
+  if request.protocol == StreamingProtocol::OpenaiRealtime {
+    outgoing
+      .send(Message::text(
+        realtime_session_update(request.model, request.prompt).to_string(),
+      ))
+      .await
+      .map_err(|e| DiktError::Network(format!("Failed to start streaming session: {e}")))?;
+  }
+
+  let mut audio_done = false;
+  let mut partial = String::new();
+  let deadline = tokio::time::sleep(Duration::MAX);
+  tokio::pin!(deadline);
+
+  loop {
+    tokio::select! {
+      frame = frames.recv(), if !audio_done => {
+        let message = match frame {
+          Some(frame) => audio_message(request.protocol, &frame),
+          None => {
+            audio_done = true;
+            deadline
+              .as_mut()
+              .reset(tokio::time::Instant::now() + FINAL_TRANSCRIPT_TIMEOUT);
+            end_of_audio_message(request.protocol)
+          }
+        };
+        outgoing
+          .send(message)
+          .await
+          .map_err(|e| DiktError::Network(format!("Failed to send audio: {e}")))?;
+      }
+      message = incoming.next() => {
+        let message = match message {
+          Some(Ok(message)) => message,
+          Some(Err(e)) => {
+            return Err(DiktError::Network(format!("Streaming connection failed: {e}")))
+          }
+          None => return Err(closed_early()),
+        };
+        let text = match message {
+          Message::Text(text) => text,
+          Message::Close(_) => {
+            return Err(closed_early())
+          }
+          _ => continue,
+        };
+
+        match parse_server_event(request.protocol, &text) {
+          ServerEvent::Partial(text) => {
+            partial = text;
+            on_partial(partial.clone());
+          }
+          ServerEvent::Delta(delta) => {
+            partial.push_str(&delta);
+            on_partial(partial.clone());
+          }
+          ServerEvent::Final(text) => {
+            let _ = outgoing.send(Message::Close(None)).await;
+            return Ok(TranscriptionResult {
+              text: text.trim().to_string(),
+              duration_secs: None,
+              language: None,
+              segments: None,
+              provider: None,
+              upload_ms: None,
+            });
+          }
+          ServerEvent::Error(message) => {
+            return Err(DiktError::Other(format!("Streaming transcription failed: {message}")))
+          }
+          ServerEvent::Ignored => {}
+        }
+      }
+      _ = &mut deadline, if audio_done => {
+        return Err(DiktError::Network(
+          "Timed out waiting for the final streaming transcript".to_string(),
+        ));
+      }
+    }
+  }
+}
+
+/// A rejected handshake carries the server's status, so a bad key is `Auth`;
+/// failing to connect at all means the server is unreachable.
+fn connect_error(error: Error) -> DiktError {
+  match error {
+    Error::Http(response) => {
+      let body = response
+        .body()
+        .as_deref()
+        .map(|body| String::from_utf8_lossy(body).into_owned())
+        .unwrap_or_default();
+      DiktError::from_status(response.status().as_u16(), body)
+        .context("Streaming connection failed")
+    }
+    Error::Io(_) | Error::Tls(_) => {
+      DiktError::Network(format!("Streaming connection failed: {error}"))
+    }
+    _ => DiktError::Other(format!("Streaming connection failed: {error}")),
+  }
+}
+
+fn closed_early() -> DiktError {
+  DiktError::Network("Streaming connection closed before the final transcript".to_string())
+}
+
+fn realtime_session_update(model: &str, prompt: Option<&str>) -> Value {
+  let mut transcription = json!({ "model": model });
+  if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+    transcription["prompt"] = json!(prompt);
+  }
+
+  // No server-side turn detection: the whole dictation is one committed turn.
+  json!({
+    "type": "transcription_session.update",
+    "session": {
+      "input_audio_format": "pcm16",
+      "input_audio_transcription": transcription,
+      "turn_detection": null,
+    },
+  })
+}
+
+fn audio_message(protocol: StreamingProtocol, frame: &[i16]) -> Message {
+  let bytes: Vec<u8> = frame.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+  match protocol {
+    StreamingProtocol::OpenaiRealtime => Message::text(
+      json!({ "type": "input_audio_buffer.append", "audio": BASE64.encode(bytes) }).to_string(),
+    ),
+    StreamingProtocol::Generic => Message::binary(bytes),
+  }
+}
+
+fn end_of_audio_message(protocol: StreamingProtocol) -> Message {
+  match protocol {
+    StreamingProtocol::OpenaiRealtime => {
+      Message::text(json!({ "type": "input_audio_buffer.commit" }).to_string())
+    }
+    StreamingProtocol::Generic => Message::text(json!({ "type": "end" }).to_string()),
+  }
+}
+
+fn parse_server_event(protocol: StreamingProtocol, text: &str) -> ServerEvent {
+  let Ok(event) = serde_json::from_str::<Value>(text) else {
+    return ServerEvent::Ignored;
+  };
+  let event_type = event.get("type").and_then(Value::as_str).unwrap_or_default();
+  let field = |name: &str| {
+    event
+      .get(name)
+      .and_then(Value::as_str)
+      .unwrap_or_default()
+      .to_string()
+  };
+
+  match (protocol, event_type) {
+    (StreamingProtocol::OpenaiRealtime, "conversation.item.input_audio_transcription.delta") => {
+      ServerEvent::Delta(field("delta"))
+    }
+    (StreamingProtocol::OpenaiRealtime, "conversation.item.input_audio_transcription.completed") => {
+      ServerEvent::Final(field("transcript"))
+    }
+    (StreamingProtocol::OpenaiRealtime, "conversation.item.input_audio_transcription.failed")
+    | (StreamingProtocol::OpenaiRealtime, "error") => ServerEvent::Error(
+      event
+        .pointer("/error/message")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error")
+        .to_string(),
+    ),
+    (StreamingProtocol::Generic, "partial") => ServerEvent::Partial(field("text")),
+    (StreamingProtocol::Generic, "final") => ServerEvent::Final(field("text")),
+    (StreamingProtocol::Generic, "error") => ServerEvent::Error(field("message")),
+    _ => ServerEvent::Ignored,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use futures_util::{SinkExt, StreamExt};
+  use serde_json::{json, Value};
+  use tokio::net::TcpListener;
+  use tokio_tungstenite::tungstenite::Message;
+
+  use super::*;
+
+  type Socket = tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>;
+
+  /// Accept a single WebSocket client on a random local port and hand it to `serve`.
+  async fn stand_in_server<F, Fut>(serve: F) -> String
+  where
+    F: FnOnce(Socket) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send,
+  {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+      let (stream, _) = listener.accept().await.unwrap();
+      let socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+      serve(socket).await;
+    });
+    format!("ws://{address}")
+  }
+
+  fn request(url: &str, protocol: StreamingProtocol) -> StreamRequest<'_> {
+    StreamRequest {
+      url,
+      api_key: "test-key",
+      model: "gpt-4o-transcribe",
+      protocol,
+      prompt: Some("Vocabulary: Kubernetes"),
+    }
+  }
+
+  #[tokio::test]
+  async fn generic_protocol_streams_frames_and_reports_partials() {
+    let url = stand_in_server(|mut socket| async move {
+      let mut samples = 0;
+      while let Some(Ok(message)) = socket.next().await {
+        match message {
+          Message::Binary(bytes) => {
+            samples += bytes.len() / 2;
+            let partial = json!({ "type": "partial", "text": format!("{samples} samples") });
+            socket.send(Message::text(partial.to_string())).await.unwrap();
+          }
+          Message::Text(_) => {
+            let done = json!({ "type": "final", "text": format!(" heard {samples} samples ") });
+            socket.send(Message::text(done.to_string())).await.unwrap();
+          }
+          _ => break,
+        }
+      }
+    })
+    .await;
+
+    let (sender, frames) = tokio::sync::mpsc::unbounded_channel();
+    sender.send(vec![0i16; 160]).unwrap();
+    sender.send(vec![0i16; 160]).unwrap();
+    drop(sender);
+
+    let partials = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&partials);
+    let result = transcribe_stream(
+      request(&url, StreamingProtocol::Generic),
+      frames,
+      &move |text| seen.lock().unwrap().push(text),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(result.text, "heard 320 samples");
+    assert_eq!(
+      *partials.lock().unwrap(),
+      vec!["160 samples".to_string(), "320 samples".to_string()]
+    );
+  }
+
+  #[tokio::test]
+  async fn realtime_protocol_configures_session_and_commits_audio() {
+    let url = stand_in_server(|mut socket| async move {
+      let mut events = Vec::new();
+      while let Some(Ok(Message::Text(text))) = socket.next().await {
+        let event: Value = serde_json::from_str(&text).unwrap();
+        let event_type = event["type"].as_str().unwrap().to_string();
+        events.push(event);
+        if event_type == "input_audio_buffer.commit" {
+          break;
+        }
+      }
+
+      assert_eq!(events[0]["type"], "transcription_session.update");
+      assert_eq!(events[0]["session"]["input_audio_transcription"]["model"], "gpt-4o-transcribe");
+      assert_eq!(events[0]["session"]["input_audio_transcription"]["prompt"], "Vocabulary: Kubernetes");
+      assert_eq!(events[1]["type"], "input_audio_buffer.append");
+      assert_eq!(BASE64.decode(events[1]["audio"].as_str().unwrap()).unwrap(), vec![1, 0, 2, 0]);
+
+      for event in [
+        json!({ "type": "conversation.item.input_audio_transcription.delta", "delta": "Hello" }),
+        json!({ "type": "conversation.item.input_audio_transcription.delta", "delta": " world" }),
+        json!({ "type": "conversation.item.input_audio_transcription.completed", "transcript": "Hello world." }),
+      ] {
+        socket.send(Message::text(event.to_string())).await.unwrap();
+      }
+    })
+    .await;
+
+    let (sender, frames) = tokio::sync::mpsc::unbounded_channel();
+    sender.send(vec![1i16, 2]).unwrap();
+    drop(sender);
+
+    let partials = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&partials);
+    let result = transcribe_stream(
+      request(&url, StreamingProtocol::OpenaiRealtime),
+      frames,
+      &move |text| seen.lock().unwrap().push(text),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(result.text, "Hello world.");
+    assert_eq!(
+      *partials.lock().unwrap(),
+      vec!["Hello".to_string(), "Hello world".to_string()]
+    );
+  }
+
+  #[tokio::test]
+  async fn unreachable_server_is_a_network_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    let (_sender, frames) = tokio::sync::mpsc::unbounded_channel();
+    let error = transcribe_stream(request(&url, StreamingProtocol::Generic), frames, &|_| {})
+      .await
+      .unwrap_err();
+
+    assert!(matches!(error, DiktError::Network(_)), "{error:?}");
+  }
+
+  #[test]
+  fn server_errors_are_surfaced() {
+    let event = r#"{"type":"error","error":{"message":"invalid model"}}"#;
+    assert_eq!(
+      parse_server_event(StreamingProtocol::OpenaiRealtime, event),
+      ServerEvent::Error("invalid model".to_string())
+    );
+    assert_eq!(
+      parse_server_event(StreamingProtocol::Generic, r#"{"type":"error","message":"busy"}"#),
+      ServerEvent::Error("busy".to_string())
+    );
+    assert_eq!(
+      parse_server_event(StreamingProtocol::Generic, "not json"),
+      ServerEvent::Ignored
+    );
+  }
+}
@@ -6,6 +6,8 @@ use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 
+use crate::domain::types::{RejectedFormat, StageTimings, StepOutput};
+
 const MAX_HISTORY_ITEMS: usize = 10_000;
 static LAST_HISTORY_ERROR: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 
@@ -18,13 +20,51 @@ pub struct TranscriptionHistoryItem {
     pub duration_secs: Option<f64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub language: Option<String>,
+    /// Id of the mode that ran, which outlives renaming it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode_id: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub mode_name: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub original_text: Option<String>,
+    /// Output of each step when the mode ran a multi-step pipeline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_outputs: Option<Vec<StepOutput>>,
+    /// Mode output that was discarded for reading like a reply to the dictation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected_format: Option<RejectedFormat>,
+    /// Provider that transcribed the recording, which may be a fallback.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Transcribed later from the offline queue rather than pasted live.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deferred: bool,
+    /// How long each stage of the dictation took.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings_ms: Option<StageTimings>,
 }
 
-fn history_path() -> Result<PathBuf, String> {
+/// Latency of one stage over the entries that timed it.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct StageLatency {
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub samples: usize,
+}
+
+/// Per-stage latency over recent history; stages nobody timed are `None`.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct LatencySummary {
+    pub sessions: usize,
+    pub stop: Option<StageLatency>,
+    pub upload: Option<StageLatency>,
+    pub transcribe: Option<StageLatency>,
+    pub format: Option<StageLatency>,
+    pub paste: Option<StageLatency>,
+}
+
+/// Per-user directory holding the history file and other app data.
+pub fn data_dir() -> PathBuf {
     let base_dir = if let Ok(appdata) = std::env::var("APPDATA") {
         PathBuf::from(appdata)
     } else if let Ok(xdg) = std::env::var("XDG_CONFIG_HOME") {
@@ -35,7 +75,11 @@ fn history_path() -> Result<PathBuf, String> {
         std::env::temp_dir()
     };
 
-    Ok(base_dir.join("dikt").join("transcription_history.json"))
+    base_dir.join("dikt")
+}
+
+fn history_path() -> Result<PathBuf, String> {
+    Ok(data_dir().join("transcription_history.json"))
 }
 
 fn set_last_error(message: String) {
@@ -103,11 +147,18 @@ pub struct AppendItemParams {
     pub text: String,
     pub duration_secs: Option<f64>,
     pub language: Option<String>,
+    pub mode_id: Option<String>,
     pub mode_name: Option<String>,
     pub original_text: Option<String>,
+    pub step_outputs: Option<Vec<StepOutput>>,
+    pub rejected_format: Option<RejectedFormat>,
+    pub provider: Option<String>,
+    pub deferred: bool,
+    pub timings_ms: Option<StageTimings>,
 }
 
-pub fn append_item(params: AppendItemParams) -> Result<(), String> {
+/// Add an entry to the top of history and return its id.
+pub fn append_item(params: AppendItemParams) -> Result<String, String> {
     let path = history_path()?;
     let mut items = load_history_from(&path)?;
 
@@ -116,19 +167,41 @@ pub fn append_item(params: AppendItemParams) -> Result<(), String> {
         .map_err(|e| e.to_string())?
         .as_millis() as i64;
 
+    let id = uuid::Uuid::new_v4().to_string();
     let item = TranscriptionHistoryItem {
-        id: uuid::Uuid::new_v4().to_string(),
+        id: id.clone(),
         text: params.text,
         created_at_ms: now_ms,
         duration_secs: params.duration_secs,
         language: params.language,
+        mode_id: params.mode_id,
         mode_name: params.mode_name,
         original_text: params.original_text,
+        step_outputs: params.step_outputs,
+        rejected_format: params.rejected_format,
+        provider: params.provider,
+        deferred: params.deferred,
+        timings_ms: params.timings_ms,
     };
 
     items.insert(0, item);
     items.truncate(MAX_HISTORY_ITEMS);
 
+    save_history_to(&path, &items)?;
+    Ok(id)
+}
+
+/// Change the entry with `id`; does nothing when it has already been removed.
+pub fn update_item(
+    id: &str,
+    update: impl FnOnce(&mut TranscriptionHistoryItem),
+) -> Result<(), String> {
+    let path = history_path()?;
+    let mut items = load_history_from(&path)?;
+    let Some(item) = items.iter_mut().find(|item| item.id == id) else {
+        return Ok(());
+    };
+    update(item);
     save_history_to(&path, &items)
 }
 
@@ -144,6 +217,46 @@ pub fn clear_history() -> Result<(), String> {
     save_history_to(&path, &[])
 }
 
+/// p50/p95 per stage over the `recent` newest entries that have timings.
+pub fn latency_summary(recent: usize) -> Result<LatencySummary, String> {
+    Ok(summarize_latency(&load_history()?, recent))
+}
+
+fn summarize_latency(items: &[TranscriptionHistoryItem], recent: usize) -> LatencySummary {
+    let timings: Vec<StageTimings> = items
+        .iter()
+        .filter_map(|item| item.timings_ms)
+        .take(recent)
+        .collect();
+    let stage = |pick: fn(&StageTimings) -> Option<u64>| {
+        let mut samples: Vec<u64> = timings.iter().filter_map(pick).collect();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        Some(StageLatency {
+            p50_ms: percentile(&samples, 50),
+            p95_ms: percentile(&samples, 95),
+            samples: samples.len(),
+        })
+    };
+
+    LatencySummary {
+        sessions: timings.len(),
+        stop: stage(|timings| timings.stop),
+        upload: stage(|timings| timings.upload),
+        transcribe: stage(|timings| timings.transcribe),
+        format: stage(|timings| timings.format),
+        paste: stage(|timings| timings.paste),
+    }
+}
+
+/// Nearest-rank percentile of non-empty, sorted `samples`.
+fn percentile(samples: &[u64], percent: usize) -> u64 {
+    let rank = (samples.len() * percent).div_ceil(100).max(1);
+    samples[rank - 1]
+}
+
 pub fn record_runtime_error(message: String) {
     set_last_error(message);
 }
@@ -285,4 +398,28 @@ mod tests {
         assert_eq!(loaded[0].id, format!("item-{}", count - 1));
         let _ = fs::remove_dir_all(path.parent().unwrap().parent().unwrap());
     }
+
+    #[test]
+    fn latency_summary_uses_recent_timed_entries() {
+        let timed = |transcribe: u64, format: Option<u64>| TranscriptionHistoryItem {
+            timings_ms: Some(StageTimings {
+                stop: Some(10),
+                transcribe: Some(transcribe),
+                format,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut items: Vec<TranscriptionHistoryItem> =
+            (1..=20).map(|i| timed(i * 100, (i % 2 == 0).then_some(i))).collect();
+        items.insert(3, TranscriptionHistoryItem::default());
+        items.push(timed(99_999, None));
+
+        let summary = summarize_latency(&items, 20);
+        assert_eq!(summary.sessions, 20);
+        let transcribe = summary.transcribe.unwrap();
+        assert_eq!((transcribe.p50_ms, transcribe.p95_ms), (1000, 1900));
+        assert_eq!(summary.format.unwrap().samples, 10);
+        assert_eq!(summary.upload, None);
+    }
 }
@@ -1,4 +1,4 @@
-import { createSignal, createEffect, onCleanup, onMount } from 'solid-js';
+import { Show, createSignal, createEffect, onCleanup, onMount } from 'solid-js';
 import { register, unregister } from '@tauri-apps/plugin-global-shortcut';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
@@ -7,15 +7,19 @@ import { getCurrentWindow } from '@tauri-apps/api/window';
 import type { DictationUpdate, Settings, Status } from './types';
 import { DEFAULT_SETTINGS } from './constants';
 import { Pill, Tooltip } from './components';
+import { describeError } from './lib/errors';
 
 export default function App() {
   const [status, setStatus] = createSignal<Status>('idle');
   const [error, setError] = createSignal('');
+  const [partialText, setPartialText] = createSignal('');
+  const [canRetry, setCanRetry] = createSignal(false);
   const [settings, setSettings] = createSignal<Settings>(DEFAULT_SETTINGS);
   const [isHovered, setIsHovered] = createSignal(false);
   const [isSettingsOpen, setIsSettingsOpen] = createSignal(false);
 
   let isHolding = false;
+  let cancelRequested = false;
   let registeredHotkey = DEFAULT_SETTINGS.hotkey;
   const hotkeyRegistrationMessage = 'Could not register hotkey - it may be in use by another app. Change it in Settings.';
 
@@ -46,41 +50,31 @@ export default function App() {
       isHolding = true;
       setError('');
       setStatus('recording');
+      cancelRequested = false;
       try {
         await invoke('start_recording');
       } catch (err) {
         isHolding = false;
         setStatus('error');
-        setError(String(err));
+        setError(describeError(err));
       }
       return;
     }
 
     if (status() === 'recording') {
-      setStatus('transcribing');
-      try {
-        await invoke('stop_and_transcribe');
-        if (status() !== 'error') {
-          setStatus('done');
-          setTimeout(() => {
-            if (status() === 'done') setStatus('idle');
-          }, 1500);
-        }
-      } catch (err) {
-        setStatus('error');
-        setError(String(err));
-      }
+      await stopAndTranscribe();
       return;
     }
 
     if (status() === 'idle' || status() === 'done' || status() === 'error') {
       setError('');
       setStatus('recording');
+      cancelRequested = false;
       try {
         await invoke('start_recording');
       } catch (err) {
         setStatus('error');
-        setError(String(err));
+        setError(describeError(err));
       }
     }
   };
@@ -89,9 +83,21 @@ export default function App() {
     if (settings().hotkey_mode !== 'hold' || !isHolding) return;
 
     isHolding = false;
+    await stopAndTranscribe();
+  };
+
+  const stopAndTranscribe = () => runDictation('stop_and_transcribe');
+
+  const retryLastDictation = () => {
+    cancelRequested = false;
+    setError('');
+    return runDictation('retry_last_dictation');
+  };
+
+  const runDictation = async (command: 'stop_and_transcribe' | 'retry_last_dictation') => {
     setStatus('transcribing');
     try {
-      await invoke('stop_and_transcribe');
+      await invoke(command);
       if (status() !== 'error') {
         setStatus('done');
         setTimeout(() => {
@@ -99,8 +105,24 @@ export default function App() {
         }, 1500);
       }
     } catch (err) {
+      if (cancelRequested) {
+        setStatus('idle');
+        return;
+      }
       setStatus('error');
-      setError(String(err));
+      setError(describeError(err));
+    }
+  };
+
+  const cancelDictation = async () => {
+    cancelRequested = true;
+    isHolding = false;
+    try {
+      await invoke('cancel_dictation');
+      setStatus('idle');
+    } catch (err) {
+      setStatus('error');
+      setError(describeError(err));
     }
   };
 
@@ -116,7 +138,7 @@ export default function App() {
         setError('');
       }
     } catch (err) {
-      const settingsError = String(err);
+      const settingsError = describeError(err);
       const registered = await registerHotkey(DEFAULT_SETTINGS.hotkey);
       if (!registered) {
         setError(`${settingsError}\n${hotkeyRegistrationMessage}`);
@@ -158,9 +180,15 @@ export default function App() {
 
     const unlistenDictation = await listen<DictationUpdate>('dictation:update', (event) => {
         const payload = event.payload;
+        if (payload.partial_text !== undefined) {
+          setPartialText(payload.partial_text);
+        } else if (payload.state !== 'recording' && payload.state !== 'transcribing') {
+          setPartialText('');
+        }
+        setCanRetry(payload.can_retry === true);
         switch (payload.state) {
         case 'recording': {
-          setError('');
+          if (payload.partial_text === undefined) setError(payload.message ?? '');
           setStatus('recording');
           break;
         }
@@ -187,10 +215,16 @@ export default function App() {
           }, 1500);
           break;
         }
+        case 'cancelled': {
+          isHolding = false;
+          setError('');
+          setStatus('idle');
+          break;
+        }
         case 'error': {
           isHolding = false;
           setStatus('error');
-          setError(payload.message ?? 'Error');
+          setError(payload.error ? describeError(payload.error) : payload.message ?? 'Error');
           break;
         }
         case 'idle':
@@ -237,8 +271,10 @@ export default function App() {
     // Window: 360x100, pill centered at bottom with 8px padding.
     // Margin must be large enough for the 50ms cursor tracker to catch
     // an approaching cursor before it reaches the pill (~15px at normal speed).
-    const pillW = active ? 90 : 48;
-    const pillH = active ? 28 : 20;
+    // The error pill grows to fit its retry and settings buttons.
+    const expanded = active || status() === 'error';
+    const pillW = status() === 'error' && canRetry() ? 110 : expanded ? 90 : 48;
+    const pillH = expanded ? 28 : 20;
     const pillX = (360 - pillW) / 2;
     const pillY = 100 - 8 - pillH;
     const margin = 15;
@@ -276,6 +312,15 @@ export default function App() {
         onPointerMove={() => { if (!isHovered()) setIsHovered(true); }}
         onPointerLeave={() => setIsHovered(false)}
       >
+        <Show
+          when={
+            partialText() &&
+            (status() === 'recording' || status() === 'transcribing' || status() === 'formatting')
+          }
+        >
+          <div class="live-transcript">{partialText().slice(-50)}</div>
+        </Show>
+
         <Tooltip
           visible={isHovered() && !isActive() && !isSettingsOpen()}
           hotkey={settings().hotkey}
@@ -288,6 +333,8 @@ export default function App() {
           error={error}
           onMouseDown={startDrag}
           onSettingsClick={toggleSettingsWindow}
+          onCancel={cancelDictation}
+          onRetry={canRetry() ? retryLastDictation : undefined}
         />
       </div>
     </div>
@@ -1,19 +1,35 @@
-import { createSignal, createEffect, createMemo, onCleanup, onMount, Switch, Match } from 'solid-js';
+import { createSignal, createEffect, createMemo, onCleanup, onMount, untrack, Switch, Match } from 'solid-js';
 import { invoke } from '@tauri-apps/api/core';
 import { emit, listen } from '@tauri-apps/api/event';
 import { Toaster } from 'solid-sonner';
 
-import type { Settings, Tab, VocabularyEntry, TranscriptionHistoryItem, Mode } from './types';
+import type {
+  Settings,
+  Tab,
+  VocabularyEntry,
+  TranscriptionHistoryItem,
+  Mode,
+  ModeRule,
+  ModeStep,
+  ModeExample,
+  LatencySummary,
+  FormatterBackend,
+  GenerationParams
+} from './types';
 import {
   CHAT_MODELS,
   DEFAULT_SETTINGS,
+  FORMATTER_BACKENDS,
+  MODE_PROVIDERS,
   MAX_REPLACEMENTS_PER_ENTRY,
   MAX_VOCABULARY_ENTRIES
 } from './constants';
-import { DEFAULT_MODES } from './defaultModes';
+import { DEFAULT_GENERATION_PARAMS, DEFAULT_MODES } from './defaultModes';
 import { Layout, SettingsPage, RightPanel, HistoryPage, DictionaryPage, ModesPage } from './components/Settings';
 import type { HistoryStats } from './components/Settings';
 import { notifyError, notifyInfo, notifySuccess } from './lib/notify';
+import { describeError } from './lib/errors';
+import { endpointKey, modeEndpoint, modeKeyRef } from './lib/modeEndpoint';
 
 const createVocabularyId = (): string => {
   if (typeof crypto !== 'undefined' && 'randomUUID' in crypto) {
@@ -53,11 +69,13 @@ export default function SettingsApp() {
   const [saving, setSaving] = createSignal(false);
 
   const [history, setHistory] = createSignal<TranscriptionHistoryItem[]>([]);
+  const [latency, setLatency] = createSignal<LatencySummary | null>(null);
   const [historySearchQuery, setHistorySearchQuery] = createSignal('');
 
   const [modelsList, setModelsList] = createSignal<string[]>([]);
   const [modelsLoading, setModelsLoading] = createSignal(false);
   const [modelsError, setModelsError] = createSignal('');
+  const [endpointModels, setEndpointModels] = createSignal<Record<string, string[]>>({});
 
   const [isDark, setIsDark] = createSignal(true);
   const [audioLevel, setAudioLevel] = createSignal<{ rms_db: number; peak_db: number } | null>(null);
@@ -185,6 +203,7 @@ export default function SettingsApp() {
     try {
       const items = await invoke<TranscriptionHistoryItem[]>('get_transcription_history');
       setHistory(items);
+      setLatency(await invoke<LatencySummary>('get_latency_summary').catch(() => null));
     } catch (err) {
       notifyError(err, 'Failed to load history.');
     }
@@ -200,6 +219,29 @@ export default function SettingsApp() {
     }
   };
 
+  const addExampleFromHistory = async (id: string) => {
+    try {
+      const mode = await invoke<Mode>('add_mode_example_from_history', { id });
+      // Only the examples are taken over, so unsaved edits to the mode survive.
+      setSettings((current) => ({
+        ...current,
+        modes: current.modes.map((existing) =>
+          existing.id === mode.id ? { ...existing, examples: mode.examples } : existing
+        )
+      }));
+      notifySuccess(`Added as an example for ${mode.name}.`);
+    } catch (err) {
+      notifyError(err, 'Failed to add the example.');
+    }
+  };
+
+  const updateModeExamples = (id: string, examples: ModeExample[]) => {
+    setSettings((current) => ({
+      ...current,
+      modes: current.modes.map((mode) => (mode.id === id ? { ...mode, examples } : mode))
+    }));
+  };
+
   const clearHistory = async () => {
     try {
       await invoke('clear_transcription_history');
@@ -306,12 +348,14 @@ export default function SettingsApp() {
         setSettings((current) => ({
           ...current,
           modes: current.modes.map((mode) =>
-            availableModels.includes(mode.model) ? mode : { ...mode, model: defaultModel }
+            modeEndpoint(current, mode) !== null || availableModels.includes(mode.model)
+              ? mode
+              : { ...mode, model: defaultModel }
           )
         }));
       }
     } catch (err) {
-      setModelsError('Model fetch failed: ' + String(err));
+      setModelsError('Model fetch failed: ' + describeError(err));
       const fallback = CHAT_MODELS[provider] ?? [];
       setModelsList(fallback);
       if (reconcileModes && provider !== 'custom' && fallback.length > 0) {
@@ -319,7 +363,9 @@ export default function SettingsApp() {
         setSettings((current) => ({
           ...current,
           modes: current.modes.map((mode) =>
-            fallback.includes(mode.model) ? mode : { ...mode, model: defaultModel }
+            modeEndpoint(current, mode) !== null || fallback.includes(mode.model)
+              ? mode
+              : { ...mode, model: defaultModel }
           )
         }));
       }
@@ -328,6 +374,68 @@ export default function SettingsApp() {
     }
   };
 
+  // Models of modes with their own endpoint; the transcription provider's are in modelsList.
+  const modeModels = (mode: Mode): string[] => {
+    const endpoint = modeEndpoint(settings(), mode);
+    if (endpoint === null) return modelsList();
+    return endpointModels()[endpointKey(endpoint)] ?? FORMATTER_BACKENDS[mode.backend].models;
+  };
+
+  const fetchModeModels = async (mode: Mode) => {
+    const endpoint = modeEndpoint(settings(), mode);
+    if (endpoint === null) return;
+    try {
+      const result = await invoke<string[]>('fetch_provider_models', endpoint);
+      setEndpointModels((current) => ({ ...current, [endpointKey(endpoint)]: result }));
+    } catch (err) {
+      setModelsError(`Model fetch failed for ${endpoint.baseUrl}: ${describeError(err)}`);
+    }
+  };
+
+  const refreshModeModels = (id: string) => {
+    const mode = settings().modes.find((candidate) => candidate.id === id);
+    if (mode) void fetchModeModels(mode);
+  };
+
+  const updateModeEndpoint = (id: string, changes: Partial<Mode>) => {
+    setSettings((current) => ({
+      ...current,
+      modes: current.modes.map((mode) => {
+        if (mode.id !== id) return mode;
+        const next = { ...mode, ...changes };
+        return { ...next, model: modeModels(next)[0] ?? '' };
+      })
+    }));
+    refreshModeModels(id);
+  };
+
+  const setModeBackend = (id: string, backend: FormatterBackend) =>
+    updateModeEndpoint(id, { backend });
+
+  const setModeProvider = (id: string, provider: string | null) =>
+    updateModeEndpoint(id, {
+      provider,
+      base_url: provider === null ? null : MODE_PROVIDERS[provider]?.base_url || null
+    });
+
+  const modeApiKey = (mode: Mode): string | null => {
+    const keyRef = modeKeyRef(mode);
+    if (keyRef === null) return null;
+    return keyRef === settings().provider ? settings().api_key : settings().provider_api_keys[keyRef] ?? '';
+  };
+
+  // Stored under the mode's key reference, so modes naming the same provider share it.
+  const setModeApiKey = (id: string, apiKey: string) => {
+    const mode = settings().modes.find((candidate) => candidate.id === id);
+    const keyRef = mode ? modeKeyRef(mode) : null;
+    if (keyRef === null) return;
+    setSettings((current) => ({
+      ...current,
+      api_key: keyRef === current.provider ? apiKey : current.api_key,
+      provider_api_keys: { ...current.provider_api_keys, [keyRef]: apiKey }
+    }));
+  };
+
   const addMode = () => {
     const id = typeof crypto !== 'undefined' && 'randomUUID' in crypto
       ? crypto.randomUUID()
@@ -337,7 +445,21 @@ export default function SettingsApp() {
     const defaultModel = available.includes(preferred) ? preferred
       : available.length > 0 ? available[0]
       : preferred;
-    const newMode: Mode = { id, name: '', system_prompt: '', model: defaultModel };
+    const newMode: Mode = {
+      id,
+      name: '',
+      system_prompt: '',
+      model: defaultModel,
+      kind: 'format',
+      backend: 'openai_compatible',
+      provider: null,
+      base_url: null,
+      api_key_ref: null,
+      ...DEFAULT_GENERATION_PARAMS,
+      steps: [],
+      examples: [],
+      reply_check: false
+    };
     setSettings((current) => ({ ...current, modes: [...current.modes, newMode] }));
   };
 
@@ -345,18 +467,56 @@ export default function SettingsApp() {
     setSettings((current) => {
       const nextModes = current.modes.filter((mode) => mode.id !== id);
       const nextActiveModeId = current.active_mode_id === id ? null : current.active_mode_id;
-      return { ...current, modes: nextModes, active_mode_id: nextActiveModeId };
+      // Rules for the deleted mode would otherwise silently stop matching.
+      const nextRules = current.mode_rules.filter((rule) => rule.mode_id !== id);
+      return { ...current, modes: nextModes, active_mode_id: nextActiveModeId, mode_rules: nextRules };
     });
     void saveSettingsQuiet();
   };
 
-  const updateMode = (id: string, field: keyof Mode, value: string) => {
+  const addModeRule = () => {
+    const id = typeof crypto !== 'undefined' && 'randomUUID' in crypto
+      ? crypto.randomUUID()
+      : `rule-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
+    const rule: ModeRule = { id, app: '', title: '', mode_id: settings().active_mode_id };
+    setSettings((current) => ({ ...current, mode_rules: [...current.mode_rules, rule] }));
+  };
+
+  const updateModeRule = (id: string, patch: Partial<ModeRule>) => {
+    setSettings((current) => ({
+      ...current,
+      mode_rules: current.mode_rules.map((rule) => (rule.id === id ? { ...rule, ...patch } : rule))
+    }));
+  };
+
+  const deleteModeRule = (id: string) => {
+    setSettings((current) => ({
+      ...current,
+      mode_rules: current.mode_rules.filter((rule) => rule.id !== id)
+    }));
+  };
+
+  const updateMode = (id: string, field: keyof Mode, value: string | boolean) => {
     setSettings((current) => ({
       ...current,
       modes: current.modes.map((mode) => (mode.id === id ? { ...mode, [field]: value } : mode))
     }));
   };
 
+  const updateModeSteps = (id: string, steps: ModeStep[]) => {
+    setSettings((current) => ({
+      ...current,
+      modes: current.modes.map((mode) => (mode.id === id ? { ...mode, steps } : mode))
+    }));
+  };
+
+  const updateModeParams = (id: string, params: Partial<GenerationParams>) => {
+    setSettings((current) => ({
+      ...current,
+      modes: current.modes.map((mode) => (mode.id === id ? { ...mode, ...params } : mode))
+    }));
+  };
+
   const setActiveModeId = (id: string | null) => {
     setSettings((current) => ({ ...current, active_mode_id: id }));
     void saveSettingsQuiet();
@@ -466,6 +626,9 @@ export default function SettingsApp() {
     const shouldReconcileModes = isInitialLoad;
     if (shouldReconcileModes) isInitialLoad = false;
     void fetchModels(shouldReconcileModes);
+    if (shouldReconcileModes) {
+      untrack(() => settings().modes).forEach((mode) => void fetchModeModels(mode));
+    }
   });
 
   onMount(async () => {
@@ -492,6 +655,10 @@ export default function SettingsApp() {
       void loadHistory();
     });
 
+    const unlistenDeferred = await listen<{ text: string }>('dictation:deferred', () => {
+      notifySuccess('A queued dictation was transcribed and saved to history.');
+    });
+
     const unlistenAudioLevel = await listen<{ rms_db: number; peak_db: number }>('audio:level', (event) => {
       setAudioLevel(event.payload);
       clearTimeout(audioLevelTimer);
@@ -502,6 +669,7 @@ export default function SettingsApp() {
       void unlistenOpened();
       void unlistenHistoryUpdated();
       void unlistenHistoryError();
+      void unlistenDeferred();
       void unlistenAudioLevel();
       clearTimeout(audioLevelTimer);
     });
@@ -553,10 +721,12 @@ export default function SettingsApp() {
             totalCount={() => history().length}
             todayCount={() => historyStats().todayCount}
             totalAudioSecs={() => historyStats().totalAudioSecs}
+            latency={latency}
             searchQuery={historySearchQuery}
             onSearchQueryChange={(value) => setHistorySearchQuery(value)}
             onCopy={copyHistoryText}
             onDelete={deleteHistoryItem}
+            onAddExample={addExampleFromHistory}
             onClearAll={clearHistory}
           />
         </Match>
@@ -581,14 +751,26 @@ export default function SettingsApp() {
           <ModesPage
             modes={() => settings().modes}
             activeModeId={() => settings().active_mode_id}
-            modelsList={modelsList}
+            modeModels={modeModels}
+            modeApiKey={modeApiKey}
             modelsLoading={modelsLoading}
             modelsError={modelsError}
             onUpdateMode={updateMode}
+            onUpdateModeParams={updateModeParams}
+            onUpdateModeSteps={updateModeSteps}
+            onUpdateModeExamples={updateModeExamples}
+            onSetModeBackend={setModeBackend}
+            onSetModeProvider={setModeProvider}
+            onSetModeApiKey={setModeApiKey}
+            onRefreshModeModels={refreshModeModels}
             onSetActiveModeId={setActiveModeId}
             onAddMode={addMode}
             onDeleteMode={deleteMode}
             onResetModes={resetModes}
+            modeRules={() => settings().mode_rules}
+            onAddModeRule={addModeRule}
+            onUpdateModeRule={updateModeRule}
+            onDeleteModeRule={deleteModeRule}
             onSave={saveModes}
             saving={saving}
           />
@@ -0,0 +1,14 @@
+type CancelButtonProps = {
+  onClick: () => void;
+};
+
+export default function CancelButton(props: CancelButtonProps) {
+  return (
+    <button class="cancel-button" onClick={props.onClick} title="Cancel">
+      <svg width="10" height="10" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="3">
+        <line x1="18" y1="6" x2="6" y2="18" />
+        <line x1="6" y1="6" x2="18" y2="18" />
+      </svg>
+    </button>
+  );
+}
@@ -5,12 +5,16 @@ import IdleDots from './IdleDots';
 import SineWaves from './SineWaves';
 import LoadingDots from './LoadingDots';
 import GearButton from './GearButton';
+import CancelButton from './CancelButton';
+import RetryButton from './RetryButton';
 
 type PillProps = {
   status: Accessor<Status>;
   error: Accessor<string>;
   onMouseDown: (e: MouseEvent) => void;
   onSettingsClick: () => void;
+  onCancel: () => void;
+  onRetry?: () => void;
 };
 
 export function formatHotkey(hotkey: string): string {
@@ -45,6 +49,11 @@ export default function Pill(props: PillProps) {
         <LoadingDots />
       </Show>
 
+      {/* Pasting is already underway and cannot be undone */}
+      <Show when={props.status() === 'recording' || props.status() === 'transcribing' || props.status() === 'formatting'}>
+        <CancelButton onClick={props.onCancel} />
+      </Show>
+
       <Show when={props.status() === 'done'}>
         <svg class="check-icon" width="18" height="18" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2.5">
           <polyline points="20 6 9 17 4 12" />
@@ -53,6 +62,9 @@ export default function Pill(props: PillProps) {
 
       <Show when={props.status() === 'error'}>
         <span class="error-icon" title={props.error()}>!</span>
+        <Show when={props.onRetry}>
+          {(onRetry) => <RetryButton onClick={onRetry()} />}
+        </Show>
         <GearButton onClick={props.onSettingsClick} />
       </Show>
     </div>
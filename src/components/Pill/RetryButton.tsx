@@ -0,0 +1,14 @@
+type RetryButtonProps = {
+  onClick: () => void;
+};
+
+export default function RetryButton(props: RetryButtonProps) {
+  return (
+    <button class="gear-button" onClick={props.onClick} title="Retry without re-recording">
+      <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2">
+        <polyline points="1 4 1 10 7 10" />
+        <path d="M3.51 15a9 9 0 1 0 2.13-9.36L1 10" />
+      </svg>
+    </button>
+  );
+}
@@ -1,13 +1,18 @@
 import { For, Show, createEffect, createMemo, createSignal, on } from 'solid-js';
 import type { Accessor } from 'solid-js';
-import type { TranscriptionHistoryItem } from '../../types';
+import type { LatencySummary, Provider, TranscriptionHistoryItem } from '../../types';
+import { PROVIDERS } from '../../constants';
 import {
   formatDurationHuman,
   formatTotalAudio,
   formatItemTime,
   formatExactTime,
+  formatLatencySummary,
+  formatMs,
+  formatStageTimings,
   getLanguageCode,
   groupByDate,
+  totalStageMs,
 } from './historyUtils';
 import {
   Timer,
@@ -25,6 +30,11 @@ import {
   ChevronRight,
   Mail,
   Code,
+  CloudOff,
+  Server,
+  Gauge,
+  BookmarkPlus,
+  ShieldAlert,
 } from 'lucide-solid';
 import type { Component } from 'solid-js';
 
@@ -42,10 +52,12 @@ export type HistoryPageProps = {
   totalCount: Accessor<number>;
   todayCount: Accessor<number>;
   totalAudioSecs: Accessor<number>;
+  latency: Accessor<LatencySummary | null>;
   searchQuery: Accessor<string>;
   onSearchQueryChange: (value: string) => void;
   onCopy: (text: string) => void;
   onDelete: (id: string) => void;
+  onAddExample: (id: string) => void;
   onClearAll: () => void;
 };
 
@@ -53,6 +65,7 @@ function HistoryItem(props: {
   item: TranscriptionHistoryItem;
   onCopy: (text: string) => void;
   onDelete: (id: string) => void;
+  onAddExample: (id: string) => void;
 }) {
   const hasOriginalText = () =>
     props.item.original_text != null && props.item.original_text !== props.item.text;
@@ -86,6 +99,43 @@ function HistoryItem(props: {
             </div>
           </details>
         </Show>
+
+        <Show when={props.item.rejected_format}>
+          {(rejected) => (
+            <details class="group/details mt-3">
+              <summary class="cursor-pointer text-xs text-primary font-medium hover:brightness-110 transition-colors list-none flex items-center gap-1 w-fit select-none">
+                Show rejected output
+                <ChevronDown size={12} class="group-open/details:rotate-180 transition-transform" />
+              </summary>
+              <div class="mt-2 p-3 bg-black/20 rounded-md border-l-2 border-amber-500/40 text-gray-500 text-sm break-words">
+                {rejected().text}
+              </div>
+            </details>
+          )}
+        </Show>
+
+        <Show when={props.item.step_outputs?.length}>
+          <details class="group/details mt-3">
+            <summary class="cursor-pointer text-xs text-primary font-medium hover:brightness-110 transition-colors list-none flex items-center gap-1 w-fit select-none">
+              Show steps
+              <ChevronDown size={12} class="group-open/details:rotate-180 transition-transform" />
+            </summary>
+            <ol class="mt-2 flex flex-col gap-2">
+              <For each={props.item.step_outputs}>
+                {(output, index) => (
+                  <li class="p-3 bg-black/20 rounded-md border-l-2 border-white/10 text-sm break-words">
+                    <div class="text-[10px] uppercase font-bold tracking-wide text-gray-600 mb-1">
+                      {index() + 1}. {output.step}
+                    </div>
+                    <Show when={output.error} fallback={<span class="text-gray-500">{output.text}</span>}>
+                      <span class="text-red-400/80">{output.error}</span>
+                    </Show>
+                  </li>
+                )}
+              </For>
+            </ol>
+          </details>
+        </Show>
       </div>
 
       <div class="flex items-center gap-4 mt-1 text-xs text-gray-500 select-none">
@@ -112,6 +162,41 @@ function HistoryItem(props: {
           <span>{props.item.mode_name ?? 'Dictation'}</span>
         </div>
 
+        <Show when={props.item.provider}>
+          <div class="flex items-center gap-1" title="Provider that transcribed this recording">
+            <Server size={12} />
+            <span>{PROVIDERS[props.item.provider as Provider]?.label ?? props.item.provider}</span>
+          </div>
+        </Show>
+
+        <Show when={props.item.timings_ms}>
+          {(timings) => (
+            <div class="flex items-center gap-1" title={formatStageTimings(timings())}>
+              <Gauge size={12} />
+              <span>{formatMs(totalStageMs(timings()))}</span>
+            </div>
+          )}
+        </Show>
+
+        <Show when={props.item.rejected_format}>
+          {(rejected) => (
+            <div
+              class="flex items-center gap-1 text-amber-500/80"
+              title={`Mode output rejected: ${rejected().reason}. The transcript was pasted instead.`}
+            >
+              <ShieldAlert size={12} />
+              <span>Format rejected</span>
+            </div>
+          )}
+        </Show>
+
+        <Show when={props.item.deferred}>
+          <div class="flex items-center gap-1 text-amber-500/80" title="Recorded offline and transcribed later">
+            <CloudOff size={12} />
+            <span>Deferred</span>
+          </div>
+        </Show>
+
         <div class="flex items-center gap-1" title={formatExactTime(props.item.created_at_ms)}>
           <Clock size={12} />
           <span>{formatItemTime(props.item.created_at_ms)}</span>
@@ -127,6 +212,16 @@ function HistoryItem(props: {
         >
           <Copy size={16} />
         </button>
+        <Show when={props.item.mode_id != null && hasOriginalText()}>
+          <button
+            type="button"
+            onClick={() => props.onAddExample(props.item.id)}
+            class="p-1.5 rounded-lg text-gray-500 hover:text-white hover:bg-white/10 transition-colors"
+            title={`Use as an example for ${props.item.mode_name}`}
+          >
+            <BookmarkPlus size={16} />
+          </button>
+        </Show>
         <button
           type="button"
           onClick={() => props.onDelete(props.item.id)}
@@ -280,6 +375,14 @@ export default function HistoryPage(props: HistoryPageProps) {
                   <span class="font-semibold text-white">{formatTotalAudio(props.totalAudioSecs())}</span>
                 </div>
               </Show>
+              <Show when={props.latency()?.sessions ? props.latency() : null}>
+                {(latency) => (
+                  <div class="flex items-center gap-1.5 shrink-0" title={formatLatencySummary(latency())}>
+                    <Gauge size={14} class="text-primary" />
+                    <span class="hidden sm:inline">Latency</span>
+                  </div>
+                )}
+              </Show>
             </div>
           </div>
 
@@ -338,6 +441,7 @@ export default function HistoryPage(props: HistoryPageProps) {
                           item={item}
                           onCopy={props.onCopy}
                           onDelete={props.onDelete}
+                          onAddExample={props.onAddExample}
                         />
                       )}
                     </For>
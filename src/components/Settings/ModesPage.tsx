@@ -1,21 +1,35 @@
 import { Index, Show, createSignal, createMemo } from 'solid-js';
 import type { Accessor } from 'solid-js';
-import type { Mode } from '../../types';
+import type { FormatterBackend, GenerationParams, Mode, ModeExample, ModeKind, ModeRule, ModeStep } from '../../types';
 import { MODE_ICONS, MODE_COLORS, MODE_DESCRIPTIONS, DEFAULT_MODE_IDS } from '../../defaultModes';
-import { Plus, Pencil, Trash2, Save, Layers, RotateCcw } from 'lucide-solid';
+import { FORMATTER_BACKENDS, MODE_KINDS, MODE_PROVIDERS, PROMPT_VARIABLES } from '../../constants';
+import { Plus, Pencil, Trash2, Save, Layers, RotateCcw, ArrowUp, ArrowDown } from 'lucide-solid';
 import Select from './Select';
 
 export type ModesPageProps = {
   modes: Accessor<Mode[]>;
   activeModeId: Accessor<string | null>;
-  modelsList: Accessor<string[]>;
+  modeModels: (mode: Mode) => string[];
+  /** Key the mode authenticates with, or null when it uses the transcription key. */
+  modeApiKey: (mode: Mode) => string | null;
   modelsLoading: Accessor<boolean>;
   modelsError: Accessor<string>;
-  onUpdateMode: (id: string, field: keyof Mode, value: string) => void;
+  onUpdateMode: (id: string, field: keyof Mode, value: string | boolean) => void;
+  onUpdateModeParams: (id: string, params: Partial<GenerationParams>) => void;
+  onUpdateModeSteps: (id: string, steps: ModeStep[]) => void;
+  onUpdateModeExamples: (id: string, examples: ModeExample[]) => void;
+  onSetModeBackend: (id: string, backend: FormatterBackend) => void;
+  onSetModeProvider: (id: string, provider: string | null) => void;
+  onSetModeApiKey: (id: string, apiKey: string) => void;
+  onRefreshModeModels: (id: string) => void;
   onSetActiveModeId: (id: string | null) => void;
   onAddMode: () => void;
   onDeleteMode: (id: string) => void;
   onResetModes: () => void;
+  modeRules: Accessor<ModeRule[]>;
+  onAddModeRule: () => void;
+  onUpdateModeRule: (id: string, patch: Partial<ModeRule>) => void;
+  onDeleteModeRule: (id: string) => void;
   onSave: () => Promise<boolean>;
   saving: Accessor<boolean>;
 };
@@ -138,10 +152,210 @@ function CollapsedModeCard(props: {
   );
 }
 
+const backendOptions = (Object.keys(FORMATTER_BACKENDS) as FormatterBackend[]).map((backend) => ({
+  value: backend,
+  label: FORMATTER_BACKENDS[backend].label
+}));
+
+const kindOptions = (Object.keys(MODE_KINDS) as ModeKind[]).map((kind) => ({
+  value: kind,
+  label: MODE_KINDS[kind].label
+}));
+
+// Select values are strings, so '' stands for "no provider of its own".
+const providerOptions = [
+  { value: '', label: 'Backend default' },
+  ...Object.entries(MODE_PROVIDERS).map(([value, { label }]) => ({ value, label }))
+];
+
+// Empty or unparsable input means "use the provider default".
+const parseOptionalNumber = (value: string): number | null => {
+  if (value.trim() === '') return null;
+  const parsed = Number(value);
+  return Number.isFinite(parsed) ? parsed : null;
+};
+
+const inputClass =
+  'w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700';
+
+type LlmStep = Extract<ModeStep, { type: 'llm' }>;
+type RegexStep = Extract<ModeStep, { type: 'regex' }>;
+
+const STEP_TYPES: Record<ModeStep['type'], { label: string; create: () => ModeStep }> = {
+  llm: { label: 'LLM', create: () => ({ type: 'llm', system_prompt: '', model: null }) },
+  vocabulary: { label: 'Vocabulary replacements', create: () => ({ type: 'vocabulary' }) },
+  regex: { label: 'Regex rewrite', create: () => ({ type: 'regex', pattern: '', replacement: '' }) }
+};
+
+const stepTypeOptions = (Object.keys(STEP_TYPES) as ModeStep['type'][]).map((type) => ({
+  value: type,
+  label: STEP_TYPES[type].label
+}));
+
+function ModeStepsEditor(props: { steps: ModeStep[]; onChange: (steps: ModeStep[]) => void }) {
+  const replace = (index: number, step: ModeStep) =>
+    props.onChange(props.steps.map((current, i) => (i === index ? step : current)));
+  const move = (index: number, offset: number) => {
+    const target = index + offset;
+    if (target < 0 || target >= props.steps.length) return;
+    const steps = [...props.steps];
+    [steps[index], steps[target]] = [steps[target], steps[index]];
+    props.onChange(steps);
+  };
+
+  return (
+    <div class="flex flex-col gap-3">
+      <div class="flex justify-between items-end">
+        <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Steps</label>
+        <button
+          type="button"
+          onClick={() => props.onChange([...props.steps, STEP_TYPES.llm.create()])}
+          class="px-2.5 py-1 rounded text-[11px] font-medium text-zinc-400 hover:text-white hover:bg-white/5 transition-colors cursor-pointer flex items-center gap-1"
+        >
+          <Plus size={12} />
+          Add Step
+        </button>
+      </div>
+      <p class="text-xs text-zinc-500">
+        Without steps the mode makes one request with the system prompt above. With steps, each runs on the previous output and history keeps every step's result.
+      </p>
+      <Index each={props.steps}>
+        {(step, index) => (
+          <div class="flex flex-col gap-2 p-3 rounded-lg border border-white/10">
+            <div class="flex items-center gap-2">
+              <span class="text-[11px] text-zinc-500 w-5">{index + 1}.</span>
+              <Select
+                value={step().type}
+                options={stepTypeOptions}
+                onChange={(v) => {
+                  if (v !== step().type) replace(index, STEP_TYPES[v as ModeStep['type']].create());
+                }}
+                class="px-3 flex-1"
+              />
+              <button type="button" onClick={() => move(index, -1)} class="p-1.5 rounded text-zinc-500 hover:text-white hover:bg-white/5 cursor-pointer" title="Move up">
+                <ArrowUp size={14} />
+              </button>
+              <button type="button" onClick={() => move(index, 1)} class="p-1.5 rounded text-zinc-500 hover:text-white hover:bg-white/5 cursor-pointer" title="Move down">
+                <ArrowDown size={14} />
+              </button>
+              <button
+                type="button"
+                onClick={() => props.onChange(props.steps.filter((_, i) => i !== index))}
+                class="p-1.5 rounded text-zinc-500 hover:text-red-400 hover:bg-white/5 cursor-pointer"
+                title="Remove step"
+              >
+                <Trash2 size={14} />
+              </button>
+            </div>
+            {/* Show with a boolean keeps the inputs (and their focus) while typing. */}
+            <Show when={step().type === 'llm'}>
+              <textarea
+                value={(step() as LlmStep).system_prompt}
+                onInput={(e) => replace(index, { ...(step() as LlmStep), system_prompt: (e.target as HTMLTextAreaElement).value })}
+                placeholder="System prompt for this step..."
+                rows={3}
+                class={`${inputClass} font-mono resize-none`}
+              />
+              <input
+                type="text"
+                value={(step() as LlmStep).model ?? ''}
+                onInput={(e) => replace(index, { ...(step() as LlmStep), model: (e.target as HTMLInputElement).value || null })}
+                placeholder="Model (defaults to the mode's)"
+                class={inputClass}
+              />
+            </Show>
+            <Show when={step().type === 'regex'}>
+              <div class="grid grid-cols-1 md:grid-cols-2 gap-2">
+                <input
+                  type="text"
+                  value={(step() as RegexStep).pattern}
+                  onInput={(e) => replace(index, { ...(step() as RegexStep), pattern: (e.target as HTMLInputElement).value })}
+                  placeholder="Pattern, e.g. \bum\b"
+                  class={`${inputClass} font-mono`}
+                />
+                <input
+                  type="text"
+                  value={(step() as RegexStep).replacement}
+                  onInput={(e) => replace(index, { ...(step() as RegexStep), replacement: (e.target as HTMLInputElement).value })}
+                  placeholder="Replacement ($1 for groups)"
+                  class={`${inputClass} font-mono`}
+                />
+              </div>
+            </Show>
+            <Show when={step().type === 'vocabulary'}>
+              <p class="text-xs text-zinc-500">Applies the replacements from your dictionary.</p>
+            </Show>
+          </div>
+        )}
+      </Index>
+    </div>
+  );
+}
+
+function ModeExamplesEditor(props: { examples: ModeExample[]; onChange: (examples: ModeExample[]) => void }) {
+  const update = (index: number, patch: Partial<ModeExample>) =>
+    props.onChange(props.examples.map((example, i) => (i === index ? { ...example, ...patch } : example)));
+
+  return (
+    <div class="flex flex-col gap-3">
+      <div class="flex justify-between items-end">
+        <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Examples</label>
+        <button
+          type="button"
+          onClick={() => props.onChange([...props.examples, { input: '', output: '' }])}
+          class="px-2.5 py-1 rounded text-[11px] font-medium text-zinc-400 hover:text-white hover:bg-white/5 transition-colors cursor-pointer flex items-center gap-1"
+        >
+          <Plus size={12} />
+          Add Example
+        </button>
+      </div>
+      <p class="text-xs text-zinc-500">
+        Sent before your transcript to show the model what you expect. Add one from History with the bookmark button.
+      </p>
+      <Index each={props.examples}>
+        {(example, index) => (
+          <div class="grid grid-cols-1 md:grid-cols-[1fr_1fr_auto] gap-2 items-start">
+            <textarea
+              value={example().input}
+              onInput={(e) => update(index, { input: (e.target as HTMLTextAreaElement).value })}
+              placeholder="Transcript"
+              rows={3}
+              class={`${inputClass} resize-none`}
+            />
+            <textarea
+              value={example().output}
+              onInput={(e) => update(index, { output: (e.target as HTMLTextAreaElement).value })}
+              placeholder="Expected output"
+              rows={3}
+              class={`${inputClass} resize-none`}
+            />
+            <button
+              type="button"
+              onClick={() => props.onChange(props.examples.filter((_, i) => i !== index))}
+              class="p-1.5 rounded text-zinc-500 hover:text-red-400 hover:bg-white/5 cursor-pointer"
+              title="Remove example"
+            >
+              <Trash2 size={14} />
+            </button>
+          </div>
+        )}
+      </Index>
+    </div>
+  );
+}
+
 function ExpandedModeCard(props: {
   mode: Mode;
   modelOptions: { value: string; label: string }[];
-  onUpdateMode: (field: keyof Mode, value: string) => void;
+  apiKey: string | null;
+  onUpdateMode: (field: keyof Mode, value: string | boolean) => void;
+  onUpdateParams: (params: Partial<GenerationParams>) => void;
+  onUpdateSteps: (steps: ModeStep[]) => void;
+  onUpdateExamples: (examples: ModeExample[]) => void;
+  onSetBackend: (backend: FormatterBackend) => void;
+  onSetProvider: (provider: string | null) => void;
+  onSetApiKey: (apiKey: string) => void;
+  onRefreshModels: () => void;
   onCancel: () => void;
   onSave: () => void;
   onDelete: () => void;
@@ -149,6 +363,30 @@ function ExpandedModeCard(props: {
 }) {
   const icon = () => MODE_ICONS[props.mode.id] ?? 'tune';
   const colors = () => MODE_COLORS[props.mode.id];
+  const [schemaText, setSchemaText] = createSignal(
+    props.mode.json_schema ? JSON.stringify(props.mode.json_schema, null, 2) : ''
+  );
+  const [schemaError, setSchemaError] = createSignal('');
+
+  const applySchema = () => {
+    const text = schemaText().trim();
+    if (text === '') {
+      setSchemaError('');
+      props.onUpdateParams({ json_schema: null });
+      return;
+    }
+    try {
+      const schema: unknown = JSON.parse(text);
+      if (typeof schema !== 'object' || schema === null || Array.isArray(schema)) {
+        setSchemaError('The schema must be a JSON object.');
+        return;
+      }
+      setSchemaError('');
+      props.onUpdateParams({ json_schema: schema as Record<string, unknown> });
+    } catch (err) {
+      setSchemaError(`Invalid JSON: ${err instanceof Error ? err.message : String(err)}`);
+    }
+  };
 
   return (
     <div class="bg-surface-dark border border-zinc-700 rounded overflow-hidden shadow-2xl relative">
@@ -195,6 +433,86 @@ function ExpandedModeCard(props: {
               class="px-3"
             />
           </div>
+
+          {/* Type */}
+          <div class="flex flex-col gap-2">
+            <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Type</label>
+            <Select
+              value={props.mode.kind}
+              options={kindOptions}
+              onChange={(v) => props.onUpdateMode('kind', v)}
+              class="px-3"
+            />
+            <p class="text-xs text-zinc-500">{MODE_KINDS[props.mode.kind].description}</p>
+            <Show when={props.mode.kind === 'format'}>
+              <label class="flex items-center gap-2 text-sm text-gray-300 cursor-pointer select-none">
+                <input
+                  type="checkbox"
+                  class="accent-primary"
+                  checked={props.mode.reply_check}
+                  onChange={(e) => props.onUpdateMode('reply_check', e.currentTarget.checked)}
+                />
+                Reject chat replies
+              </label>
+              <p class="text-xs text-zinc-500">
+                Pastes the transcript when the output answers the dictation instead of formatting it. Only for cleanup
+                modes; ones that rewrite freely, like emails or translations, would always be rejected.
+              </p>
+            </Show>
+          </div>
+
+          {/* Backend */}
+          <div class="flex flex-col gap-2">
+            <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Backend</label>
+            <Select
+              value={props.mode.backend}
+              options={backendOptions}
+              onChange={(v) => props.onSetBackend(v as FormatterBackend)}
+              class="px-3"
+            />
+          </div>
+
+          {/* Provider */}
+          <div class="flex flex-col gap-2">
+            <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Provider</label>
+            <Select
+              value={props.mode.provider ?? ''}
+              options={providerOptions}
+              onChange={(v) => props.onSetProvider(v || null)}
+              class="px-3"
+            />
+          </div>
+
+          {/* Base URL */}
+          <div class="flex flex-col gap-2">
+            <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Base URL</label>
+            <input
+              type="text"
+              value={props.mode.base_url ?? ''}
+              onInput={(e) => props.onUpdateMode('base_url', (e.target as HTMLInputElement).value)}
+              onChange={() => props.onRefreshModels()}
+              placeholder={FORMATTER_BACKENDS[props.mode.backend].base_url || 'Transcription provider URL'}
+              class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+            />
+          </div>
+
+          {/* API key, shared by all modes naming the same provider */}
+          <div class="flex flex-col gap-2">
+            <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">API Key</label>
+            <Show
+              when={props.apiKey !== null}
+              fallback={<p class="text-xs text-zinc-500 py-2">Uses the transcription provider's key.</p>}
+            >
+              <input
+                type="password"
+                value={props.apiKey ?? ''}
+                onInput={(e) => props.onSetApiKey((e.target as HTMLInputElement).value)}
+                onChange={() => props.onRefreshModels()}
+                placeholder={props.mode.backend === 'ollama' ? 'Not needed for local Ollama' : 'sk-...'}
+                class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+              />
+            </Show>
+          </div>
         </div>
 
         {/* System Prompt */}
@@ -213,6 +531,89 @@ function ExpandedModeCard(props: {
           <p class="text-xs text-zinc-500 mt-1">
             This prompt instructs the AI on how to format and process your transcription.
           </p>
+          <p class="text-xs text-zinc-500">
+            Variables: {PROMPT_VARIABLES.map((name) => `{{${name}}}`).join(', ')}
+          </p>
+        </div>
+
+        <ModeStepsEditor steps={props.mode.steps} onChange={props.onUpdateSteps} />
+
+        <ModeExamplesEditor examples={props.mode.examples} onChange={props.onUpdateExamples} />
+
+        {/* Generation Parameters */}
+        <div class="flex flex-col gap-3">
+          <label class="text-xs font-medium text-gray-500 uppercase tracking-wide">Generation</label>
+          <div class="grid grid-cols-1 md:grid-cols-3 gap-5">
+            <div class="flex flex-col gap-2">
+              <span class="text-[11px] text-zinc-500">Temperature</span>
+              <input
+                type="number"
+                min="0"
+                max="2"
+                step="0.1"
+                value={props.mode.temperature ?? ''}
+                onInput={(e) =>
+                  props.onUpdateParams({ temperature: parseOptionalNumber((e.target as HTMLInputElement).value) })}
+                placeholder="Default"
+                class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+              />
+            </div>
+            <div class="flex flex-col gap-2">
+              <span class="text-[11px] text-zinc-500">Top P</span>
+              <input
+                type="number"
+                min="0"
+                max="1"
+                step="0.05"
+                value={props.mode.top_p ?? ''}
+                onInput={(e) =>
+                  props.onUpdateParams({ top_p: parseOptionalNumber((e.target as HTMLInputElement).value) })}
+                placeholder="Default"
+                class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+              />
+            </div>
+            <div class="flex flex-col gap-2">
+              <span class="text-[11px] text-zinc-500">Max Tokens</span>
+              <input
+                type="number"
+                min="1"
+                step="1"
+                value={props.mode.max_tokens ?? ''}
+                onInput={(e) => {
+                  const value = parseOptionalNumber((e.target as HTMLInputElement).value);
+                  props.onUpdateParams({ max_tokens: value === null ? null : Math.max(1, Math.round(value)) });
+                }}
+                placeholder="Default"
+                class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+              />
+            </div>
+          </div>
+          <div class="flex flex-col gap-2">
+            <span class="text-[11px] text-zinc-500">Stop Sequences (one per line)</span>
+            <textarea
+              value={props.mode.stop.join('\n')}
+              onChange={(e) =>
+                props.onUpdateParams({
+                  stop: (e.target as HTMLTextAreaElement).value.split('\n').filter((line) => line !== '')
+                })}
+              rows={2}
+              class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700 font-mono resize-none"
+            />
+          </div>
+          <div class="flex flex-col gap-2">
+            <span class="text-[11px] text-zinc-500">JSON Schema (optional, not supported by Anthropic)</span>
+            <textarea
+              value={schemaText()}
+              onInput={(e) => setSchemaText((e.target as HTMLTextAreaElement).value)}
+              onChange={applySchema}
+              placeholder={'{ "type": "object", "properties": { ... } }'}
+              rows={3}
+              class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700 font-mono resize-none"
+            />
+            <Show when={schemaError()}>
+              <p class="text-xs text-red-400">{schemaError()}</p>
+            </Show>
+          </div>
         </div>
 
         {/* Footer Actions */}
@@ -254,6 +655,90 @@ function ExpandedModeCard(props: {
   );
 }
 
+function ModeRulesSection(props: {
+  rules: ModeRule[];
+  modes: Mode[];
+  onAdd: () => void;
+  onUpdate: (id: string, patch: Partial<ModeRule>) => void;
+  onDelete: (id: string) => void;
+  onSave: () => void;
+  saving: boolean;
+}) {
+  // Select values are strings, so '' stands for "paste raw text".
+  const modeOptions = () => [
+    { value: '', label: 'Raw text' },
+    ...props.modes.map((mode) => ({ value: mode.id, label: mode.name || 'Untitled mode' }))
+  ];
+
+  return (
+    <section class="mt-8 flex flex-col gap-3">
+      <div class="flex items-end justify-between gap-4">
+        <div class="flex flex-col gap-1">
+          <h2 class="text-sm font-semibold text-white">App Rules</h2>
+          <p class="text-xs text-zinc-500">
+            Pick a mode from the window focused when recording starts (X11 only for now). The first matching rule wins; other apps use the active mode.
+          </p>
+        </div>
+        <button
+          type="button"
+          onClick={() => props.onAdd()}
+          class="px-3 py-2 rounded-lg text-xs font-medium text-zinc-300 hover:text-white hover:bg-white/5 transition-colors cursor-pointer flex items-center gap-1.5 shrink-0"
+        >
+          <Plus size={14} />
+          Add Rule
+        </button>
+      </div>
+      <Index each={props.rules}>
+        {(rule) => (
+          <div class="grid grid-cols-1 md:grid-cols-[1fr_1fr_1fr_auto] gap-3 items-center">
+            <input
+              type="text"
+              value={rule().app}
+              onInput={(e) => props.onUpdate(rule().id, { app: (e.target as HTMLInputElement).value })}
+              placeholder="App, e.g. terminal"
+              class={inputClass}
+            />
+            <input
+              type="text"
+              value={rule().title}
+              onInput={(e) => props.onUpdate(rule().id, { title: (e.target as HTMLInputElement).value })}
+              placeholder="Window title contains..."
+              class={inputClass}
+            />
+            <Select
+              value={rule().mode_id ?? ''}
+              options={modeOptions()}
+              onChange={(v) => props.onUpdate(rule().id, { mode_id: v || null })}
+              class="px-3"
+            />
+            <button
+              type="button"
+              onClick={() => props.onDelete(rule().id)}
+              class="p-2 rounded-lg text-zinc-500 hover:text-red-400 hover:bg-white/5 transition-colors cursor-pointer"
+              title="Delete rule"
+            >
+              <Trash2 size={14} />
+            </button>
+          </div>
+        )}
+      </Index>
+      <Show when={props.rules.length > 0}>
+        <div class="flex justify-end">
+          <button
+            type="button"
+            onClick={() => props.onSave()}
+            disabled={props.saving}
+            class="px-4 py-2 rounded-lg text-sm font-semibold text-black bg-primary hover:bg-primary-dark transition-colors cursor-pointer flex items-center gap-1.5 disabled:opacity-50"
+          >
+            <Save size={14} />
+            Save Rules
+          </button>
+        </div>
+      </Show>
+    </section>
+  );
+}
+
 export default function ModesPage(props: ModesPageProps) {
   const [editingModeId, setEditingModeId] = createSignal<string | null>(null);
 
@@ -263,9 +748,12 @@ export default function ModesPage(props: ModesPageProps) {
     return props.modes().find((m) => m.id === id) ?? null;
   });
 
-  const modelOptions = createMemo(() =>
-    props.modelsList().map((m) => ({ value: m, label: m }))
-  );
+  const modelOptions = (mode: Mode) => {
+    const models = props.modeModels(mode);
+    // Keep the saved model selectable even when the backend no longer lists it.
+    const withCurrent = mode.model && !models.includes(mode.model) ? [mode.model, ...models] : models;
+    return withCurrent.map((m) => ({ value: m, label: m }));
+  };
 
   const handleNewMode = () => {
     props.onAddMode();
@@ -393,8 +881,16 @@ export default function ModesPage(props: ModesPageProps) {
                 >
                   <ExpandedModeCard
                     mode={mode()}
-                    modelOptions={modelOptions()}
+                    modelOptions={modelOptions(mode())}
+                    apiKey={props.modeApiKey(mode())}
                     onUpdateMode={(field, value) => props.onUpdateMode(mode().id, field, value)}
+                    onUpdateParams={(params) => props.onUpdateModeParams(mode().id, params)}
+                    onUpdateSteps={(steps) => props.onUpdateModeSteps(mode().id, steps)}
+                    onUpdateExamples={(examples) => props.onUpdateModeExamples(mode().id, examples)}
+                    onSetBackend={(backend) => props.onSetModeBackend(mode().id, backend)}
+                    onSetProvider={(provider) => props.onSetModeProvider(mode().id, provider)}
+                    onSetApiKey={(apiKey) => props.onSetModeApiKey(mode().id, apiKey)}
+                    onRefreshModels={() => props.onRefreshModeModels(mode().id)}
                     onCancel={() => setEditingModeId(null)}
                     onSave={() => void handleSave()}
                     onDelete={() => handleDelete(mode().id)}
@@ -404,10 +900,20 @@ export default function ModesPage(props: ModesPageProps) {
               )}
             </Index>
 
+            <ModeRulesSection
+              rules={props.modeRules()}
+              modes={props.modes()}
+              onAdd={props.onAddModeRule}
+              onUpdate={props.onUpdateModeRule}
+              onDelete={props.onDeleteModeRule}
+              onSave={() => void props.onSave()}
+              saving={props.saving()}
+            />
+
             {/* Footer tip */}
             <div class="mt-8 text-center border-t border-white/5 pt-6">
               <p class="text-xs text-zinc-600">
-                Modes are applied automatically after transcription completes. Mode processing uses your configured API provider.
+                Modes are applied automatically after transcription completes. Unless a mode sets its own provider, it uses your transcription provider.
               </p>
             </div>
           </div>
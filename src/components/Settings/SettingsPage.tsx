@@ -1,7 +1,8 @@
-import { Show, For, createSignal } from 'solid-js';
+import { Show, For, Index, createSignal, onMount } from 'solid-js';
 import type { Accessor, Setter, JSX } from 'solid-js';
-import type { Settings, Provider } from '../../types';
+import type { Settings, Provider, AudioFormat, StreamingProtocol, TranscriptionEndpoint } from '../../types';
 import { CHAT_MODELS, PROVIDERS } from '../../constants';
+import { invoke } from '@tauri-apps/api/core';
 import { CircleCheck } from 'lucide-solid';
 import { notifyError, notifySuccess } from '../../lib/notify';
 import Select from './Select';
@@ -37,20 +38,67 @@ const PROVIDER_OPTIONS: ProviderOption[] = [
   { value: 'groq', label: 'Groq', iconComponent: GroqIcon },
   { value: 'openai', label: 'OpenAI', iconComponent: OpenAIIcon },
   { value: 'custom', label: 'Custom', icon: 'dns' },
+  { value: 'local', label: 'Local', icon: 'offline_bolt' },
 ];
 
+const FALLBACK_PROVIDER_OPTIONS = PROVIDER_OPTIONS
+  .filter((option) => option.value !== 'local')
+  .map((option) => ({ value: option.value, label: option.label }));
+
 const PROVIDER_KEY_URLS: Partial<Record<Provider, string>> = {
   groq: 'https://console.groq.com/keys',
   openai: 'https://platform.openai.com/api-keys',
 };
 
+const AUDIO_FORMAT_OPTIONS: { value: AudioFormat; label: string }[] = [
+  { value: 'wav', label: 'WAV' },
+  { value: 'flac', label: 'FLAC' },
+  { value: 'opus', label: 'Opus' },
+];
+
+const STREAMING_OPTIONS: { value: StreamingProtocol | 'off'; label: string }[] = [
+  { value: 'off', label: 'Off' },
+  { value: 'openai_realtime', label: 'Realtime' },
+  { value: 'generic', label: 'Generic' },
+];
+
+const STREAMING_URL_PLACEHOLDERS: Record<StreamingProtocol, string> = {
+  openai_realtime: 'wss://api.openai.com/v1/realtime?intent=transcription',
+  generic: 'ws://localhost:8765/transcribe',
+};
+
+const STREAMING_MODEL_PLACEHOLDERS: Record<StreamingProtocol, string> = {
+  openai_realtime: 'gpt-4o-transcribe',
+  generic: 'Model (optional)',
+};
+
+// Key entries used when streaming_api_key_ref is unset; generic servers get their own.
+const STREAMING_KEY_REFS: Record<StreamingProtocol, string> = {
+  openai_realtime: 'openai',
+  generic: 'streaming',
+};
+
 const formatHotkey = (raw: string): string =>
   raw.replace('CommandOrControl', 'Ctrl').replace(/\+/g, ' + ');
 
 export default function SettingsPage(props: SettingsPageProps) {
   const [showApiKey, setShowApiKey] = createSignal(false);
+  const [inputDevices, setInputDevices] = createSignal<string[]>([]);
 
-  const onField = (key: 'base_url' | 'model' | 'hotkey' | 'api_key') => (event: Event) => {
+  onMount(async () => {
+    try {
+      setInputDevices(await invoke<string[]>('list_input_devices'));
+    } catch (err) {
+      notifyError(err, 'Failed to list microphones.');
+    }
+  });
+
+  const inputDeviceOptions = () => [
+    { value: '', label: 'System default' },
+    ...inputDevices().map((name) => ({ value: name, label: name })),
+  ];
+
+  const onField = (key: 'base_url' | 'model' | 'hotkey' | 'api_key' | 'local_model_path') => (event: Event) => {
     const target = event.target as HTMLInputElement;
     props.setSettings((current) => ({ ...current, [key]: target.value }));
   };
@@ -61,6 +109,18 @@ export default function SettingsPage(props: SettingsPageProps) {
     props.setSettings((current) => {
       if (provider === current.provider) return current;
 
+      // Local transcription keeps the current API for mode formatting
+      if (provider === 'local') return { ...current, provider };
+      if (current.provider === 'local') {
+        return {
+          ...current,
+          provider,
+          base_url: config.base_url || current.base_url,
+          model: providerModelMemory[provider] ?? config.models[0] ?? current.model,
+          api_key: current.provider_api_keys[provider] ?? current.api_key,
+        };
+      }
+
       // Stash current model for the old provider
       providerModelMemory[current.provider] = current.model;
 
@@ -93,6 +153,73 @@ export default function SettingsPage(props: SettingsPageProps) {
     props.onSaveQuiet();
   };
 
+  const streamingChoice = () =>
+    props.settings().streaming_enabled ? props.settings().streaming_protocol : 'off';
+
+  const setStreamingChoice = (choice: StreamingProtocol | 'off') => {
+    props.setSettings((current) => ({
+      ...current,
+      streaming_enabled: choice !== 'off',
+      streaming_protocol: choice === 'off' ? current.streaming_protocol : choice,
+    }));
+    props.onSaveQuiet();
+  };
+
+  const streamingKeyRef = () =>
+    props.settings().streaming_api_key_ref ?? STREAMING_KEY_REFS[props.settings().streaming_protocol];
+
+  const streamingApiKey = () => {
+    const keyRef = streamingKeyRef();
+    return keyRef === props.settings().provider
+      ? props.settings().api_key
+      : props.settings().provider_api_keys[keyRef] ?? '';
+  };
+
+  // Stored under the key reference, so streaming and modes naming the same provider share it.
+  const setStreamingApiKey = (apiKey: string) => {
+    const keyRef = streamingKeyRef();
+    props.setSettings((current) => ({
+      ...current,
+      api_key: keyRef === current.provider ? apiKey : current.api_key,
+      provider_api_keys: { ...current.provider_api_keys, [keyRef]: apiKey },
+      streaming_api_key_ref: keyRef,
+    }));
+  };
+
+  const endpointFor = (provider: Provider): TranscriptionEndpoint => ({
+    provider,
+    base_url: PROVIDERS[provider].base_url,
+    model: PROVIDERS[provider].models[0] ?? '',
+  });
+
+  const addFallback = () => {
+    props.setSettings((current) => {
+      const used = [current.provider, ...current.transcription_fallbacks.map((f) => f.provider)];
+      const next = FALLBACK_PROVIDER_OPTIONS.find((o) => !used.includes(o.value))?.value ?? 'custom';
+      return { ...current, transcription_fallbacks: [...current.transcription_fallbacks, endpointFor(next)] };
+    });
+  };
+
+  const updateFallback = (index: number, patch: Partial<TranscriptionEndpoint>) => {
+    props.setSettings((current) => ({
+      ...current,
+      transcription_fallbacks: current.transcription_fallbacks.map((f, i) => (i === index ? { ...f, ...patch } : f)),
+    }));
+  };
+
+  const removeFallback = (index: number) => {
+    props.setSettings((current) => ({
+      ...current,
+      transcription_fallbacks: current.transcription_fallbacks.filter((_, i) => i !== index),
+    }));
+  };
+
+  /** Fallbacks use the key saved for their provider. */
+  const hasSavedKey = (provider: Provider) =>
+    provider === props.settings().provider
+      ? !!props.settings().api_key
+      : !!props.settings().provider_api_keys[provider];
+
   const providerConfig = () => PROVIDERS[props.settings().provider];
   const keyUrl = () => PROVIDER_KEY_URLS[props.settings().provider];
   const copyKeyUrl = async () => {
@@ -106,6 +233,23 @@ export default function SettingsPage(props: SettingsPageProps) {
     }
   };
 
+  const revealLog = async () => {
+    try {
+      await invoke('reveal_log_file');
+    } catch (err) {
+      notifyError(err, 'Failed to show the log file.');
+    }
+  };
+
+  const copyLog = async () => {
+    try {
+      await navigator.clipboard.writeText(await invoke<string>('export_log'));
+      notifySuccess('Copied log.');
+    } catch (err) {
+      notifyError(err, 'Failed to copy the log.');
+    }
+  };
+
   return (
     <>
       {/* Header */}
@@ -126,7 +270,7 @@ export default function SettingsPage(props: SettingsPageProps) {
           </div>
 
           {/* Provider Cards */}
-          <div class="grid grid-cols-3 gap-3 mb-6">
+          <div class="grid grid-cols-4 gap-3 mb-6">
             <For each={PROVIDER_OPTIONS}>
               {(option) => {
                 const isActive = () => props.settings().provider === option.value;
@@ -166,106 +310,198 @@ export default function SettingsPage(props: SettingsPageProps) {
 
           {/* Connection Details Card */}
           <div class="bg-surface-dark border border-white/10 rounded-xl p-6 space-y-5">
-            <div class="grid grid-cols-2 gap-5">
-              {/* Base URL */}
-              <div class="space-y-1.5">
-                <label class="text-xs text-gray-500 font-medium ml-1">BASE URL</label>
-                <div class="relative">
-                  <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px]">
-                    link
-                  </span>
-                  <input
-                    class="w-full bg-input-bg border border-white/15 rounded-lg py-2 pl-10 pr-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
-                    type="text"
-                    value={props.settings().base_url}
-                    onInput={onField('base_url')}
-                    placeholder="https://api.example.com/v1"
-                  />
+            <Show
+              when={props.settings().provider !== 'local'}
+              fallback={
+                <div class="space-y-1.5">
+                  <label class="text-xs text-gray-500 font-medium ml-1">MODEL FILE</label>
+                  <div class="relative">
+                    <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px]">
+                      folder_open
+                    </span>
+                    <input
+                      class="w-full bg-input-bg border border-white/15 rounded-lg py-2 pl-10 pr-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                      type="text"
+                      value={props.settings().local_model_path}
+                      onInput={onField('local_model_path')}
+                      placeholder="/path/to/ggml-base.en.bin"
+                    />
+                  </div>
+                  <p class="text-[11px] text-gray-600 pl-1 pt-1">
+                    Whisper GGML/GGUF model, run on the CPU. Audio never leaves this machine.
+                  </p>
                 </div>
-              </div>
-
-              {/* Model ID */}
-              <div class="space-y-1.5">
-                <label class="text-xs text-gray-500 font-medium ml-1">MODEL ID</label>
-                <div class="relative">
-                  <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px] z-10 pointer-events-none">
-                    view_in_ar
-                  </span>
-                  <Show
-                    when={props.settings().provider !== 'custom'}
-                    fallback={
+              }
+            >
+              <>
+                <div class="grid grid-cols-2 gap-5">
+                  {/* Base URL */}
+                  <div class="space-y-1.5">
+                    <label class="text-xs text-gray-500 font-medium ml-1">BASE URL</label>
+                    <div class="relative">
+                      <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px]">
+                        link
+                      </span>
                       <input
                         class="w-full bg-input-bg border border-white/15 rounded-lg py-2 pl-10 pr-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
                         type="text"
-                        value={props.settings().model}
-                        onInput={onField('model')}
-                        placeholder="model-name"
+                        value={props.settings().base_url}
+                        onInput={onField('base_url')}
+                        placeholder="https://api.example.com/v1"
                       />
+                    </div>
+                  </div>
+
+                  {/* Model ID */}
+                  <div class="space-y-1.5">
+                    <label class="text-xs text-gray-500 font-medium ml-1">MODEL ID</label>
+                    <div class="relative">
+                      <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px] z-10 pointer-events-none">
+                        view_in_ar
+                      </span>
+                      <Show
+                        when={props.settings().provider !== 'custom'}
+                        fallback={
+                          <input
+                            class="w-full bg-input-bg border border-white/15 rounded-lg py-2 pl-10 pr-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                            type="text"
+                            value={props.settings().model}
+                            onInput={onField('model')}
+                            placeholder="model-name"
+                          />
+                        }
+                      >
+                        <Select
+                          value={props.settings().model}
+                          options={providerConfig().models.map((m) => ({ value: m, label: m }))}
+                          onChange={(value) => props.setSettings((current) => ({ ...current, model: value }))}
+                          class="pl-10 pr-8 font-mono"
+                        />
+                        <span class="absolute right-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px] pointer-events-none z-10">
+                          arrow_drop_down
+                        </span>
+                      </Show>
+                    </div>
+                  </div>
+                </div>
+
+                {/* API Key */}
+                <div class="space-y-1.5">
+                  <div class="flex justify-between items-center px-1">
+                    <label class="text-xs text-gray-500 font-medium">API KEY</label>
+                    <Show when={keyUrl()}>
+                      <button
+                        type="button"
+                        onClick={() => void copyKeyUrl()}
+                        class="text-xs text-primary hover:underline cursor-pointer"
+                      >
+                        Get key
+                      </button>
+                    </Show>
+                  </div>
+                  <div class="relative">
+                    <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px]">
+                      key
+                    </span>
+                    <input
+                      class="w-full bg-input-bg border border-white/15 rounded-lg py-2 pl-10 pr-10 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                      type={showApiKey() ? 'text' : 'password'}
+                      value={props.settings().api_key}
+                      onInput={onField('api_key')}
+                      placeholder="sk-..."
+                    />
+                    <button
+                      type="button"
+                      onClick={() => setShowApiKey((v) => !v)}
+                      class="absolute right-2 top-1/2 -translate-y-1/2 flex items-center justify-center w-7 h-7 text-gray-500 hover:text-gray-300 rounded hover:bg-white/5 transition-colors"
+                    >
+                      <span class="material-symbols-outlined text-[18px] leading-none">
+                        {showApiKey() ? 'visibility' : 'visibility_off'}
+                      </span>
+                    </button>
+                  </div>
+                  <Show
+                    when={props.settings().api_key}
+                    fallback={
+                      <p class="text-[11px] text-amber-500/80 pl-1 pt-1">
+                        Missing API key — required for transcription.
+                      </p>
                     }
                   >
-                    <Select
-                      value={props.settings().model}
-                      options={providerConfig().models.map((m) => ({ value: m, label: m }))}
-                      onChange={(value) => props.setSettings((current) => ({ ...current, model: value }))}
-                      class="pl-10 pr-8 font-mono"
-                    />
-                    <span class="absolute right-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px] pointer-events-none z-10">
-                      arrow_drop_down
-                    </span>
+                    <p class="text-[11px] text-gray-600 pl-1 pt-1">
+                      Your key is stored locally and encrypted.
+                    </p>
                   </Show>
                 </div>
-              </div>
-            </div>
+              </>
+            </Show>
+          </div>
 
-            {/* API Key */}
-            <div class="space-y-1.5">
-              <div class="flex justify-between items-center px-1">
-                <label class="text-xs text-gray-500 font-medium">API KEY</label>
-                <Show when={keyUrl()}>
-                  <button
-                    type="button"
-                    onClick={() => void copyKeyUrl()}
-                    class="text-xs text-primary hover:underline cursor-pointer"
-                  >
-                    Get key
-                  </button>
-                </Show>
-              </div>
-              <div class="relative">
-                <span class="absolute left-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px]">
-                  key
-                </span>
-                <input
-                  class="w-full bg-input-bg border border-white/15 rounded-lg py-2 pl-10 pr-10 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
-                  type={showApiKey() ? 'text' : 'password'}
-                  value={props.settings().api_key}
-                  onInput={onField('api_key')}
-                  placeholder="sk-..."
-                />
+          {/* Fallback Providers */}
+          <Show when={props.settings().provider !== 'local'}>
+            <div class="mt-6">
+              <div class="flex items-center justify-between mb-3 px-1">
+                <div class="flex flex-col gap-1">
+                  <span class="text-sm font-medium text-gray-200">Fallback Providers</span>
+                  <span class="text-xs text-gray-500">Tried in order when the provider above is down or rate limited</span>
+                </div>
                 <button
                   type="button"
-                  onClick={() => setShowApiKey((v) => !v)}
-                  class="absolute right-2 top-1/2 -translate-y-1/2 flex items-center justify-center w-7 h-7 text-gray-500 hover:text-gray-300 rounded hover:bg-white/5 transition-colors"
+                  onClick={addFallback}
+                  class="text-xs text-primary hover:underline cursor-pointer"
                 >
-                  <span class="material-symbols-outlined text-[18px] leading-none">
-                    {showApiKey() ? 'visibility' : 'visibility_off'}
-                  </span>
+                  Add fallback
                 </button>
               </div>
-              <Show
-                when={props.settings().api_key}
-                fallback={
-                  <p class="text-[11px] text-amber-500/80 pl-1 pt-1">
-                    Missing API key — required for transcription.
-                  </p>
-                }
-              >
-                <p class="text-[11px] text-gray-600 pl-1 pt-1">
-                  Your key is stored locally and encrypted.
-                </p>
-              </Show>
+              <div class="space-y-3">
+                <Index each={props.settings().transcription_fallbacks}>
+                  {(fallback, index) => (
+                    <div class="bg-surface-dark border border-white/10 rounded-xl p-4 space-y-2">
+                      <div class="grid grid-cols-[8rem_1fr_1fr_auto] gap-3 items-center">
+                        <div class="relative">
+                          <Select
+                            value={fallback().provider}
+                            options={FALLBACK_PROVIDER_OPTIONS}
+                            onChange={(value) => updateFallback(index, endpointFor(value as Provider))}
+                            class="pr-8"
+                          />
+                          <span class="absolute right-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px] pointer-events-none z-10">
+                            arrow_drop_down
+                          </span>
+                        </div>
+                        <input
+                          class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                          type="text"
+                          value={fallback().base_url}
+                          onInput={(e) => updateFallback(index, { base_url: (e.target as HTMLInputElement).value })}
+                          placeholder="https://api.example.com/v1"
+                        />
+                        <input
+                          class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                          type="text"
+                          value={fallback().model}
+                          onInput={(e) => updateFallback(index, { model: (e.target as HTMLInputElement).value })}
+                          placeholder="model-name"
+                        />
+                        <button
+                          type="button"
+                          onClick={() => removeFallback(index)}
+                          class="flex items-center justify-center w-7 h-7 text-gray-500 hover:text-gray-300 rounded hover:bg-white/5 transition-colors cursor-pointer"
+                        >
+                          <span class="material-symbols-outlined text-[18px] leading-none">close</span>
+                        </button>
+                      </div>
+                      <Show when={!hasSavedKey(fallback().provider)}>
+                        <p class="text-[11px] text-amber-500/80 pl-1">
+                          No saved {PROVIDERS[fallback().provider].label} key — select {PROVIDERS[fallback().provider].label} above once to enter it.
+                        </p>
+                      </Show>
+                    </div>
+                  )}
+                </Index>
+              </div>
             </div>
-          </div>
+          </Show>
 
           {/* Provider Actions */}
           <div class="mt-5">
@@ -351,6 +587,111 @@ export default function SettingsPage(props: SettingsPageProps) {
               </div>
             </div>
 
+            {/* Microphone */}
+            <div class="p-4 flex items-center justify-between group hover:bg-white/[0.02] transition-colors">
+              <div class="flex flex-col gap-1">
+                <span class="text-sm font-medium text-gray-200">Microphone</span>
+                <span class="text-xs text-gray-500">Input device used for dictation</span>
+              </div>
+              <div class="relative w-56">
+                <Select
+                  value={props.settings().input_device ?? ''}
+                  options={inputDeviceOptions()}
+                  onChange={(value) => setBehavior('input_device', value || null)}
+                  class="pr-8"
+                />
+                <span class="absolute right-3 top-1/2 -translate-y-1/2 text-gray-600 material-symbols-outlined text-[18px] pointer-events-none z-10">
+                  arrow_drop_down
+                </span>
+              </div>
+            </div>
+
+            {/* Upload Format */}
+            <div class="p-4 flex items-center justify-between group hover:bg-white/[0.02] transition-colors">
+              <div class="flex flex-col gap-1">
+                <span class="text-sm font-medium text-gray-200">Upload Format</span>
+                <span class="text-xs text-gray-500">Compressed formats upload faster on slow networks</span>
+              </div>
+              <div class="flex bg-input-bg p-1 rounded-lg border border-white/15">
+                <For each={AUDIO_FORMAT_OPTIONS}>
+                  {(option) => (
+                    <button
+                      type="button"
+                      onClick={() => setBehavior('audio_format', option.value)}
+                      class={`px-3 py-1.5 rounded text-xs font-medium transition-colors ${
+                        props.settings().audio_format === option.value
+                          ? 'bg-white/10 text-white shadow-sm'
+                          : 'text-gray-500 hover:text-gray-300'
+                      }`}
+                    >
+                      {option.label}
+                    </button>
+                  )}
+                </For>
+              </div>
+            </div>
+
+            {/* Live Transcription */}
+            <div class="p-4 flex flex-col gap-3 group hover:bg-white/[0.02] transition-colors">
+              <div class="flex items-center justify-between">
+                <div class="flex flex-col gap-1">
+                  <span class="text-sm font-medium text-gray-200">Live Transcription</span>
+                  <span class="text-xs text-gray-500">Stream audio over WebSocket and show text while you speak</span>
+                </div>
+                <div class="flex bg-input-bg p-1 rounded-lg border border-white/15">
+                  <For each={STREAMING_OPTIONS}>
+                    {(option) => (
+                      <button
+                        type="button"
+                        onClick={() => setStreamingChoice(option.value)}
+                        class={`px-3 py-1.5 rounded text-xs font-medium transition-colors ${
+                          streamingChoice() === option.value
+                            ? 'bg-white/10 text-white shadow-sm'
+                            : 'text-gray-500 hover:text-gray-300'
+                        }`}
+                      >
+                        {option.label}
+                      </button>
+                    )}
+                  </For>
+                </div>
+              </div>
+              <Show when={props.settings().streaming_enabled}>
+                <input
+                  class="w-full bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                  type="text"
+                  value={props.settings().streaming_url}
+                  onInput={(e) => {
+                    const value = (e.target as HTMLInputElement).value;
+                    props.setSettings((current) => ({ ...current, streaming_url: value }));
+                  }}
+                  onBlur={() => props.onSaveQuiet()}
+                  placeholder={STREAMING_URL_PLACEHOLDERS[props.settings().streaming_protocol]}
+                />
+                <div class="flex gap-2">
+                  <input
+                    class="flex-1 bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                    type="text"
+                    value={props.settings().streaming_model}
+                    onInput={(e) => {
+                      const value = (e.target as HTMLInputElement).value;
+                      props.setSettings((current) => ({ ...current, streaming_model: value }));
+                    }}
+                    onBlur={() => props.onSaveQuiet()}
+                    placeholder={STREAMING_MODEL_PLACEHOLDERS[props.settings().streaming_protocol]}
+                  />
+                  <input
+                    class="flex-1 bg-input-bg border border-white/15 rounded-lg py-2 px-3 text-sm font-mono text-gray-300 focus:outline-none focus:border-primary focus:ring-1 focus:ring-primary transition-colors placeholder-gray-700"
+                    type="password"
+                    value={streamingApiKey()}
+                    onInput={(e) => setStreamingApiKey((e.target as HTMLInputElement).value)}
+                    onBlur={() => props.onSaveQuiet()}
+                    placeholder={`API key (${streamingKeyRef()})`}
+                  />
+                </div>
+              </Show>
+            </div>
+
             {/* Output Mode */}
             <div class="p-4 flex items-center justify-between group hover:bg-white/[0.02] transition-colors rounded-b-lg">
               <div class="flex flex-col gap-1">
@@ -385,6 +726,39 @@ export default function SettingsPage(props: SettingsPageProps) {
           </div>
         </section>
 
+        <hr class="border-white/5 my-2" />
+
+        {/* Diagnostics */}
+        <section>
+          <h3 class="text-sm font-semibold text-gray-300 uppercase tracking-wider mb-4">
+            Diagnostics
+          </h3>
+          <div class="bg-surface-dark border border-white/10 rounded-xl p-1">
+            <div class="p-4 flex items-center justify-between group hover:bg-white/[0.02] transition-colors rounded-lg">
+              <div class="flex flex-col gap-1">
+                <span class="text-sm font-medium text-gray-200">Log File</span>
+                <span class="text-xs text-gray-500">Attach it to bug reports; API keys are redacted</span>
+              </div>
+              <div class="flex gap-2">
+                <button
+                  type="button"
+                  onClick={revealLog}
+                  class="px-3 py-1.5 rounded-lg text-xs font-medium text-gray-300 border border-white/15 hover:bg-white/5 transition-colors cursor-pointer"
+                >
+                  Show File
+                </button>
+                <button
+                  type="button"
+                  onClick={copyLog}
+                  class="px-3 py-1.5 rounded-lg text-xs font-medium text-gray-300 border border-white/15 hover:bg-white/5 transition-colors cursor-pointer"
+                >
+                  Copy Log
+                </button>
+              </div>
+            </div>
+          </div>
+        </section>
+
         {/* Spacer */}
         <div class="h-10" />
       </div>
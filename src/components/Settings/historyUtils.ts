@@ -1,4 +1,4 @@
-import type { TranscriptionHistoryItem } from '../../types';
+import type { LatencySummary, Stage, StageTimings, TranscriptionHistoryItem } from '../../types';
 
 export function formatDurationHuman(seconds: number): string {
   if (!Number.isFinite(seconds) || seconds < 0) return '0s';
@@ -133,3 +133,38 @@ export function groupByDate(items: TranscriptionHistoryItem[]): DateGroup[] {
 
   return order.map((key) => buckets[key]);
 }
+
+const STAGES: { key: Stage; label: string }[] = [
+  { key: 'stop', label: 'Stop' },
+  { key: 'upload', label: 'Upload' },
+  { key: 'transcribe', label: 'Transcribe' },
+  { key: 'format', label: 'Format' },
+  { key: 'paste', label: 'Paste' },
+];
+
+export function formatMs(ms: number): string {
+  if (!Number.isFinite(ms) || ms < 0) return '0ms';
+  if (ms < 1000) return `${Math.round(ms)}ms`;
+  return `${(ms / 1000).toFixed(1)}s`;
+}
+
+export function totalStageMs(timings: StageTimings): number {
+  return STAGES.reduce((total, stage) => total + (timings[stage.key] ?? 0), 0);
+}
+
+/** One line per stage that ran, e.g. "Upload: 420ms". */
+export function formatStageTimings(timings: StageTimings): string {
+  return STAGES.filter((stage) => timings[stage.key] != null)
+    .map((stage) => `${stage.label}: ${formatMs(timings[stage.key]!)}`)
+    .join('\n');
+}
+
+/** One line per timed stage with its p50 and p95 over recent dictations. */
+export function formatLatencySummary(summary: LatencySummary): string {
+  const lines = STAGES.flatMap((stage) => {
+    const latency = summary[stage.key];
+    if (!latency) return [];
+    return [`${stage.label}: p50 ${formatMs(latency.p50_ms)}, p95 ${formatMs(latency.p95_ms)}`];
+  });
+  return [`Last ${summary.sessions} timed dictations`, ...lines].join('\n');
+}
@@ -1,10 +1,11 @@
-import type { Provider, Settings } from './types';
+import type { FormatterBackend, ModeKind, Provider, Settings } from './types';
 import { DEFAULT_MODES } from './defaultModes';
 
 export const CHAT_MODELS: Record<Provider, string[]> = {
   groq: ['llama-3.3-70b-versatile', 'llama-3.1-8b-instant', 'qwen/qwen3-32b'],
   openai: ['gpt-4o-mini', 'gpt-4o', 'gpt-4.1-mini', 'gpt-4.1-nano'],
-  custom: []
+  custom: [],
+  local: []
 };
 
 export const PROVIDERS: Record<Provider, { label: string; base_url: string; models: string[] }> = {
@@ -22,9 +23,65 @@ export const PROVIDERS: Record<Provider, { label: string; base_url: string; mode
     label: 'Custom',
     base_url: '',
     models: []
+  },
+  local: {
+    label: 'Local',
+    base_url: '',
+    models: []
   }
 };
 
+// Chat APIs a mode can format with. OpenAI-compatible modes use the transcription
+// provider's base URL, key and model list.
+export const FORMATTER_BACKENDS: Record<FormatterBackend, { label: string; base_url: string; models: string[] }> = {
+  openai_compatible: {
+    label: 'Transcription provider',
+    base_url: '',
+    models: []
+  },
+  anthropic_messages: {
+    label: 'Anthropic',
+    base_url: 'https://api.anthropic.com/v1',
+    models: ['claude-haiku-4-5', 'claude-sonnet-4-5']
+  },
+  ollama: {
+    label: 'Ollama',
+    base_url: 'http://localhost:11434',
+    models: ['llama3.2']
+  }
+};
+
+// Entry in provider_api_keys holding the Anthropic key used by anthropic_messages modes.
+export const ANTHROPIC_KEY_PROVIDER = 'anthropic';
+
+export const MODE_KINDS: Record<ModeKind, { label: string; description: string }> = {
+  format: { label: 'Format dictation', description: 'Rewrites what you dictate.' },
+  transform: {
+    label: 'Transform selection',
+    description: 'Your dictation is an instruction applied to the selected text (or the clipboard); the result replaces the selection.'
+  }
+};
+
+// Placeholders a mode's system prompt may use; mirrors `domain::template::VARIABLES`.
+export const PROMPT_VARIABLES = [
+  'date',
+  'time',
+  'language',
+  'clipboard',
+  'selected_text',
+  'active_app',
+  'user_name',
+  'vocabulary'
+] as const;
+
+// Providers a mode can name for its chat endpoint; the base URL prefills the mode's own.
+export const MODE_PROVIDERS: Record<string, { label: string; base_url: string }> = {
+  groq: { label: 'Groq', base_url: PROVIDERS.groq.base_url },
+  openai: { label: 'OpenAI', base_url: PROVIDERS.openai.base_url },
+  [ANTHROPIC_KEY_PROVIDER]: { label: 'Anthropic', base_url: FORMATTER_BACKENDS.anthropic_messages.base_url },
+  custom: { label: 'Custom', base_url: '' }
+};
+
 export const DEFAULT_SETTINGS: Settings = {
   provider: 'groq',
   base_url: PROVIDERS.groq.base_url,
@@ -36,7 +93,19 @@ export const DEFAULT_SETTINGS: Settings = {
   provider_api_keys: {},
   vocabulary: [],
   active_mode_id: null,
-  modes: DEFAULT_MODES
+  modes: DEFAULT_MODES,
+  input_device: null,
+  target_sample_rate: 16000,
+  audio_format: 'wav',
+  streaming_enabled: false,
+  streaming_url: '',
+  streaming_protocol: 'openai_realtime',
+  streaming_model: '',
+  streaming_api_key_ref: null,
+  local_model_path: '',
+  transcription_fallbacks: [],
+  retry_policy: { max_attempts: 3, base_delay_ms: 500, jitter: 0.2 },
+  mode_rules: []
 };
 
 export const MAX_VOCABULARY_ENTRIES = 100;
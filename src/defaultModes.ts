@@ -1,4 +1,12 @@
-import type { Mode } from './types';
+import type { GenerationParams, Mode } from './types';
+
+export const DEFAULT_GENERATION_PARAMS: GenerationParams = {
+  temperature: null,
+  max_tokens: null,
+  top_p: null,
+  stop: [],
+  json_schema: null,
+};
 
 // IDs of built-in default modes (not deletable)
 export const DEFAULT_MODE_IDS = new Set(['clean-draft', 'email-composer', 'developer-log']);
@@ -57,6 +65,15 @@ Rules:
 
 Output only the cleaned text. No commentary, no explanations, no preamble.`,
     model: 'llama-3.3-70b-versatile',
+    kind: 'format',
+    backend: 'openai_compatible',
+    provider: null,
+    base_url: null,
+    api_key_ref: null,
+    ...DEFAULT_GENERATION_PARAMS,
+    steps: [],
+    examples: [],
+    reply_check: true,
   },
   {
     id: 'email-composer',
@@ -81,6 +98,15 @@ Rules:
 
 Output only the email. No commentary or meta-text outside the email itself.`,
     model: 'llama-3.3-70b-versatile',
+    kind: 'format',
+    backend: 'openai_compatible',
+    provider: null,
+    base_url: null,
+    api_key_ref: null,
+    ...DEFAULT_GENERATION_PARAMS,
+    steps: [],
+    examples: [],
+    reply_check: false,
   },
   {
     id: 'developer-log',
@@ -101,5 +127,14 @@ Rules:
 - Output plain text only. No markdown, no headers, no bullet points unless the speaker explicitly dictated them.
 - Output only the cleaned text. No commentary, no preamble.`,
     model: 'llama-3.3-70b-versatile',
+    kind: 'format',
+    backend: 'openai_compatible',
+    provider: null,
+    base_url: null,
+    api_key_ref: null,
+    ...DEFAULT_GENERATION_PARAMS,
+    steps: [],
+    examples: [],
+    reply_check: true,
   },
 ];
@@ -0,0 +1,37 @@
+import type { DiktError } from '../types';
+
+const isDiktError = (error: unknown): error is DiktError =>
+  typeof error === 'object' && error !== null && 'code' in error && 'message' in error;
+
+const hintFor = (error: DiktError): string => {
+  switch (error.code) {
+    case 'auth':
+      return 'API key missing or rejected - check it in Settings.';
+    case 'rate_limited':
+      return 'Rate limited by the provider - try again in a moment.';
+    case 'no_audio':
+      return 'No speech detected - check your microphone.';
+    case 'busy':
+      return 'A dictation is already in progress.';
+    case 'provider':
+      return error.status !== undefined && error.status >= 500
+        ? `Provider error (${error.status}) - try again or switch provider.`
+        : error.message;
+    default:
+      return error.message;
+  }
+};
+
+/** Turns an error from a command or a `dictation:update` into text for the pill. */
+export const describeError = (error: unknown): string => {
+  if (isDiktError(error)) {
+    return hintFor(error);
+  }
+  if (typeof error === 'string') {
+    return error;
+  }
+  if (error instanceof Error) {
+    return error.message;
+  }
+  return String(error);
+};
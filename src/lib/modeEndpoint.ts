@@ -0,0 +1,46 @@
+import type { FormatterBackend, Mode, Settings } from '../types';
+import { ANTHROPIC_KEY_PROVIDER, FORMATTER_BACKENDS, PROVIDERS } from '../constants';
+
+export type ModeEndpoint = {
+  backend: FormatterBackend;
+  baseUrl: string;
+  apiKey: string;
+};
+
+const nonEmpty = (value: string | null | undefined): string | null => value?.trim() || null;
+
+/** Entry in provider_api_keys the mode authenticates with; null means the transcription key. */
+export const modeKeyRef = (mode: Mode): string | null =>
+  nonEmpty(mode.api_key_ref) ??
+  nonEmpty(mode.provider) ??
+  (mode.backend === 'anthropic_messages' ? ANTHROPIC_KEY_PROVIDER : null);
+
+/**
+ * Where the mode sends chat requests, resolved like `ChatEndpoint::for_mode` in the
+ * backend. Null when it simply uses the transcription provider.
+ */
+export const modeEndpoint = (settings: Settings, mode: Mode): ModeEndpoint | null => {
+  const keyRef = modeKeyRef(mode);
+  const customBaseUrl = nonEmpty(mode.base_url);
+  if (mode.backend === 'openai_compatible' && customBaseUrl === null && keyRef === null) {
+    return null;
+  }
+  const apiKey = keyRef === null
+    ? (mode.backend === 'openai_compatible' ? settings.api_key : '')
+    : keyRef === settings.provider
+      ? settings.api_key
+      : settings.provider_api_keys[keyRef] ?? '';
+  // Another provider's key must not go to the transcription server.
+  const provider = nonEmpty(mode.provider);
+  const providerBaseUrl = provider !== null && provider !== settings.provider
+    ? PROVIDERS[provider as keyof typeof PROVIDERS]?.base_url ?? ''
+    : settings.base_url;
+  return {
+    backend: mode.backend,
+    baseUrl: customBaseUrl ?? (FORMATTER_BACKENDS[mode.backend].base_url || providerBaseUrl),
+    apiKey
+  };
+};
+
+/** Cache key for the model list of an endpoint. */
+export const endpointKey = (endpoint: ModeEndpoint) => `${endpoint.backend} ${endpoint.baseUrl}`;
@@ -1,7 +1,12 @@
 export type Status = 'idle' | 'recording' | 'transcribing' | 'formatting' | 'pasting' | 'done' | 'error';
 export type Tab = 'settings' | 'dictionary' | 'history' | 'modes';
-export type Provider = 'groq' | 'openai' | 'custom';
+export type Provider = 'groq' | 'openai' | 'custom' | 'local';
 export type HotkeyMode = 'hold' | 'lock';
+export type AudioFormat = 'wav' | 'flac' | 'opus';
+export type StreamingProtocol = 'openai_realtime' | 'generic';
+export type FormatterBackend = 'openai_compatible' | 'anthropic_messages' | 'ollama';
+
+export type ModeKind = 'format' | 'transform';
 
 export type VocabularyEntry = {
   id: string;
@@ -10,11 +15,78 @@ export type VocabularyEntry = {
   enabled: boolean;
 };
 
+// Picks a mode for dictations started in matching windows; the first match wins.
+export type ModeRule = {
+  id: string;
+  // Case-insensitive substrings; an empty one matches anything.
+  app: string;
+  title: string;
+  // null pastes the raw transcript.
+  mode_id: string | null;
+};
+
+// One step of a mode pipeline; see `ModeStep` in the backend.
+export type ModeStep =
+  | { type: 'llm'; system_prompt: string; model: string | null }
+  | { type: 'vocabulary' }
+  | { type: 'regex'; pattern: string; replacement: string };
+
+export type StepOutput = {
+  step: ModeStep['type'];
+  text: string;
+  error?: string;
+};
+
+// Mode output discarded for reading like a reply; the transcript was pasted instead.
+export type RejectedFormat = {
+  text: string;
+  reason: string;
+};
+
+// A transcript and the text the mode should have made of it.
+export type ModeExample = {
+  input: string;
+  output: string;
+};
+
 export type Mode = {
   id: string;
   name: string;
   system_prompt: string;
   model: string;
+  // 'transform' applies the dictated instruction to the selected text instead.
+  kind: ModeKind;
+  backend: FormatterBackend;
+  // Own endpoint and key reference; null keeps the backend's defaults.
+  provider: string | null;
+  base_url: string | null;
+  api_key_ref: string | null;
+  // Generation parameters; null leaves the provider default.
+  temperature: number | null;
+  max_tokens: number | null;
+  top_p: number | null;
+  stop: string[];
+  json_schema: Record<string, unknown> | null;
+  // Run in order on the previous output; empty means one request with system_prompt.
+  steps: ModeStep[];
+  // Few-shot pairs sent before the transcript.
+  examples: ModeExample[];
+  // Paste the transcript instead when the output reads like a reply to it; for cleanup modes.
+  reply_check: boolean;
+};
+
+export type GenerationParams = Pick<Mode, 'temperature' | 'max_tokens' | 'top_p' | 'stop' | 'json_schema'>;
+
+export type TranscriptionEndpoint = {
+  provider: Provider;
+  base_url: string;
+  model: string;
+};
+
+export type RetryPolicy = {
+  max_attempts: number;
+  base_delay_ms: number;
+  jitter: number;
 };
 
 export type Settings = {
@@ -25,10 +97,25 @@ export type Settings = {
   hotkey_mode: HotkeyMode;
   copy_to_clipboard_on_success: boolean;
   api_key: string;
-  provider_api_keys: Partial<Record<Provider, string>>;
+  // Keyed by transcription provider, plus 'anthropic' for anthropic_messages modes.
+  provider_api_keys: Partial<Record<string, string>>;
   vocabulary: VocabularyEntry[];
   active_mode_id: string | null;
   modes: Mode[];
+  input_device: string | null;
+  target_sample_rate: number;
+  audio_format: AudioFormat;
+  streaming_enabled: boolean;
+  streaming_url: string;
+  streaming_protocol: StreamingProtocol;
+  // Empty uses the protocol's default model.
+  streaming_model: string;
+  // Entry in provider_api_keys with the streaming key; null uses the protocol's provider.
+  streaming_api_key_ref: string | null;
+  local_model_path: string;
+  transcription_fallbacks: TranscriptionEndpoint[];
+  retry_policy: RetryPolicy;
+  mode_rules: ModeRule[];
 };
 
 export type TranscriptionHistoryItem = {
@@ -37,12 +124,52 @@ export type TranscriptionHistoryItem = {
   created_at_ms: number;
   duration_secs?: number;
   language?: string;
+  mode_id?: string;
   mode_name?: string;
   original_text?: string;
+  step_outputs?: StepOutput[];
+  rejected_format?: RejectedFormat;
+  provider?: string;
+  deferred?: boolean;
+  timings_ms?: StageTimings;
+};
+
+export type Stage = 'stop' | 'upload' | 'transcribe' | 'format' | 'paste';
+
+/** Milliseconds per dictation stage; stages that did not run are missing. */
+export type StageTimings = Partial<Record<Stage, number>>;
+
+export type StageLatency = {
+  p50_ms: number;
+  p95_ms: number;
+  samples: number;
+};
+
+export type LatencySummary = { sessions: number } & Partial<Record<Stage, StageLatency | null>>;
+
+export type DiktError = {
+  code:
+    | 'auth'
+    | 'rate_limited'
+    | 'network'
+    | 'provider'
+    | 'no_audio'
+    | 'paste'
+    | 'permission'
+    | 'storage'
+    | 'busy'
+    | 'cancelled'
+    | 'other';
+  message: string;
+  status?: number;
 };
 
 export type DictationUpdate = {
-  state: 'idle' | 'recording' | 'transcribing' | 'formatting' | 'pasting' | 'done' | 'error';
+  state: 'idle' | 'recording' | 'transcribing' | 'formatting' | 'pasting' | 'done' | 'error' | 'cancelled';
   message?: string;
   text?: string;
+  partial_text?: string;
+  can_retry?: boolean;
+  error?: DiktError;
+  timings_ms?: StageTimings;
 };